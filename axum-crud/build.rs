@@ -0,0 +1,11 @@
+//! Generates the prost messages and the tonic server and client for
+//! `proto/food.proto`, with the `protoc` shipped by `protoc-bin-vendored`.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/food.proto");
+
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure().compile_protos(&["proto/food.proto"], &["proto"])?;
+
+    Ok(())
+}
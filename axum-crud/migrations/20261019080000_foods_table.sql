@@ -0,0 +1,21 @@
+-- The initial migration created `user_details` instead of the `foods_table`
+-- the application queries. Create it with the columns the code expects.
+
+CREATE TABLE IF NOT EXISTS foods_table (
+  -- Timestamps
+  cid varchar(128) NOT NULL,
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  mid varchar(128) NOT NULL,
+  mtime TIMESTAMP WITH TIME ZONE,
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 2000) PRIMARY KEY,
+
+  stamp_code varchar(128) NOT NULL UNIQUE,
+
+  food_name varchar(128) NOT NULL,
+  category varchar(128) NOT NULL,
+  stocks int NOT NULL,
+  price float NOT NULL,
+  total_quantity int NOT NULL,
+  food_status food_stat DEFAULT 'active'
+);
@@ -0,0 +1,31 @@
+-- Kitchens / branches and the stock each one holds per food.
+
+CREATE TABLE stores (
+  -- Timestamps
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  mtime TIMESTAMP WITH TIME ZONE,
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  store_name varchar(128) NOT NULL UNIQUE,
+  address varchar(256)
+);
+
+CREATE TABLE food_stocks (
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  store_id BIGINT NOT NULL REFERENCES stores(id),
+  stocks int NOT NULL DEFAULT 0 CHECK (stocks >= 0),
+
+  PRIMARY KEY (food_id, store_id)
+);
+
+CREATE TABLE stock_transfers (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  from_store_id BIGINT NOT NULL REFERENCES stores(id),
+  to_store_id BIGINT NOT NULL REFERENCES stores(id),
+  quantity int NOT NULL CHECK (quantity > 0)
+);
@@ -0,0 +1,57 @@
+-- Received batches of a food with their expiry, consumed first-expiring first.
+
+CREATE TYPE lot_stat AS ENUM('active','depleted','written off');
+
+CREATE TABLE food_lots (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  mtime TIMESTAMP WITH TIME ZONE,
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  store_id BIGINT REFERENCES stores(id),
+  lot_code varchar(128),
+
+  received_quantity int NOT NULL CHECK (received_quantity > 0),
+  quantity int NOT NULL CHECK (quantity >= 0),
+  received_date DATE NOT NULL DEFAULT current_date,
+  expiry_date DATE NOT NULL,
+  lot_status lot_stat NOT NULL DEFAULT 'active'
+);
+
+CREATE INDEX food_lots_fefo_idx ON food_lots (food_id, store_id, expiry_date)
+  WHERE lot_status = 'active';
+
+-- Ledger of every stock change, `delta` is signed.
+
+CREATE TABLE stock_movements (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  store_id BIGINT REFERENCES stores(id),
+  delta int NOT NULL,
+  reason varchar(32) NOT NULL,
+  lot_id BIGINT REFERENCES food_lots(id)
+);
+
+CREATE INDEX stock_movements_food_idx ON stock_movements (food_id, ctime);
+
+-- Orders decrement stock, line by line.
+
+CREATE TABLE orders (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  store_id BIGINT REFERENCES stores(id)
+);
+
+CREATE TABLE order_lines (
+  order_id BIGINT NOT NULL REFERENCES orders(id),
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  quantity int NOT NULL CHECK (quantity > 0),
+
+  PRIMARY KEY (order_id, food_id)
+);
@@ -0,0 +1,8 @@
+-- Base unit each food's `stocks` and `total_quantity` are counted in, and
+-- how many base units a case holds.
+
+CREATE TYPE food_unit AS ENUM('piece','g','kg','ml','litre');
+
+ALTER TABLE foods_table
+  ADD COLUMN unit food_unit NOT NULL DEFAULT 'piece',
+  ADD COLUMN case_size int CHECK (case_size > 0);
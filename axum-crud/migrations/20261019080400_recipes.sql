@@ -0,0 +1,11 @@
+-- Bill of materials: base units of each ingredient that go into one base
+-- unit of the composite food.
+
+CREATE TABLE recipe_items (
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  ingredient_id BIGINT NOT NULL REFERENCES foods_table(id),
+  quantity int NOT NULL CHECK (quantity > 0),
+
+  PRIMARY KEY (food_id, ingredient_id),
+  CHECK (food_id != ingredient_id)
+);
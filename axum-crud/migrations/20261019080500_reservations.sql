@@ -0,0 +1,21 @@
+-- Stock held for an order awaiting payment. A held reservation counts
+-- against the available stock until it is confirmed, released or expires.
+
+CREATE TYPE reservation_stat AS ENUM('held','confirmed','released','expired');
+
+CREATE TABLE reservations (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  mtime TIMESTAMP WITH TIME ZONE,
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  store_id BIGINT REFERENCES stores(id),
+  quantity int NOT NULL CHECK (quantity > 0),
+  expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  reservation_status reservation_stat NOT NULL DEFAULT 'held',
+  order_id BIGINT REFERENCES orders(id)
+);
+
+CREATE INDEX reservations_held_idx ON reservations (food_id, store_id)
+  WHERE reservation_status = 'held';
@@ -0,0 +1,14 @@
+-- Responses of mutating requests sent with an `Idempotency-Key` header,
+-- replayed when the same request is retried.
+
+CREATE TABLE idempotency_keys (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  idempotency_key varchar(255) PRIMARY KEY,
+  fingerprint char(64) NOT NULL,
+  completed boolean NOT NULL DEFAULT false,
+  response_status int,
+  response_headers jsonb,
+  response_body bytea,
+  expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
@@ -0,0 +1,16 @@
+-- Keys for the `Authorization: Bearer` / `X-Api-Key` headers. Only the
+-- SHA-256 of a key is stored, it is shown once when issued.
+
+CREATE TABLE api_keys (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  key_name varchar(128) NOT NULL,
+  key_prefix varchar(16) NOT NULL,
+  key_hash char(64) NOT NULL UNIQUE,
+  scopes text[] NOT NULL CHECK (cardinality(scopes) > 0 AND scopes <@ ARRAY['read','write','admin']),
+  expires_at TIMESTAMP WITH TIME ZONE,
+  revoked_at TIMESTAMP WITH TIME ZONE,
+  last_used_at TIMESTAMP WITH TIME ZONE
+);
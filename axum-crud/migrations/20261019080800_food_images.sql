@@ -0,0 +1,16 @@
+-- Pictures of foods. The files live in the `IMAGE_DIR` store, keyed by id,
+-- along with thumbnails at fixed sizes.
+
+CREATE TABLE food_images (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  content_type varchar(32) NOT NULL,
+  byte_size int NOT NULL,
+  width int NOT NULL,
+  height int NOT NULL
+);
+
+CREATE INDEX food_images_food_idx ON food_images (food_id);
@@ -0,0 +1,5 @@
+-- Listings page on the raw bytes of `cid`, a UUIDv7 stored as base64,
+-- which sort in creation order where the text does not.
+
+CREATE INDEX foods_cid_order_idx ON foods_table ((decode(cid || '==', 'base64')) DESC)
+  WHERE food_status != 'removed';
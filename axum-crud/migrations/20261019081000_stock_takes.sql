@@ -0,0 +1,35 @@
+-- Stock-take (cycle count) sessions. Counts are collected while a session
+-- is open, each with the stock expected at count time; approving it applies
+-- every line's variance to the stock and keeps it.
+
+CREATE TYPE stock_take_stat AS ENUM('open','approved','cancelled');
+
+CREATE TABLE stock_takes (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  mtime TIMESTAMP WITH TIME ZONE,
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  store_id BIGINT REFERENCES stores(id),
+  category varchar(128),
+  note text,
+  stock_take_status stock_take_stat NOT NULL DEFAULT 'open',
+  opened_by varchar(128),
+  closed_by varchar(128),
+  closed_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE TABLE stock_take_counts (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  mtime TIMESTAMP WITH TIME ZONE,
+
+  stock_take_id BIGINT NOT NULL REFERENCES stock_takes(id),
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  counted int NOT NULL CHECK (counted >= 0),
+  -- Stock when the line was counted.
+  expected int,
+  -- Filled in on approval.
+  variance int,
+
+  PRIMARY KEY (stock_take_id, food_id)
+);
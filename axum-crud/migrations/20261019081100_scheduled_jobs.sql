@@ -0,0 +1,42 @@
+-- Background jobs: run history, prices taking effect later and the time a
+-- food was removed, so removed foods can be purged after a while.
+
+CREATE TYPE job_run_stat AS ENUM('running','succeeded','failed');
+
+CREATE TABLE job_runs (
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  job_name varchar(64) NOT NULL,
+  -- The schedule's tick, a replica finding it taken skips it.
+  scheduled_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  job_run_status job_run_stat NOT NULL DEFAULT 'running',
+  started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  finished_at TIMESTAMP WITH TIME ZONE,
+  detail text,
+  error text
+);
+
+CREATE INDEX job_runs_job_idx ON job_runs (job_name, id DESC);
+CREATE UNIQUE INDEX job_runs_tick_idx ON job_runs (job_name, scheduled_at);
+
+CREATE TABLE scheduled_prices (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  food_id BIGINT NOT NULL REFERENCES foods_table(id),
+  price float NOT NULL CHECK (price >= 0),
+  effective_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  -- Set once the price is applied, or superseded by a later one that was
+  -- due at the same time.
+  applied_at TIMESTAMP WITH TIME ZONE,
+  created_by varchar(128)
+);
+
+CREATE INDEX scheduled_prices_due_idx ON scheduled_prices (effective_at)
+  WHERE applied_at IS NULL;
+
+ALTER TABLE foods_table ADD COLUMN removed_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE foods_table SET removed_at = coalesce(mtime, ctime)
+  WHERE food_status = 'removed';
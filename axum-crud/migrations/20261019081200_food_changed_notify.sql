@@ -0,0 +1,18 @@
+-- Announces every change to a food or its per-store stock on the
+-- `food_changed` channel, with the food id as payload, so each replica can
+-- drop what it cached of that food.
+
+CREATE FUNCTION notify_food_changed() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_notify('food_changed', to_jsonb(COALESCE(NEW, OLD)) ->> TG_ARGV[0]);
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER foods_table_changed
+  AFTER INSERT OR UPDATE OR DELETE ON foods_table
+  FOR EACH ROW EXECUTE FUNCTION notify_food_changed('id');
+
+CREATE TRIGGER food_stocks_changed
+  AFTER INSERT OR UPDATE OR DELETE ON food_stocks
+  FOR EACH ROW EXECUTE FUNCTION notify_food_changed('food_id');
@@ -0,0 +1,102 @@
+-- Outbound webhooks. Subscriptions name the events they want; a trigger
+-- queues one delivery per matching subscription in the transaction that
+-- changed the food, and the dispatcher sends them from `webhook_deliveries`.
+
+CREATE TABLE webhook_subscriptions (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  url text NOT NULL,
+  -- Kept as is, deliveries are signed with it.
+  secret text NOT NULL,
+  events text[] NOT NULL CHECK (cardinality(events) > 0 AND events <@ ARRAY['food.created','food.updated','food.removed','food.out_of_stock'])
+);
+
+CREATE TYPE webhook_delivery_stat AS ENUM('pending','delivered','dead');
+
+CREATE TABLE webhook_deliveries (
+  ctime TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+
+  subscription_id BIGINT NOT NULL REFERENCES webhook_subscriptions (id) ON DELETE CASCADE,
+  -- Shared by the deliveries of one event, receivers dedupe on it.
+  event_id uuid NOT NULL,
+  event_type text NOT NULL,
+  payload jsonb NOT NULL,
+
+  delivery_status webhook_delivery_stat NOT NULL DEFAULT 'pending',
+  attempts integer NOT NULL DEFAULT 0,
+  -- Also pushed ahead while an attempt is in flight, so another replica
+  -- does not pick the delivery up.
+  next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  last_attempt_at TIMESTAMP WITH TIME ZONE,
+  last_status_code integer,
+  last_error text,
+  delivered_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE delivery_status = 'pending';
+CREATE INDEX webhook_deliveries_subscription_idx ON webhook_deliveries (subscription_id, id DESC);
+
+CREATE FUNCTION queue_webhook(food_id bigint, store_id bigint, event_type text) RETURNS void AS $$
+DECLARE
+  event_id uuid := gen_random_uuid();
+BEGIN
+  INSERT INTO webhook_deliveries (subscription_id, event_id, event_type, payload)
+  SELECT s.id, event_id, event_type, jsonb_build_object(
+      'id', event_id,
+      'type', event_type,
+      'occurred_at', to_char(now() at time zone 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"Z"'),
+      'data', jsonb_build_object(
+        'id', f.id,
+        'stamp_code', f.stamp_code,
+        'food_name', f.food_name,
+        'category', f.category,
+        'store_id', store_id,
+        'stocks', CASE WHEN store_id IS NULL THEN f.stocks ELSE (SELECT stocks FROM food_stocks WHERE food_stocks.food_id = f.id AND food_stocks.store_id = queue_webhook.store_id) END,
+        'price', f.price,
+        'unit', f.unit,
+        'food_status', f.food_status))
+  FROM webhook_subscriptions s
+  JOIN foods_table f ON f.id = food_id
+  WHERE event_type = ANY(s.events);
+END;
+$$ LANGUAGE plpgsql;
+
+-- created, removed, updated and the food's own stock running out.
+CREATE FUNCTION foods_table_webhooks() RETURNS trigger AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    PERFORM queue_webhook(NEW.id, NULL, 'food.created');
+  ELSIF NEW.food_status = 'removed' AND OLD.food_status != 'removed' THEN
+    PERFORM queue_webhook(NEW.id, NULL, 'food.removed');
+  ELSIF (NEW.food_name, NEW.category, NEW.stocks, NEW.price, NEW.total_quantity, NEW.unit, NEW.case_size, NEW.food_status)
+      IS DISTINCT FROM (OLD.food_name, OLD.category, OLD.stocks, OLD.price, OLD.total_quantity, OLD.unit, OLD.case_size, OLD.food_status) THEN
+    PERFORM queue_webhook(NEW.id, NULL, 'food.updated');
+    IF NEW.stocks <= 0 AND OLD.stocks > 0 THEN
+      PERFORM queue_webhook(NEW.id, NULL, 'food.out_of_stock');
+    END IF;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER foods_table_webhooks
+  AFTER INSERT OR UPDATE ON foods_table
+  FOR EACH ROW EXECUTE FUNCTION foods_table_webhooks();
+
+-- A store's stock of the food running out.
+CREATE FUNCTION food_stocks_webhooks() RETURNS trigger AS $$
+BEGIN
+  IF NEW.stocks <= 0 AND OLD.stocks > 0 THEN
+    PERFORM queue_webhook(NEW.food_id, NEW.store_id, 'food.out_of_stock');
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER food_stocks_webhooks
+  AFTER UPDATE OF stocks ON food_stocks
+  FOR EACH ROW EXECUTE FUNCTION food_stocks_webhooks();
@@ -0,0 +1,12 @@
+-- Idempotency keys belong to the API key that sent them, 0 standing for
+-- `API_ADMIN_KEY`. A claimed key is held until `locked_until`, a request
+-- that never completed frees it for a retry once that passes.
+
+ALTER TABLE idempotency_keys
+  ADD COLUMN api_key_id BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN locked_until TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
+
+ALTER TABLE idempotency_keys DROP CONSTRAINT idempotency_keys_pkey;
+ALTER TABLE idempotency_keys ADD PRIMARY KEY (api_key_id, idempotency_key);
+
+CREATE INDEX idempotency_keys_expires_idx ON idempotency_keys (expires_at);
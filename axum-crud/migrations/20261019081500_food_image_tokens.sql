@@ -0,0 +1,5 @@
+-- Images are served without an API key, under a random token instead of
+-- their id, so that only clients given a food's image URLs can load them.
+
+ALTER TABLE food_images
+  ADD COLUMN public_token varchar(32) NOT NULL UNIQUE DEFAULT replace(gen_random_uuid()::text, '-', '');
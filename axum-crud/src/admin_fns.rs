@@ -0,0 +1,479 @@
+//! Maintenance tasks behind the `axum-crud-admin` CLI.
+//!
+//! They go through the same controllers as the API, so unit checks,
+//! reservations and the `stock_movements` ledger apply to them as they do
+//! to requests.
+
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use time::format_description::well_known::Rfc3339;
+use tracing::{debug, warn};
+
+use crate::{
+    crud_fns::{FoodModelController, FoodRelations, FoodToCreate, ModelController},
+    error::{Error, Result},
+    images_fns::file_names,
+    metrics::metrics,
+    pagination::{PageParams, MAX_PAGE_SIZE},
+    stock_fns::{put_stock, record_movement, take_stock},
+    store::MIGRATOR,
+    stores_fns::{StoreModelController, StoreToCreate},
+    units::Unit,
+};
+
+#[derive(Clone, Debug)]
+pub struct AdminModelController;
+
+/// Ledger reasons written by the application itself. Manual adjustments
+/// may not pose as one of them, reorder usage is read from `order` and
+/// `produce`.
+const RESERVED_REASONS: [&str; 5] = ["receive", "waste", "order", "produce", "stock_take"];
+
+/// `stock_movements.reason` is a `varchar(32)`.
+const MAX_REASON_LEN: usize = 32;
+
+/// Name, category, stock, price, total quantity, unit and case size.
+type DemoFood = (&'static str, &'static str, i32, f32, i32, Unit, Option<i32>);
+
+const DEMO_FOODS: [DemoFood; 6] = [
+    (
+        "Jasmine rice",
+        "grains",
+        25_000,
+        0.004,
+        25_000,
+        Unit::G,
+        Some(5_000),
+    ),
+    (
+        "Whole milk",
+        "dairy",
+        12_000,
+        0.002,
+        12_000,
+        Unit::Ml,
+        Some(1_000),
+    ),
+    ("Eggs", "dairy", 60, 0.3, 60, Unit::Piece, Some(30)),
+    ("Tomatoes", "produce", 8, 2.5, 8, Unit::Kg, None),
+    ("Olive oil", "pantry", 3_000, 0.01, 3_000, Unit::Ml, None),
+    ("Flour", "baking", 0, 1.2, 0, Unit::Kg, Some(25)),
+];
+
+/// A row of an import file. Columns are matched by header, others (like
+/// `id` of an export) are ignored. A `stamp_code` must not belong to a
+/// food already, the imported food gets a new one.
+#[derive(Debug, Deserialize)]
+struct FoodRow {
+    stamp_code: Option<String>,
+    food_name: String,
+    category: String,
+    stocks: i32,
+    price: f32,
+    total_quantity: i32,
+    unit: Option<Unit>,
+    case_size: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportRow {
+    id: i64,
+    stamp_code: String,
+    food_name: String,
+    category: String,
+    stocks: i32,
+    price: f64,
+    total_quantity: i32,
+    unit: Unit,
+    case_size: Option<i32>,
+    created_at: String,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct CategoryStock {
+    pub category: String,
+    pub foods: i64,
+    pub total_stocks: i64,
+    pub out_of_stock: i64,
+}
+
+impl AdminModelController {
+    /// Applies the migrations not yet recorded in `_sqlx_migrations`.
+    pub async fn migrate(mm: ModelController) -> Result<()> {
+        debug!("{:<12} - migrate", "HANDLER");
+
+        MIGRATOR
+            .run(mm.db()?)
+            .await
+            .map_err(|err| Error::MigrationFailed(err.to_string()))
+    }
+
+    /// Creates a demo store and a handful of foods. Does nothing when foods
+    /// exist, unless `force` is set. Returns the number of foods created.
+    pub async fn seed(mm: ModelController, force: bool) -> Result<usize> {
+        debug!("{:<12} - seed", "HANDLER");
+
+        if !force
+            && FoodModelController::inventory_stats(mm.clone(), None)
+                .await?
+                .total
+                > 0
+        {
+            return Ok(0);
+        }
+
+        StoreModelController::create(
+            mm.clone(),
+            StoreToCreate {
+                store_name: String::from("Demo kitchen"),
+                address: None,
+            },
+        )
+        .await?;
+
+        for (food_name, category, stocks, price, total_quantity, unit, case_size) in DEMO_FOODS {
+            let data = FoodToCreate {
+                food_name: food_name.to_string(),
+                category: category.to_string(),
+                stocks,
+                price,
+                total_quantity,
+                unit,
+                case_size,
+            };
+            FoodModelController::create(mm.clone(), data, None).await?;
+        }
+
+        Ok(DEMO_FOODS.len())
+    }
+
+    /// Creates a food per CSV row, quantities in the row's unit, all or
+    /// none. Every row is checked first, a row carrying the stamp code of
+    /// an existing food is rejected so that re-importing an export does
+    /// not duplicate it. Returns the number of foods created.
+    pub async fn import_csv(mm: ModelController, reader: impl Read) -> Result<usize> {
+        debug!("{:<12} - import_csv", "HANDLER");
+
+        let mut foods = Vec::new();
+        for row in csv::Reader::from_reader(reader).deserialize::<FoodRow>() {
+            let row = row.map_err(|err| Error::CsvFailed(err.to_string()))?;
+            let unit = row.unit.unwrap_or_default();
+            unit.check_base()
+                .and_then(|_| Unit::check_case_size(row.case_size))
+                .map_err(|err| Error::CsvFailed(format!("{}: {err}", row.food_name)))?;
+
+            if let Some(stamp_code) = row.stamp_code.filter(|code| !code.is_empty()) {
+                match mm.foods().get_by_stamp_code(stamp_code.clone(), None).await {
+                    Ok(_) => {
+                        return Err(Error::CsvFailed(format!(
+                            "{}: stamp code {stamp_code} exists, clear it to import the row as a new food",
+                            row.food_name
+                        )))
+                    }
+                    Err(Error::FoodStampCodeNotFound(_)) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            foods.push(FoodToCreate {
+                food_name: row.food_name,
+                category: row.category,
+                stocks: row.stocks,
+                price: row.price,
+                total_quantity: row.total_quantity,
+                unit,
+                case_size: row.case_size,
+            });
+        }
+
+        let ids = FoodModelController::create_many(mm, foods).await?;
+        Ok(ids.len())
+    }
+
+    /// Writes the foods not removed as CSV, newest first, in the format
+    /// `import_csv` reads. Returns the number of foods written.
+    pub async fn export_csv(
+        mm: ModelController,
+        store_id: Option<i64>,
+        writer: impl Write,
+    ) -> Result<usize> {
+        debug!("{:<12} - export_csv", "HANDLER");
+
+        let mut csv = csv::Writer::from_writer(writer);
+        let mut page = PageParams {
+            limit: Some(MAX_PAGE_SIZE),
+            cursor: None,
+        };
+        let mut count = 0;
+
+        loop {
+            let foods =
+                FoodModelController::select(mm.clone(), store_id, None, &page, FoodRelations::NONE)
+                    .await?;
+
+            for food in foods.items {
+                csv.serialize(ExportRow {
+                    id: food.id,
+                    stamp_code: food.stamp_code,
+                    food_name: food.food_name,
+                    category: food.category,
+                    stocks: food.stocks,
+                    price: food.price,
+                    total_quantity: food.total_quantity,
+                    unit: food.unit,
+                    case_size: food.case_size,
+                    created_at: food.created_at.format(&Rfc3339).unwrap_or_default(),
+                })
+                .map_err(|err| Error::CsvFailed(err.to_string()))?;
+                count += 1;
+            }
+
+            match foods.next_cursor {
+                Some(cursor) => page.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        csv.flush()
+            .map_err(|err| Error::CsvFailed(err.to_string()))?;
+        Ok(count)
+    }
+
+    /// Adds `delta` to the stock, or takes it out when negative, and records
+    /// the change in the ledger under `reason`. Stock held by reservations
+    /// cannot be taken. Returns the new stock.
+    pub async fn adjust_stock(
+        mm: ModelController,
+        food_id: i64,
+        store_id: Option<i64>,
+        delta: i32,
+        reason: &str,
+    ) -> Result<i32> {
+        debug!("{:<12} - adjust_stock", "HANDLER");
+
+        let reason = reason.trim();
+        check_reason(reason)?;
+        if delta == 0 {
+            return Err(Error::InvalidStockAdjustment(String::from(
+                "delta must not be zero",
+            )));
+        }
+
+        let timer = metrics().query_timer("adjust_stock");
+        let res = async {
+            let mut tx = mm
+                .db()?
+                .begin()
+                .await
+                .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+            let stocks = if delta > 0 {
+                put_stock(&mut tx, food_id, store_id, delta).await?
+            } else {
+                take_stock(&mut tx, food_id, store_id, -delta).await?
+            };
+            record_movement(&mut tx, food_id, store_id, delta, reason, None).await?;
+
+            tx.commit()
+                .await
+                .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+            Ok(stocks)
+        }
+        .await;
+        timer.observe(res)
+    }
+
+    /// Foods, their summed stock and foods out of stock per category,
+    /// optionally in one store.
+    pub async fn stock_by_category(
+        mm: ModelController,
+        store_id: Option<i64>,
+    ) -> Result<Vec<CategoryStock>> {
+        debug!("{:<12} - stock_by_category", "HANDLER");
+
+        let timer = metrics().query_timer("stock_by_category");
+        let query = "select f.category, count(*) as foods, coalesce(sum(x.stocks), 0)::bigint as total_stocks, count(*) filter (where x.stocks <= 0) as out_of_stock from foods_table f left join food_stocks s on s.food_id = f.id and s.store_id = $1 cross join lateral (select case when $1::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks) x where f.food_status != 'removed' group by f.category order by f.category";
+
+        let res = sqlx::query_as::<_, CategoryStock>(query)
+            .bind(store_id)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - stock_by_category error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// Deletes foods removed more than `after_days` ago, with their stock
+    /// rows, scheduled prices and images. Foods that appear in orders, the
+    /// ledger, lots, transfers, recipes, reservations or stock takes keep
+    /// their `removed` row so that history stays whole. Returns the number
+    /// of foods deleted.
+    pub async fn purge_removed_foods(mm: &ModelController, after_days: u32) -> Result<u64> {
+        let timer = metrics().query_timer("purge_removed_foods");
+        let res = async {
+            let mut tx = mm
+                .db()?
+                .begin()
+                .await
+                .map_err(|err| Error::DeleteFailed(err.to_string()))?;
+
+            let query = "select id from foods_table f where food_status = 'removed' and removed_at < now() - make_interval(days => $1) and not exists (select 1 from stock_movements where food_id = f.id) and not exists (select 1 from order_lines where food_id = f.id) and not exists (select 1 from food_lots where food_id = f.id) and not exists (select 1 from stock_transfers where food_id = f.id) and not exists (select 1 from recipe_items where food_id = f.id or ingredient_id = f.id) and not exists (select 1 from reservations where food_id = f.id) and not exists (select 1 from stock_take_counts where food_id = f.id) for update skip locked";
+            let ids = sqlx::query_scalar::<_, i64>(query)
+                .bind(after_days as i32)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|err| Error::DeleteFailed(err.to_string()))?;
+            if ids.is_empty() {
+                return Ok((0, Vec::new()));
+            }
+
+            for query in [
+                "delete from food_stocks where food_id = any($1)",
+                "delete from scheduled_prices where food_id = any($1)",
+            ] {
+                sqlx::query(query)
+                    .bind(&ids)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|err| Error::DeleteFailed(err.to_string()))?;
+            }
+            let images = sqlx::query_as::<_, (i64, String)>(
+                "delete from food_images where food_id = any($1) returning id, content_type",
+            )
+            .bind(&ids)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|err| Error::DeleteFailed(err.to_string()))?;
+            let purged = sqlx::query("delete from foods_table where id = any($1)")
+                .bind(&ids)
+                .execute(&mut *tx)
+                .await
+                .map_err(|err| Error::DeleteFailed(err.to_string()))?
+                .rows_affected();
+
+            tx.commit()
+                .await
+                .map_err(|err| Error::DeleteFailed(err.to_string()))?;
+            Ok((purged, images))
+        }
+        .await
+        .inspect_err(|_| debug!("{:<12} - purge_removed_foods error", "ERROR_CONTROLLER"));
+
+        // -- The rows are gone, leftover files are only wasted space.
+        let res = match res {
+            Ok((purged, images)) => {
+                for (id, content_type) in images {
+                    for name in file_names(id, &content_type) {
+                        if let Err(err) = mm.images().delete(&name).await {
+                            warn!("{:<12} - removing {name} failed {err:?}", "IMAGES");
+                        }
+                    }
+                }
+                Ok(purged)
+            }
+            Err(err) => Err(err),
+        };
+        timer.observe(res)
+    }
+}
+
+fn check_reason(reason: &str) -> Result<()> {
+    if reason.is_empty() || reason.len() > MAX_REASON_LEN {
+        return Err(Error::InvalidStockAdjustment(format!(
+            "reason must be 1 to {MAX_REASON_LEN} characters"
+        )));
+    }
+    if RESERVED_REASONS.contains(&reason) {
+        return Err(Error::InvalidStockAdjustment(format!(
+            "reason {reason} is recorded by the application itself"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_import_export_csv() {
+        let mm = ModelController::in_memory();
+        let csv = "food_name,category,stocks,price,total_quantity,unit,case_size\n\
+                   \"Flour, bread\",baking,20,1.5,20,kg,25\n\
+                   Eggs,dairy,12,0.3,12,,\n";
+
+        let count = AdminModelController::import_csv(mm.clone(), csv.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let mut out = Vec::new();
+        let count = AdminModelController::export_csv(mm.clone(), None, &mut out)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // -- Its foods exist, so an export does not import again.
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("id,stamp_code,food_name,category,stocks,"));
+        assert!(out.contains(",\"Flour, bread\",baking,20,1.5,20,kg,25,"));
+        let res = AdminModelController::import_csv(mm.clone(), out.as_bytes()).await;
+        assert!(matches!(res, Err(Error::CsvFailed(_))));
+
+        let stats = FoodModelController::inventory_stats(mm, None)
+            .await
+            .unwrap();
+        assert_eq!(stats.total, 2);
+    }
+
+    #[sqlx::test]
+    async fn import_csv_creates_all_rows_or_none(db: PgPool) {
+        let mm = ModelController::with_db(db);
+        // -- The second name is longer than `food_name` takes.
+        let csv = format!(
+            "food_name,category,stocks,price,total_quantity,unit,case_size\n\
+             Eggs,dairy,12,0.3,12,piece,\n\
+             {},dairy,1,1,1,piece,\n",
+            "x".repeat(200)
+        );
+
+        let res = AdminModelController::import_csv(mm.clone(), csv.as_bytes()).await;
+        assert!(matches!(res, Err(Error::CreateFailed(_))));
+
+        let stats = FoodModelController::inventory_stats(mm, None)
+            .await
+            .unwrap();
+        assert_eq!(stats.total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_checks_every_row_first() {
+        let mm = ModelController::in_memory();
+        let csv = "food_name,category,stocks,price,total_quantity,unit,case_size\n\
+                   Eggs,dairy,12,0.3,12,piece,\n\
+                   Beer,drinks,2,1,2,case,\n";
+
+        let res = AdminModelController::import_csv(mm.clone(), csv.as_bytes()).await;
+        assert!(matches!(res, Err(Error::CsvFailed(_))));
+
+        let stats = FoodModelController::inventory_stats(mm, None)
+            .await
+            .unwrap();
+        assert_eq!(stats.total, 0);
+    }
+
+    #[test]
+    fn test_check_reason() {
+        assert!(check_reason("damaged in transit").is_ok());
+        assert!(check_reason("").is_err());
+        assert!(check_reason("order").is_err());
+        assert!(check_reason(&"x".repeat(MAX_REASON_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use base58::ToBase58;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::prelude::FromRow;
+use tracing::debug;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+};
+
+/// API keys are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct ApiKeyModelController;
+
+/// What a key may do. Each scope includes the ones before it, so `admin`
+/// can also write and read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    fn parse(scope: &str) -> Option<Scope> {
+        match scope {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyToCreate {
+    pub key_name: String,
+    pub scopes: Vec<Scope>,
+    /// Never expires when absent.
+    pub ttl_sec: Option<u64>,
+}
+
+/// A key as listed, without the secret.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct ApiKeyToSelect {
+    pub id: i64,
+    pub key_name: String,
+    /// First characters of the key, to tell keys apart.
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    /// RFC 3339, UTC.
+    pub expires_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: String,
+}
+
+/// A freshly issued key. `api_key` is not stored and cannot be shown again.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ApiKeyIssued {
+    pub api_key: String,
+    #[serde(flatten)]
+    pub key: ApiKeyToSelect,
+}
+
+/// The key a request was authenticated with, kept in the request
+/// extensions. `id` is absent for the bootstrap `API_ADMIN_KEY`.
+#[derive(Clone, Debug)]
+pub struct ApiKeyPrincipal {
+    pub id: Option<i64>,
+    pub key_name: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiKeyPrincipal {
+    pub fn allows(&self, needed: Scope) -> bool {
+        self.scopes.iter().any(|scope| *scope >= needed)
+    }
+}
+
+const KEY_PREFIX_LEN: usize = 12;
+
+/// Seconds between `last_used_at` writes for the same key.
+const LAST_USED_RESOLUTION_SEC: f64 = 60.0;
+
+const API_KEY_COLUMNS: &str = "id, key_name, key_prefix, scopes, to_char(expires_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as expires_at, to_char(revoked_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as revoked_at, to_char(last_used_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as last_used_at, to_char(ctime at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as created_at";
+
+/// SHA-256 of a key, hex encoded. Keys are random, so no salt is needed.
+pub fn hash_api_key(api_key: &str) -> String {
+    data_encoding::HEXLOWER.encode(&Sha256::digest(api_key.as_bytes()))
+}
+
+/// `ack_` followed by two v4 UUIDs, 244 random bits as each UUID fixes 6
+/// version and variant bits.
+fn new_api_key() -> String {
+    let mut secret = [0u8; 32];
+    secret[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    secret[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+
+    format!("ack_{}", secret.to_base58())
+}
+
+impl ApiKeyModelController {
+    pub async fn issue(mm: ModelController, data: ApiKeyToCreate) -> Result<ApiKeyIssued> {
+        debug!("{:<12} - issue_api_key", "HANDLER");
+
+        if data.key_name.trim().is_empty() {
+            return Err(Error::InvalidApiKey(String::from("key_name is required")));
+        }
+        if data.scopes.is_empty() {
+            return Err(Error::InvalidApiKey(String::from(
+                "at least one scope is required",
+            )));
+        }
+        if data.ttl_sec == Some(0) {
+            return Err(Error::InvalidApiKey(String::from(
+                "ttl_sec must be positive",
+            )));
+        }
+
+        let timer = metrics().query_timer("issue_api_key");
+        let api_key = new_api_key();
+        let scopes: Vec<&str> = data.scopes.iter().map(|scope| scope.as_str()).collect();
+        let query = format!("insert into api_keys (key_name, key_prefix, key_hash, scopes, expires_at) values ($1, $2, $3, $4, now() + make_interval(secs => $5)) returning {API_KEY_COLUMNS}");
+
+        let res = sqlx::query_as::<_, ApiKeyToSelect>(&query)
+            .bind(data.key_name.trim())
+            .bind(&api_key[..KEY_PREFIX_LEN])
+            .bind(hash_api_key(&api_key))
+            .bind(scopes)
+            .bind(data.ttl_sec.map(|ttl| ttl as f64))
+            .fetch_one(mm.db()?)
+            .await
+            .map(|key| ApiKeyIssued { api_key, key })
+            .map_err(|err| {
+                debug!("{:<12} - issue_api_key error", "ERROR_CONTROLLER");
+                Error::CreateFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    pub async fn select(mm: ModelController) -> Result<Vec<ApiKeyToSelect>> {
+        debug!("{:<12} - select_api_keys", "HANDLER");
+
+        let timer = metrics().query_timer("select_api_keys");
+        let query = format!("select {API_KEY_COLUMNS} from api_keys order by id");
+
+        let res = sqlx::query_as::<_, ApiKeyToSelect>(&query)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - select_api_keys error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    pub async fn revoke(mm: ModelController, id: i64) -> Result<ApiKeyToSelect> {
+        debug!("{:<12} - revoke_api_key", "HANDLER");
+
+        let timer = metrics().query_timer("revoke_api_key");
+        let query = format!("update api_keys set revoked_at = coalesce(revoked_at, now()) where id = $1 returning {API_KEY_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, ApiKeyToSelect>(&query)
+            .bind(id)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(key)) => Ok(key),
+            Ok(None) => Err(Error::ApiKeyNotFound(format!("no api key with id {id}"))),
+            Err(err) => {
+                debug!("{:<12} - revoke_api_key error", "ERROR_CONTROLLER");
+                Err(Error::UpdateFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+
+    /// Looks up a live key and notes that it was used. `None` when the key
+    /// is unknown, revoked or expired.
+    pub async fn authenticate(
+        mm: &ModelController,
+        api_key: &str,
+    ) -> Result<Option<ApiKeyPrincipal>> {
+        let timer = metrics().query_timer("authenticate_api_key");
+        timer.observe(authenticate(mm, api_key).await)
+    }
+}
+
+async fn authenticate(mm: &ModelController, api_key: &str) -> Result<Option<ApiKeyPrincipal>> {
+    let db = mm.db()?;
+
+    let query = "select id, key_name, scopes from api_keys where key_hash = $1 and revoked_at is null and (expires_at is null or expires_at > now())";
+    let key = sqlx::query_as::<_, (i64, String, Vec<String>)>(query)
+        .bind(hash_api_key(api_key))
+        .fetch_optional(db)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - authenticate_api_key error", "ERROR_CONTROLLER");
+            Error::SelectFailed(err.to_string())
+        })?;
+    let Some((id, key_name, scopes)) = key else {
+        return Ok(None);
+    };
+
+    // -- Coarse on purpose, so busy keys do not write on every request.
+    let query = "update api_keys set last_used_at = now() where id = $1 and (last_used_at is null or last_used_at < now() - make_interval(secs => $2))";
+    sqlx::query(query)
+        .bind(id)
+        .bind(LAST_USED_RESOLUTION_SEC)
+        .execute(db)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - authenticate_api_key error", "ERROR_CONTROLLER");
+            Error::UpdateFailed(err.to_string())
+        })?;
+
+    Ok(Some(ApiKeyPrincipal {
+        id: Some(id),
+        key_name,
+        scopes: scopes
+            .iter()
+            .filter_map(|scope| Scope::parse(scope))
+            .collect(),
+    }))
+}
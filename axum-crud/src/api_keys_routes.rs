@@ -0,0 +1,122 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    api_keys_fns::{ApiKeyIssued, ApiKeyModelController, ApiKeyToCreate, ApiKeyToSelect, Scope},
+    auth::mw_require_admin,
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::Result,
+};
+
+pub fn routes_api_keys(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/admin/api-keys",
+            get(api_v1_list_api_keys).post(api_v1_issue_api_key),
+        )
+        .route("/api/v1/admin/api-keys/:id", delete(api_v1_revoke_api_key))
+        .route_layer(middleware::from_fn_with_state(mm.clone(), mw_require_admin))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct IssueApiKeyPayload {
+    key_name: String,
+    scopes: Vec<Scope>,
+    /// Seconds until the key expires, never when absent.
+    ttl_sec: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/api-keys",
+    tag = "api-keys",
+    responses(
+        (status = 200, description = "Issued keys, without their secrets", body = DataBody<Vec<ApiKeyToSelect>>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+    )
+)]
+async fn api_v1_list_api_keys(
+    State(mm): State<ModelController>,
+) -> Result<Json<DataBody<Vec<ApiKeyToSelect>>>> {
+    debug!("{:<12} - api_v1_list_api_keys", "ROUTE_HANDLER");
+
+    let keys = ApiKeyModelController::select(mm).await?;
+    let body = Json(DataBody::new(keys));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/api-keys",
+    tag = "api-keys",
+    request_body = IssueApiKeyPayload,
+    responses(
+        (status = 201, description = "Key issued, `api_key` is shown only this once", body = DataBody<ApiKeyIssued>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+        (status = 400, description = "Invalid name or scopes"),
+    )
+)]
+async fn api_v1_issue_api_key(
+    State(mm): State<ModelController>,
+    Json(body): Json<IssueApiKeyPayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_issue_api_key", "ROUTE_HANDLER");
+
+    let IssueApiKeyPayload {
+        key_name,
+        scopes,
+        ttl_sec,
+    } = body;
+    let data = ApiKeyToCreate {
+        key_name,
+        scopes,
+        ttl_sec,
+    };
+
+    let issued = ApiKeyModelController::issue(mm, data).await?;
+    let body = Json(DataBody::new(issued));
+
+    // The secret must not end up in shared caches.
+    Ok((
+        StatusCode::CREATED,
+        [(header::CACHE_CONTROL, "no-store")],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/api-keys/{id}",
+    tag = "api-keys",
+    params(("id" = i64, Path, description = "API key id")),
+    responses(
+        (status = 200, description = "Key revoked, it is rejected from now on", body = DataBody<ApiKeyToSelect>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+        (status = 404, description = "API key not found"),
+    )
+)]
+async fn api_v1_revoke_api_key(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<ApiKeyToSelect>>> {
+    debug!("{:<12} - api_v1_revoke_api_key", "ROUTE_HANDLER");
+
+    let key = ApiKeyModelController::revoke(mm, id).await?;
+    let body = Json(DataBody::new(key));
+    Ok(body)
+}
@@ -0,0 +1,185 @@
+//! API-key authentication.
+//!
+//! A key is sent as `Authorization: Bearer <key>` or `X-Api-Key: <key>`.
+//! Reads need the `read` scope, any other method `write`, and the key
+//! admin routes `admin`. `API_ADMIN_KEY`, when not empty, is accepted as an
+//! admin key so the first keys can be issued.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use tracing::debug;
+
+use crate::{
+    api_keys_fns::{hash_api_key, ApiKeyModelController, ApiKeyPrincipal, Scope},
+    config::core_config,
+    crud_fns::ModelController,
+    error::Result,
+};
+
+pub const X_API_KEY: &str = "x-api-key";
+
+/// Requires `read` for safe methods and `write` for the rest.
+pub async fn mw_require_api_key(
+    State(mm): State<ModelController>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let needed = match *req.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => Scope::Read,
+        _ => Scope::Write,
+    };
+
+    require(mm, needed, req, next).await
+}
+
+/// Read scope whatever the method, for endpoints that check write access
+/// per operation, like `/graphql`.
+pub async fn mw_require_read(
+    State(mm): State<ModelController>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    require(mm, Scope::Read, req, next).await
+}
+
+pub async fn mw_require_admin(
+    State(mm): State<ModelController>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    require(mm, Scope::Admin, req, next).await
+}
+
+async fn require(
+    mm: ModelController,
+    needed: Scope,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    debug!("{:<12} - require {}", "MIDDLEWARE", needed.as_str());
+
+    let Some(api_key) = api_key_from(req.headers()) else {
+        return unauthorized("missing API key");
+    };
+
+    let principal = match principal_for(&mm, &api_key).await {
+        Ok(principal) => principal,
+        Err(err) => return err.into_response(),
+    };
+
+    let Some(principal) = principal else {
+        return unauthorized("invalid, expired or revoked API key");
+    };
+    if !principal.allows(needed) {
+        return reject(
+            StatusCode::FORBIDDEN,
+            &format!("API key lacks the {} scope", needed.as_str()),
+        );
+    }
+
+    debug!(
+        "{:<12} - api key {} ({:?})",
+        "MIDDLEWARE", principal.key_name, principal.id
+    );
+    req.extensions_mut().insert(principal);
+    next.run(req).await
+}
+
+/// The key a request carries, from `Authorization: Bearer` or `X-Api-Key`.
+pub(crate) fn api_key_from(headers: &HeaderMap) -> Option<String> {
+    if let Some(bearer) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(bearer.trim().to_string());
+    }
+
+    headers
+        .get(X_API_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+}
+
+/// Whom `api_key` belongs to, `None` when it is unknown, expired or
+/// revoked.
+pub(crate) async fn principal_for(
+    mm: &ModelController,
+    api_key: &str,
+) -> Result<Option<ApiKeyPrincipal>> {
+    if is_admin_key(api_key) {
+        return Ok(Some(ApiKeyPrincipal {
+            id: None,
+            key_name: String::from("API_ADMIN_KEY"),
+            scopes: vec![Scope::Admin],
+        }));
+    }
+
+    ApiKeyModelController::authenticate(mm, api_key).await
+}
+
+fn is_admin_key(api_key: &str) -> bool {
+    let admin_key = &core_config().API_ADMIN_KEY;
+
+    // Compared as hashes, so the comparison time says nothing about the key.
+    !admin_key.is_empty() && hash_api_key(api_key) == hash_api_key(admin_key)
+}
+
+fn unauthorized(message: &str) -> Response {
+    let mut res = reject(StatusCode::UNAUTHORIZED, message);
+    res.headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+
+    res
+}
+
+fn reject(status: StatusCode, message: &str) -> Response {
+    let body = Json(json!({
+        "result": {
+            "message": message,
+            "status": false,
+        }
+    }));
+
+    (status, body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_from() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(api_key_from(&headers), None);
+
+        headers.insert(X_API_KEY, HeaderValue::from_static("ack_x"));
+        assert_eq!(api_key_from(&headers).as_deref(), Some("ack_x"));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer ack_b"),
+        );
+        assert_eq!(api_key_from(&headers).as_deref(), Some("ack_b"));
+    }
+
+    #[test]
+    fn test_scopes_include_lower_ones() {
+        let principal = ApiKeyPrincipal {
+            id: Some(1),
+            key_name: String::from("pos"),
+            scopes: vec![Scope::Write],
+        };
+
+        assert!(principal.allows(Scope::Read));
+        assert!(principal.allows(Scope::Write));
+        assert!(!principal.allows(Scope::Admin));
+    }
+}
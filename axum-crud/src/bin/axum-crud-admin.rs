@@ -0,0 +1,203 @@
+//! Ops CLI for the tasks otherwise done by hand in psql. Reads the same
+//! environment as the server and goes through the same controllers.
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use axum_crud::{
+    admin_fns::AdminModelController,
+    crud_fns::{FoodModelController, ModelController},
+    error::{Error, Result},
+    reorder_fns::{ReorderModelController, ReorderPolicy},
+};
+use clap::{Parser, Subcommand};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(name = "axum-crud-admin", about = "Maintenance tasks for axum-crud")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply pending database migrations.
+    Migrate,
+    /// Create a demo store and foods, skipped when foods exist.
+    Seed {
+        /// Seed even when foods exist.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Create foods from a CSV file, `-` for stdin, all or none. Rows with
+    /// the stamp code of an existing food are rejected.
+    Import { path: PathBuf },
+    /// Write the foods not removed as CSV.
+    Export {
+        /// Stock levels of this store.
+        #[arg(long)]
+        store_id: Option<i64>,
+        /// File to write, stdout when absent.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Bring removed foods back.
+    Restore {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Change a food's stock and record why in the stock ledger.
+    AdjustStock {
+        food_id: i64,
+        /// Quantity in the food's base unit, negative to take stock out.
+        #[arg(long, allow_negative_numbers = true)]
+        delta: i32,
+        #[arg(long)]
+        reason: String,
+        #[arg(long)]
+        store_id: Option<i64>,
+    },
+    /// Print stock counts per category and reorder suggestions.
+    Report {
+        #[arg(long)]
+        store_id: Option<i64>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .without_time()
+        .with_target(false)
+        .with_writer(io::stderr)
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let cli = Cli::parse();
+
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> Result<()> {
+    let mm = ModelController::new().await?;
+    let res = dispatch(mm.clone(), command).await;
+    mm.close().await;
+    res
+}
+
+async fn dispatch(mm: ModelController, command: Command) -> Result<()> {
+    match command {
+        Command::Migrate => {
+            AdminModelController::migrate(mm).await?;
+            println!("database is up to date");
+        }
+        Command::Seed { force } => match AdminModelController::seed(mm, force).await? {
+            0 => println!("foods exist, nothing seeded (--force to seed anyway)"),
+            count => println!("seeded {count} foods"),
+        },
+        Command::Import { path } => {
+            let reader: Box<dyn Read> = if path.as_os_str() == "-" {
+                Box::new(io::stdin())
+            } else {
+                Box::new(open(&path)?)
+            };
+            let count = AdminModelController::import_csv(mm, reader).await?;
+            println!("imported {count} foods");
+        }
+        Command::Export { store_id, output } => {
+            let writer: Box<dyn Write> = match &output {
+                Some(path) => Box::new(create(path)?),
+                None => Box::new(io::stdout()),
+            };
+            let count = AdminModelController::export_csv(mm, store_id, writer).await?;
+            if output.is_some() {
+                println!("exported {count} foods");
+            }
+        }
+        Command::Restore { ids } => {
+            for id in ids {
+                let food = FoodModelController::restore(mm.clone(), id).await?;
+                println!(
+                    "restored {} {} ({})",
+                    food.id, food.food_name, food.stamp_code
+                );
+            }
+        }
+        Command::AdjustStock {
+            food_id,
+            delta,
+            reason,
+            store_id,
+        } => {
+            let stocks =
+                AdminModelController::adjust_stock(mm, food_id, store_id, delta, &reason).await?;
+            println!("food {food_id} now holds {stocks}");
+        }
+        Command::Report { store_id } => report(mm, store_id).await?,
+    }
+
+    Ok(())
+}
+
+async fn report(mm: ModelController, store_id: Option<i64>) -> Result<()> {
+    let stats = FoodModelController::inventory_stats(mm.clone(), store_id).await?;
+    let categories = AdminModelController::stock_by_category(mm.clone(), store_id).await?;
+    let suggestions =
+        ReorderModelController::suggestions(mm, ReorderPolicy::default(), store_id).await?;
+
+    println!("{} foods, {} out of stock", stats.total, stats.out_of_stock);
+    println!();
+    println!(
+        "{:<24} {:>8} {:>10} {:>12}",
+        "CATEGORY", "FOODS", "STOCK", "OUT OF STOCK"
+    );
+    for c in categories {
+        println!(
+            "{:<24} {:>8} {:>10} {:>12}",
+            c.category, c.foods, c.total_stocks, c.out_of_stock
+        );
+    }
+
+    println!();
+    if suggestions.is_empty() {
+        println!("nothing to reorder");
+        return Ok(());
+    }
+    println!(
+        "{:>8} {:<24} {:>8} {:>10} {:>10}",
+        "FOOD", "NAME", "STOCK", "COVER (D)", "REORDER"
+    );
+    for s in suggestions {
+        let unit = serde_json::to_value(s.unit).unwrap_or_default();
+        println!(
+            "{:>8} {:<24} {:>8} {:>10} {:>7} {}",
+            s.food_id,
+            s.food_name,
+            s.stocks,
+            s.days_of_cover,
+            s.suggested_quantity,
+            unit.as_str().unwrap_or_default(),
+        );
+    }
+
+    Ok(())
+}
+
+fn open(path: &Path) -> Result<File> {
+    File::open(path).map_err(|err| Error::CsvFailed(format!("{}: {err}", path.display())))
+}
+
+fn create(path: &Path) -> Result<File> {
+    File::create(path).map_err(|err| Error::CsvFailed(format!("{}: {err}", path.display())))
+}
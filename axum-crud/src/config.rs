@@ -18,12 +18,60 @@ pub fn core_config() -> &'static CoreConfig {
 pub struct CoreConfig {
     pub SERVER_URL: String,
     pub SERVER_PORT: u32,
+    pub GRPC_PORT: u32,
+    pub SHUTDOWN_TIMEOUT_SEC: u64,
 
     pub DB_NAME: String,
     pub DB_HOST: String,
     pub DB_USER: String,
     pub DB_PASS: String,
     pub DB_PORT: u32,
+
+    pub DB_READ_HOST: String,
+    pub DB_READ_PORT: u32,
+    pub DB_READ_CHECK_INTERVAL_MS: u64,
+    pub DB_READ_MAX_LAG_MS: u64,
+
+    pub HEALTH_CHECK_TIMEOUT_MS: u64,
+
+    pub METRICS_REFRESH_INTERVAL_MS: u64,
+
+    pub RESERVATION_TTL_SEC: u64,
+
+    pub IDEMPOTENCY_TTL_SEC: u64,
+    pub IDEMPOTENCY_REQUEST_TIMEOUT_SEC: u64,
+
+    pub API_ADMIN_KEY: String,
+
+    pub IMAGE_DIR: String,
+    pub IMAGE_MAX_BYTES: usize,
+
+    pub LEGACY_CREATED_DATE: bool,
+
+    pub CURSOR_SECRET: String,
+
+    pub REORDER_WINDOW_DAYS: u32,
+    pub REORDER_LEAD_TIME_DAYS: u32,
+    pub REORDER_SAFETY_DAYS: u32,
+
+    pub GRAPHIQL_ENABLED: bool,
+
+    pub FOOD_CACHE_CAPACITY: usize,
+    pub FOOD_CACHE_TTL_SEC: u64,
+
+    pub JOB_EXPIRE_RESERVATIONS_CRON: String,
+    pub JOB_APPLY_SCHEDULED_PRICES_CRON: String,
+    pub JOB_PURGE_REMOVED_FOODS_CRON: String,
+    pub JOB_LOW_STOCK_SCAN_CRON: String,
+    pub JOB_PURGE_IDEMPOTENCY_KEYS_CRON: String,
+    pub PURGE_REMOVED_FOODS_AFTER_DAYS: u32,
+
+    pub WEBHOOK_POLL_INTERVAL_MS: u64,
+    pub WEBHOOK_TIMEOUT_MS: u64,
+    pub WEBHOOK_MAX_ATTEMPTS: i32,
+    pub WEBHOOK_BACKOFF_BASE_SEC: u64,
+    pub WEBHOOK_BACKOFF_MAX_SEC: u64,
+    pub WEBHOOK_ALLOW_PRIVATE_TARGETS: bool,
 }
 
 impl CoreConfig {
@@ -31,12 +79,60 @@ impl CoreConfig {
         Ok(CoreConfig {
             SERVER_URL: get_env("SERVER_URL")?,
             SERVER_PORT: get_env_parse("SERVER_PORT")?,
+            GRPC_PORT: get_env_parse("GRPC_PORT")?,
+            SHUTDOWN_TIMEOUT_SEC: get_env_parse("SHUTDOWN_TIMEOUT_SEC")?,
 
             DB_NAME: get_env("DB_NAME")?,
             DB_HOST: get_env("DB_HOST")?,
             DB_USER: get_env("DB_USER")?,
             DB_PASS: get_env("DB_PASS")?,
             DB_PORT: get_env_parse("DB_PORT")?,
+
+            DB_READ_HOST: get_env("DB_READ_HOST")?,
+            DB_READ_PORT: get_env_parse("DB_READ_PORT")?,
+            DB_READ_CHECK_INTERVAL_MS: get_env_parse("DB_READ_CHECK_INTERVAL_MS")?,
+            DB_READ_MAX_LAG_MS: get_env_parse("DB_READ_MAX_LAG_MS")?,
+
+            HEALTH_CHECK_TIMEOUT_MS: get_env_parse("HEALTH_CHECK_TIMEOUT_MS")?,
+
+            METRICS_REFRESH_INTERVAL_MS: get_env_parse("METRICS_REFRESH_INTERVAL_MS")?,
+
+            RESERVATION_TTL_SEC: get_env_parse("RESERVATION_TTL_SEC")?,
+
+            IDEMPOTENCY_TTL_SEC: get_env_parse("IDEMPOTENCY_TTL_SEC")?,
+            IDEMPOTENCY_REQUEST_TIMEOUT_SEC: get_env_parse("IDEMPOTENCY_REQUEST_TIMEOUT_SEC")?,
+
+            API_ADMIN_KEY: get_env("API_ADMIN_KEY")?,
+
+            IMAGE_DIR: get_env("IMAGE_DIR")?,
+            IMAGE_MAX_BYTES: get_env_parse("IMAGE_MAX_BYTES")?,
+
+            LEGACY_CREATED_DATE: get_env_parse("LEGACY_CREATED_DATE")?,
+
+            CURSOR_SECRET: get_env("CURSOR_SECRET")?,
+
+            REORDER_WINDOW_DAYS: get_env_parse("REORDER_WINDOW_DAYS")?,
+            REORDER_LEAD_TIME_DAYS: get_env_parse("REORDER_LEAD_TIME_DAYS")?,
+            REORDER_SAFETY_DAYS: get_env_parse("REORDER_SAFETY_DAYS")?,
+
+            GRAPHIQL_ENABLED: get_env_parse("GRAPHIQL_ENABLED")?,
+
+            FOOD_CACHE_CAPACITY: get_env_parse("FOOD_CACHE_CAPACITY")?,
+            FOOD_CACHE_TTL_SEC: get_env_parse("FOOD_CACHE_TTL_SEC")?,
+
+            JOB_EXPIRE_RESERVATIONS_CRON: get_env("JOB_EXPIRE_RESERVATIONS_CRON")?,
+            JOB_APPLY_SCHEDULED_PRICES_CRON: get_env("JOB_APPLY_SCHEDULED_PRICES_CRON")?,
+            JOB_PURGE_REMOVED_FOODS_CRON: get_env("JOB_PURGE_REMOVED_FOODS_CRON")?,
+            JOB_LOW_STOCK_SCAN_CRON: get_env("JOB_LOW_STOCK_SCAN_CRON")?,
+            JOB_PURGE_IDEMPOTENCY_KEYS_CRON: get_env("JOB_PURGE_IDEMPOTENCY_KEYS_CRON")?,
+            PURGE_REMOVED_FOODS_AFTER_DAYS: get_env_parse("PURGE_REMOVED_FOODS_AFTER_DAYS")?,
+
+            WEBHOOK_POLL_INTERVAL_MS: get_env_parse("WEBHOOK_POLL_INTERVAL_MS")?,
+            WEBHOOK_TIMEOUT_MS: get_env_parse("WEBHOOK_TIMEOUT_MS")?,
+            WEBHOOK_MAX_ATTEMPTS: get_env_parse("WEBHOOK_MAX_ATTEMPTS")?,
+            WEBHOOK_BACKOFF_BASE_SEC: get_env_parse("WEBHOOK_BACKOFF_BASE_SEC")?,
+            WEBHOOK_BACKOFF_MAX_SEC: get_env_parse("WEBHOOK_BACKOFF_MAX_SEC")?,
+            WEBHOOK_ALLOW_PRIVATE_TARGETS: get_env_parse("WEBHOOK_ALLOW_PRIVATE_TARGETS")?,
         })
     }
 }
@@ -1,33 +1,131 @@
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+use time_tz::Tz;
 use tracing::debug;
+use utoipa::ToSchema;
+
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
+    config::core_config,
     error::{Error, Result},
-    store::{new_db_pool, Db},
-    utils::{b32_hex, b64u},
+    food_cache::{FoodCache, FoodKey},
+    food_repo::{FoodRepository, InMemoryFoodRepository, PgFoodRepository},
+    image_store::{ImageStore, LocalDirImageStore},
+    images_fns::{FoodImage, ImageModelController},
+    metrics::metrics,
+    pagination::{Page, PageParams},
+    replica::{route, Replica},
+    store::{new_db_pool, new_read_pool, Db},
+    timestamps::{created_date, in_tz},
+    units::{to_base, Unit},
 };
 
 #[derive(Clone)]
 pub struct ModelController {
-    db: Db,
+    db: Option<Db>,
+    replica: Option<Arc<Replica>>,
+    foods: Arc<dyn FoodRepository>,
+    food_cache: Arc<FoodCache>,
+    images: Arc<dyn ImageStore>,
 }
 
 impl ModelController {
+    /// Postgres-backed controller.
     pub async fn new() -> Result<Self> {
         let db = new_db_pool().await?;
+        let replica = new_read_pool()?.map(|read_db| Arc::new(Replica::new(read_db)));
+        let foods = Arc::new(PgFoodRepository::new(db.clone(), replica.clone()));
+
+        Ok(ModelController {
+            db: Some(db),
+            replica,
+            foods,
+            // -- Not used until `food_cache::spawn_listener` is connected.
+            food_cache: Arc::new(FoodCache::from_config(false)),
+            images: Arc::new(LocalDirImageStore::new(&core_config().IMAGE_DIR)),
+        })
+    }
+
+    /// Database-less controller, foods are kept in process memory.
+    #[allow(dead_code)]
+    pub fn in_memory() -> Self {
+        ModelController {
+            db: None,
+            replica: None,
+            foods: Arc::new(InMemoryFoodRepository::default()),
+            food_cache: Arc::new(FoodCache::from_config(true)),
+            images: Arc::new(LocalDirImageStore::new(&core_config().IMAGE_DIR)),
+        }
+    }
 
-        Ok(ModelController { db })
+    /// Postgres-backed controller on the pool `#[sqlx::test]` hands a test,
+    /// without a replica or food cache.
+    #[cfg(test)]
+    pub(crate) fn with_db(db: Db) -> Self {
+        ModelController {
+            db: Some(db.clone()),
+            replica: None,
+            foods: Arc::new(PgFoodRepository::new(db, None)),
+            food_cache: Arc::new(FoodCache::new(0, std::time::Duration::ZERO, false)),
+            images: Arc::new(LocalDirImageStore::new(&core_config().IMAGE_DIR)),
+        }
     }
 
-    pub(crate) fn db(&self) -> &Db {
-        &self.db
+    /// The sqlx pool, if this controller is backed by Postgres.
+    pub(crate) fn db(&self) -> Result<&Db> {
+        self.db.as_ref().ok_or(Error::NoDatabase)
+    }
+
+    /// The pool for a read that may lag behind the primary, see `replica`.
+    pub(crate) fn read_db(&self) -> Result<&Db> {
+        Ok(route(self.db()?, self.replica.as_deref()))
+    }
+
+    pub(crate) fn replica(&self) -> Option<&Arc<Replica>> {
+        self.replica.as_ref()
+    }
+
+    pub(crate) fn foods(&self) -> &dyn FoodRepository {
+        self.foods.as_ref()
+    }
+
+    pub(crate) fn food_cache(&self) -> &FoodCache {
+        self.food_cache.as_ref()
+    }
+
+    pub(crate) fn images(&self) -> &dyn ImageStore {
+        self.images.as_ref()
+    }
+
+    /// Waits for checked-out connections to be returned, then closes the
+    /// pools.
+    pub async fn close(&self) {
+        if let Some(replica) = &self.replica {
+            replica.db().close().await;
+        }
+        if let Some(db) = &self.db {
+            db.close().await;
+        }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct FoodModelController;
 
+/// Related rows loaded along with foods. Callers that load them on their
+/// own, like the GraphQL resolvers, leave them out.
+#[derive(Clone, Copy, Debug)]
+pub struct FoodRelations {
+    pub images: bool,
+}
+
+impl FoodRelations {
+    pub const ALL: FoodRelations = FoodRelations { images: true };
+    pub const NONE: FoodRelations = FoodRelations { images: false };
+}
+
 #[derive(Debug, Serialize)]
 pub struct FoodToCreate {
     pub food_name: String,
@@ -35,9 +133,27 @@ pub struct FoodToCreate {
     pub stocks: i32,
     pub price: f32,
     pub total_quantity: i32,
+    pub unit: Unit,
+    pub case_size: Option<i32>,
 }
 
-#[derive(Debug, FromRow, Deserialize, Serialize)]
+#[cfg(test)]
+impl FoodToCreate {
+    /// A food counted in pieces, for the tests.
+    pub(crate) fn sample(food_name: &str, stocks: i32) -> Self {
+        FoodToCreate {
+            food_name: food_name.to_string(),
+            category: String::from("test"),
+            stocks,
+            price: 1.0,
+            total_quantity: stocks,
+            unit: Unit::Piece,
+            case_size: None,
+        }
+    }
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
 pub struct FoodToSelect {
     pub cid: String,
     pub mid: String,
@@ -48,15 +164,32 @@ pub struct FoodToSelect {
     pub stocks: i32,
     pub price: f64,
     pub total_quantity: i32,
-    pub created_date: String,
+    /// Unit `stocks` and `total_quantity` are counted in.
+    pub unit: Unit,
+    /// Base units in a case, if the food comes in cases.
+    pub case_size: Option<i32>,
+    /// RFC 3339, at the offset of the requested zone.
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<OffsetDateTime>,
+    /// `Month DD, YYYY`, only while `LEGACY_CREATED_DATE` is on.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_date: Option<String>,
+    #[sqlx(skip)]
+    pub images: Vec<FoodImage>,
 }
 
-#[derive(Debug, Serialize, FromRow)]
-struct FoodsToReturn {
-    id: i64,
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct InventoryStats {
+    pub total: i64,
+    pub out_of_stock: i64,
 }
 
-#[derive(Debug, FromRow, Deserialize, Serialize)]
+#[derive(Clone, Debug, FromRow, Deserialize, Serialize, ToSchema)]
 pub struct OneFoodToSelect {
     pub cid: String,
     pub mid: String,
@@ -67,257 +200,277 @@ pub struct OneFoodToSelect {
     pub stocks: i32,
     pub price: f64,
     pub total_quantity: i32,
+    /// Unit `stocks` and `total_quantity` are counted in.
+    pub unit: Unit,
+    /// Base units in a case, if the food comes in cases.
+    pub case_size: Option<i32>,
+    /// RFC 3339, at the offset of the requested zone.
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub updated_at: Option<OffsetDateTime>,
+    #[sqlx(skip)]
+    pub images: Vec<FoodImage>,
 }
 
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 pub struct FoodToUpdate {
     pub id: i64,
+    pub store_id: Option<i64>,
+    pub food_name: Option<String>,
+    pub category: Option<String>,
     pub stocks: Option<i32>,
     pub price: Option<f32>,
     pub total_quantity: Option<i32>,
+    pub unit: Option<Unit>,
+    /// `None` keeps the case size, `Some(None)` clears it.
+    pub case_size: Option<Option<i32>>,
+}
+
+impl FoodToSelect {
+    /// Puts the timestamps at `tz`'s offset and fills in `created_date`
+    /// when the legacy field is on.
+    fn localize(&mut self, tz: Option<&Tz>) {
+        self.created_at = in_tz(self.created_at, tz);
+        self.updated_at = self.updated_at.map(|at| in_tz(at, tz));
+        self.created_date = core_config()
+            .LEGACY_CREATED_DATE
+            .then(|| created_date(self.created_at));
+    }
+}
+
+impl OneFoodToSelect {
+    fn localize(&mut self, tz: Option<&Tz>) {
+        self.created_at = in_tz(self.created_at, tz);
+        self.updated_at = self.updated_at.map(|at| in_tz(at, tz));
+    }
 }
 
 impl FoodModelController {
-    pub async fn create(mm: ModelController, data: FoodToCreate) -> Result<i64> {
+    /// `stocks` and `total_quantity` may be given in `quantity_unit`, they
+    /// are stored in the food's base unit.
+    pub async fn create(
+        mm: ModelController,
+        mut data: FoodToCreate,
+        quantity_unit: Option<Unit>,
+    ) -> Result<i64> {
         debug!("{:<12} - create", "HANDLER");
 
-        let query = "insert into foods_table (cid, mid, stamp_code, food_name, category, stocks, price, total_quantity) values ($1,$2,$3,$4,$5,$6,$7,$8) returning id";
-        let cid = b64u().unwrap();
-        let mid = b64u().unwrap();
-        let stamp_code = b32_hex().unwrap();
+        data.unit.check_base()?;
+        Unit::check_case_size(data.case_size)?;
+        if let Some(unit) = quantity_unit {
+            data.stocks = to_base(data.stocks, unit, data.unit, data.case_size)?;
+            data.total_quantity = to_base(data.total_quantity, unit, data.unit, data.case_size)?;
+        }
 
-        let FoodToCreate {
-            food_name,
-            category,
-            stocks,
-            price,
-            total_quantity,
-        } = data;
-        let db = mm.db();
-
-        match sqlx::query_as::<_, FoodsToReturn>(query)
-            .bind(cid)
-            .bind(mid)
-            .bind(stamp_code)
-            .bind(food_name)
-            .bind(category)
-            .bind(stocks)
-            .bind(price)
-            .bind(total_quantity)
-            .fetch_one(db)
-            .await
-        {
-            Ok(food) => Ok(food.id),
-            Err(err) => {
-                debug!("{:<12} - Create failed - error {err:?}", "ERROR_CONTROLLER");
-                Err(Error::CreateFailed(err.to_string()))
-            }
+        let timer = metrics().query_timer("create");
+        timer.observe(mm.foods().create(data).await)
+    }
+
+    /// Creates the foods all or none, quantities already in their base
+    /// unit. Returns their ids in order.
+    pub async fn create_many(mm: ModelController, foods: Vec<FoodToCreate>) -> Result<Vec<i64>> {
+        debug!("{:<12} - create_many", "HANDLER");
+
+        for data in &foods {
+            data.unit.check_base()?;
+            Unit::check_case_size(data.case_size)?;
         }
+
+        let timer = metrics().query_timer("create_many");
+        timer.observe(mm.foods().create_many(foods).await)
     }
 
-    pub async fn select(mm: ModelController) -> Result<Vec<FoodToSelect>> {
+    /// Timestamps are given at `tz`'s offset, UTC without one.
+    pub async fn select(
+        mm: ModelController,
+        store_id: Option<i64>,
+        tz: Option<&Tz>,
+        page: &PageParams,
+        relations: FoodRelations,
+    ) -> Result<Page<FoodToSelect>> {
         debug!("{:<12} - select", "HANDLER");
 
-        let query = "select cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date from foods_table where food_status != 'removed' order by ctime desc";
-        let db = mm.db();
-
-        match sqlx::query_as::<_, FoodToSelect>(query).fetch_all(db).await {
-            Ok(foods) => Ok(foods),
-            Err(err) => {
-                debug!("{:<12} - select handler error", "ERROR_CONTROLLER");
-                Err(Error::SelectFailed(err.to_string()))
-            }
+        let request = page.request()?;
+        let timer = metrics().query_timer("select");
+        let foods = timer.observe(mm.foods().select(store_id, request).await)?;
+        let mut foods = page.page(foods, |food| &food.cid)?;
+
+        let ids: Vec<i64> = foods.items.iter().map(|food| food.id).collect();
+        let mut images = if relations.images {
+            ImageModelController::images_of(&mm, &ids).await?
+        } else {
+            HashMap::new()
+        };
+        for food in foods.items.iter_mut() {
+            food.images = images.remove(&food.id).unwrap_or_default();
+            food.localize(tz);
         }
+
+        Ok(foods)
     }
 
-    pub async fn get_by_id(mm: ModelController, id: i64) -> Result<OneFoodToSelect> {
+    /// Served from the food cache when it holds the food.
+    pub async fn get_by_id(
+        mm: ModelController,
+        id: i64,
+        store_id: Option<i64>,
+        tz: Option<&Tz>,
+        relations: FoodRelations,
+    ) -> Result<OneFoodToSelect> {
         debug!("{:<12} - get_by_id", "HANDLER");
 
-        let query = "select cid, mid, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date from foods_table where id = $1";
-        let db = mm.db();
-
-        match sqlx::query_as::<_, OneFoodToSelect>(query)
-            .bind(id)
-            .fetch_one(db)
-            .await
-        {
-            Ok(food) => Ok(food),
-            Err(err) => {
-                debug!("{:<12} - get_by_id error", "ERROR_CONTROLLER");
-
-                Err(Error::FoodIdNotFound(err.to_string()))
+        let key = FoodKey::Id(id, store_id);
+        let mut food = match mm.food_cache().get(&key) {
+            Some(food) => food,
+            None => {
+                let generation = mm.food_cache().generation();
+                let timer = metrics().query_timer("get_by_id");
+                let food = timer.observe(mm.foods().get_by_id(id, store_id).await)?;
+                mm.food_cache().put(key, &food, generation);
+                food
             }
+        };
+        if relations.images {
+            food.images = ImageModelController::images_of_food(&mm, food.id).await?;
         }
+        food.localize(tz);
+
+        Ok(food)
     }
 
+    /// Served from the food cache when it holds the food.
     pub async fn get_by_stamp_code(
         mm: ModelController,
         stamp_code: String,
+        store_id: Option<i64>,
+        tz: Option<&Tz>,
+        relations: FoodRelations,
     ) -> Result<OneFoodToSelect> {
         debug!("{:<12} - get_by_stamp_code", "HANDLER");
 
-        let query = "select cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date from foods_table where stamp_code = $1";
-        let db = mm.db();
-
-        match sqlx::query_as::<_, OneFoodToSelect>(query)
-            .bind(stamp_code)
-            .fetch_one(db)
-            .await
-        {
-            Ok(food) => Ok(food),
-            Err(err) => {
-                debug!("{:<12} - get_by_stamp_code error", "ERROR_CONTROLLER");
-
-                Err(Error::FoodStampCodeNotFound(err.to_string()))
+        let key = FoodKey::StampCode(stamp_code.clone(), store_id);
+        let mut food = match mm.food_cache().get(&key) {
+            Some(food) => food,
+            None => {
+                let generation = mm.food_cache().generation();
+                let timer = metrics().query_timer("get_by_stamp_code");
+                let food =
+                    timer.observe(mm.foods().get_by_stamp_code(stamp_code, store_id).await)?;
+                mm.food_cache().put(key, &food, generation);
+                food
             }
+        };
+        if relations.images {
+            food.images = ImageModelController::images_of_food(&mm, food.id).await?;
         }
+        food.localize(tz);
+
+        Ok(food)
     }
 
-    pub async fn update(mm: ModelController, data: FoodToUpdate) -> Result<FoodToSelect> {
+    /// `stocks` and `total_quantity` may be given in `quantity_unit`, they
+    /// are stored in the food's base unit, the new one if it changes.
+    pub async fn update(
+        mm: ModelController,
+        mut data: FoodToUpdate,
+        quantity_unit: Option<Unit>,
+    ) -> Result<FoodToSelect> {
         debug!("{:<12} - update handler", "HANDLER");
 
-        let db = mm.db();
-
-        match (data.stocks, data.price, data.total_quantity) {
-            (Some(stocks), Some(price), Some(total_quantity)) => {
-                let query = "update foods_table set stocks = $1, price = $2, total_quantity = $3 where id = $4 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(stocks)
-                    .bind(price)
-                    .bind(total_quantity)
-                    .bind(data.id)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
-            }
-            (Some(stocks), Some(price), _) => {
-                let query = "update foods_table set stocks = $1, price = $2 where id = $3 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(stocks)
-                    .bind(price)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
-            }
-            (Some(stocks), _, Some(total_quantity)) => {
-                let query = "update foods_table set stocks = $1, total_quantity = $2 where id = $3 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(stocks)
-                    .bind(total_quantity)
-                    .bind(data.id)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
-            }
-            (Some(stocks), _, _) => {
-                let query = "update foods_table set stocks = $1 where id = $2 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(stocks)
-                    .bind(data.id)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
-            }
-            (_, Some(price), Some(total_quantity)) => {
-                let query = "update foods_table set price = $1, total_quantity = $2 where id = $3 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(price)
-                    .bind(total_quantity)
-                    .bind(data.id)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
-            }
-            (_, Some(price), _) => {
-                let query = "update foods_table set price = $1 where id = $2 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(price)
-                    .bind(data.id)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
+        if data.food_name.is_none()
+            && data.category.is_none()
+            && data.stocks.is_none()
+            && data.price.is_none()
+            && data.total_quantity.is_none()
+            && data.unit.is_none()
+            && data.case_size.is_none()
+        {
+            return Err(Error::NothingToUpdate(format!(
+                "no fields given for food {}",
+                data.id
+            )));
+        }
+
+        if let Some(unit) = data.unit {
+            unit.check_base()?;
+        }
+        Unit::check_case_size(data.case_size.flatten())?;
+        if let Some(unit) = quantity_unit {
+            let food = mm.foods().get_by_id(data.id, None).await?;
+            let base = data.unit.unwrap_or(food.unit);
+            let case_size = data.case_size.unwrap_or(food.case_size);
+
+            if let Some(stocks) = data.stocks {
+                data.stocks = Some(to_base(stocks, unit, base, case_size)?);
             }
-            (_, _, Some(total_quantity)) => {
-                let query = "update foods_table set total_quantity = $1 where id = $2 returning cid, mid, id, stamp_code, food_name, category, stocks, price, total_quantity, to_char(ctime, 'Month DD, YYYY') as created_date";
-
-                match sqlx::query_as::<_, FoodToSelect>(query)
-                    .bind(total_quantity)
-                    .bind(data.id)
-                    .fetch_one(db)
-                    .await
-                {
-                    Ok(food_updated) => Ok(food_updated),
-                    Err(err) => {
-                        debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
-
-                        Err(Error::UpdateFailed(err.to_string()))
-                    }
-                }
+            if let Some(total_quantity) = data.total_quantity {
+                data.total_quantity = Some(to_base(total_quantity, unit, base, case_size)?);
             }
-            _ => Err(Error::FoodIdNotFound(data.id.to_string())),
         }
+
+        let id = data.id;
+        let timer = metrics().query_timer("update");
+        let res = timer.observe(mm.foods().update(data).await);
+        // -- Also on failure, an error does not prove nothing was written.
+        mm.food_cache().invalidate(id);
+        let mut food = res?;
+        food.images = ImageModelController::images_of_food(&mm, food.id).await?;
+        food.localize(None);
+
+        Ok(food)
     }
 
     pub async fn delete(mm: ModelController, id: i64) -> Result<String> {
         debug!("{:<12} - delete handler", "HANDLER");
 
-        let query = "update foods_table set food_status = 'removed' where id = $1";
-        let db = mm.db();
+        let timer = metrics().query_timer("delete");
+        let res = timer.observe(mm.foods().delete(id).await);
+        mm.food_cache().invalidate(id);
+        res
+    }
+
+    /// Undoes `delete`. Fails with `FoodIdNotFound` unless the food is
+    /// currently removed.
+    pub async fn restore(mm: ModelController, id: i64) -> Result<OneFoodToSelect> {
+        debug!("{:<12} - restore handler", "HANDLER");
 
-        match sqlx::query(query).bind(id).execute(db).await {
-            Ok(_) => Ok(String::from("Removed food successfully")),
-            Err(err) => {
-                debug!("{:<12} - delete handler error", "ERROR_CONTROLLER");
-                Err(Error::DeleteFailed(err.to_string()))
-            }
-        }
+        let timer = metrics().query_timer("restore");
+        let res = timer.observe(mm.foods().restore(id).await);
+        mm.food_cache().invalidate(id);
+        let mut food = res?;
+        food.localize(None);
+
+        Ok(food)
+    }
+
+    /// `quantity` of a food given in `quantity_unit`, converted to the
+    /// food's base unit, along with that unit.
+    pub async fn to_base_quantity(
+        mm: &ModelController,
+        food_id: i64,
+        quantity: i32,
+        quantity_unit: Option<Unit>,
+    ) -> Result<(i32, Unit)> {
+        let food = mm.foods().get_by_id(food_id, None).await?;
+        let quantity = match quantity_unit {
+            Some(unit) => to_base(quantity, unit, food.unit, food.case_size)?,
+            None => quantity,
+        };
+
+        Ok((quantity, food.unit))
+    }
+
+    /// Counts for the inventory gauges exported on `/metrics`.
+    pub async fn inventory_stats(
+        mm: ModelController,
+        store_id: Option<i64>,
+    ) -> Result<InventoryStats> {
+        debug!("{:<12} - inventory_stats", "HANDLER");
+
+        let timer = metrics().query_timer("inventory_stats");
+        timer.observe(mm.foods().inventory_stats(store_id).await)
     }
 }
@@ -1,19 +1,31 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
 };
-use serde::Deserialize;
-use serde_json::{json, Value};
+use serde::{Deserialize, Serialize};
+use time_tz::Tz;
 use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    crud_fns::{FoodModelController, FoodToCreate, FoodToUpdate, ModelController},
+    crud_fns::{
+        FoodModelController, FoodRelations, FoodToCreate, FoodToSelect, FoodToUpdate,
+        InventoryStats, ModelController, OneFoodToSelect,
+    },
     error::Result,
+    idempotency::mw_idempotency,
+    pagination::PageParams,
+    timestamps::parse_tz,
+    units::Unit,
 };
 
 pub fn routes_crud(mm: ModelController) -> Router {
-    Router::new()
+    // RPC-style paths, kept as deprecated aliases of `/api/v1/foods`.
+    let routes_legacy = Router::new()
         .route("/api/create", post(api_create_food))
         .route("/api/update", post(api_update_food))
         .route("/api/select", get(api_select_food))
@@ -23,30 +35,213 @@ pub fn routes_crud(mm: ModelController) -> Router {
             get(api_select_food_by_stamp_code),
         )
         .route("/api/delete/:id", delete(api_delete_food))
+        .route_layer(middleware::map_response(mark_deprecated));
+
+    let routes_v1 = Router::new()
+        .route(
+            "/api/v1/foods",
+            get(api_v1_list_foods).post(api_v1_create_food),
+        )
+        .route(
+            "/api/v1/foods/:id",
+            get(api_v1_get_food)
+                .patch(api_v1_patch_food)
+                .put(api_v1_put_food)
+                .delete(api_v1_delete_food),
+        )
+        .route(
+            "/api/v1/foods/stamp_code/:stamp_code",
+            get(api_v1_get_food_by_stamp_code),
+        )
+        .route("/api/v1/reports/inventory", get(api_v1_inventory_report));
+
+    routes_legacy
+        .merge(routes_v1)
+        .route_layer(middleware::from_fn_with_state(mm.clone(), mw_idempotency))
         .with_state(mm)
 }
 
-#[derive(Debug, Deserialize)]
+async fn mark_deprecated(mut res: Response) -> Response {
+    let headers = res.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    headers.insert(
+        header::LINK,
+        HeaderValue::from_static("</api/v1/foods>; rel=\"successor-version\""),
+    );
+
+    res
+}
+
+/// `?store_id=` scoping `stocks` to one store.
+#[derive(Debug, Deserialize, IntoParams)]
+struct StoreScope {
+    /// Report the stock held by this store instead of the food's own stock.
+    store_id: Option<i64>,
+}
+
+/// `?tz=` for the timestamps of the foods returned.
+#[derive(Debug, Deserialize, IntoParams)]
+struct TzParam {
+    /// IANA zone such as `Asia/Manila`, the timestamps' offset is UTC without it.
+    tz: Option<String>,
+}
+
+impl TzParam {
+    fn zone(&self) -> Result<Option<&'static Tz>> {
+        self.tz.as_deref().map(parse_tz).transpose()
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 struct CreateFoodPayload {
     food_name: String,
     category: String,
     stocks: i32,
     price: f32,
     total_quantity: i32,
+    /// Base unit the food is counted in, `piece` when absent.
+    unit: Option<Unit>,
+    /// Base units in a case.
+    case_size: Option<i32>,
+    /// Unit `stocks` and `total_quantity` are given in, the base unit when absent.
+    quantity_unit: Option<Unit>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 struct UpdateFoodPayload {
     id: i64,
     stocks: Option<i32>,
     price: Option<f32>,
     total_quantity: Option<i32>,
+    /// Unit `stocks` and `total_quantity` are given in, the base unit when absent.
+    quantity_unit: Option<Unit>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct PatchFoodPayload {
+    food_name: Option<String>,
+    category: Option<String>,
+    stocks: Option<i32>,
+    price: Option<f32>,
+    total_quantity: Option<i32>,
+    unit: Option<Unit>,
+    case_size: Option<i32>,
+    /// Unit `stocks` and `total_quantity` are given in, the base unit when absent.
+    quantity_unit: Option<Unit>,
+}
+
+// region: ---- Response envelopes
+// The `{ "result": ... }` bodies the handlers answer with.
+
+#[derive(Serialize, ToSchema)]
+struct FoodCreatedBody {
+    result: FoodCreatedResult,
+}
+
+#[derive(Serialize, ToSchema)]
+struct FoodCreatedResult {
+    message: String,
+    status: bool,
+    food_id: i64,
+}
+
+impl FoodCreatedBody {
+    fn new(food_id: i64) -> Self {
+        FoodCreatedBody {
+            result: FoodCreatedResult {
+                message: String::from("success"),
+                status: true,
+                food_id,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct DataBody<T> {
+    result: DataResult<T>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct DataResult<T> {
+    data: T,
+    status: bool,
+}
+
+impl<T> DataBody<T> {
+    pub(crate) fn new(data: T) -> Self {
+        DataBody {
+            result: DataResult { data, status: true },
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PageBody<T> {
+    result: PageResult<T>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PageResult<T> {
+    data: T,
+    /// Pass as `cursor` for the next page.
+    next_cursor: Option<String>,
+    status: bool,
+}
+
+impl<T> PageBody<T> {
+    pub(crate) fn new(data: T, next_cursor: Option<String>) -> Self {
+        PageBody {
+            result: PageResult {
+                data,
+                next_cursor,
+                status: true,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct MessageBody {
+    result: MessageResult,
+}
+
+#[derive(Serialize, ToSchema)]
+struct MessageResult {
+    message: String,
+    status: bool,
+}
+
+impl MessageBody {
+    fn new(message: impl Into<String>) -> Self {
+        MessageBody {
+            result: MessageResult {
+                message: message.into(),
+                status: true,
+            },
+        }
+    }
 }
 
+// endregion: ---- Response envelopes
+
+// region: ---- Legacy handlers
+
+#[utoipa::path(
+    post,
+    path = "/api/create",
+    tag = "foods",
+    request_body = CreateFoodPayload,
+    responses(
+        (status = 200, description = "Food created", body = FoodCreatedBody),
+        (status = 400, description = "Unit not a base unit"),
+        (status = 500, description = "Create failed"),
+    )
+)]
 async fn api_create_food(
     State(mm): State<ModelController>,
     Json(body): Json<CreateFoodPayload>,
-) -> Result<Json<Value>> {
+) -> Result<Json<FoodCreatedBody>> {
     debug!("{:<12} - api_create_food", "ROUTE_HANDLER");
 
     let CreateFoodPayload {
@@ -55,6 +250,9 @@ async fn api_create_food(
         stocks,
         price,
         total_quantity,
+        unit,
+        case_size,
+        quantity_unit,
     } = body;
 
     let data = FoodToCreate {
@@ -63,75 +261,120 @@ async fn api_create_food(
         stocks,
         price,
         total_quantity,
+        unit: unit.unwrap_or_default(),
+        case_size,
     };
 
-    let food_id = FoodModelController::create(mm, data).await?;
-    let body = Json(json!({
-        "result": {
-            "message": "success",
-            "status": true,
-            "food_id": food_id,
-        }
-    }));
+    let food_id = FoodModelController::create(mm, data, quantity_unit).await?;
+    let body = Json(FoodCreatedBody::new(food_id));
 
     Ok(body)
 }
 
-async fn api_select_food(State(mm): State<ModelController>) -> Result<Json<Value>> {
+#[utoipa::path(
+    get,
+    path = "/api/select",
+    tag = "foods",
+    params(StoreScope, TzParam, PageParams),
+    responses(
+        (status = 200, description = "Foods not removed, newest first, `next_cursor` is null on the last page", body = PageBody<Vec<FoodToSelect>>),
+        (status = 400, description = "Invalid cursor"),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_select_food(
+    State(mm): State<ModelController>,
+    Query(scope): Query<StoreScope>,
+    Query(tz): Query<TzParam>,
+    Query(page): Query<PageParams>,
+) -> Result<Json<PageBody<Vec<FoodToSelect>>>> {
     debug!("{:<12} - api_select_food", "ROUTE_HANDLER");
 
-    let foods = FoodModelController::select(mm).await?;
-    let body = Json(json!({
-        "result": {
-            "data": foods,
-            "status": true,
-        }
-    }));
+    let foods =
+        FoodModelController::select(mm, scope.store_id, tz.zone()?, &page, FoodRelations::ALL)
+            .await?;
+    let body = Json(PageBody::new(foods.items, foods.next_cursor));
     Ok(body)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/select/{id}",
+    tag = "foods",
+    params(("id" = i64, Path, description = "Food id"), StoreScope, TzParam),
+    responses(
+        (status = 200, description = "Food found", body = DataBody<OneFoodToSelect>),
+        (status = 404, description = "Food id not found"),
+    )
+)]
 async fn api_select_food_by_id(
     State(mm): State<ModelController>,
     Path(food_id): Path<i64>,
-) -> Result<Json<Value>> {
+    Query(scope): Query<StoreScope>,
+    Query(tz): Query<TzParam>,
+) -> Result<Json<DataBody<OneFoodToSelect>>> {
     debug!("{:<12} - api_select_food_by_id", "ROUTE_HANDLER");
 
     let id = food_id;
 
-    let food = FoodModelController::get_by_id(mm, id).await?;
+    let food =
+        FoodModelController::get_by_id(mm, id, scope.store_id, tz.zone()?, FoodRelations::ALL)
+            .await?;
 
-    let body = Json(json!({
-        "result": {
-            "data": food,
-            "status": true,
-        }
-    }));
+    let body = Json(DataBody::new(food));
     Ok(body)
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/select/stamp_code/{stamp_code}",
+    tag = "foods",
+    params(("stamp_code" = String, Path, description = "Food stamp code"), StoreScope, TzParam),
+    responses(
+        (status = 200, description = "Food found", body = DataBody<OneFoodToSelect>),
+        (status = 404, description = "Stamp code not found"),
+    )
+)]
 async fn api_select_food_by_stamp_code(
     State(mm): State<ModelController>,
     Path(stamp_code): Path<String>,
-) -> Result<Json<Value>> {
+    Query(scope): Query<StoreScope>,
+    Query(tz): Query<TzParam>,
+) -> Result<Json<DataBody<OneFoodToSelect>>> {
     debug!("{:<12} - api_select_food_by_stamp_code", "ROUTE_HANDLER");
 
-    let stamp_code = stamp_code;
+    let food = FoodModelController::get_by_stamp_code(
+        mm,
+        stamp_code,
+        scope.store_id,
+        tz.zone()?,
+        FoodRelations::ALL,
+    )
+    .await?;
 
-    let food = FoodModelController::get_by_stamp_code(mm, stamp_code).await?;
-
-    let body = Json(json!({
-        "result": {
-            "data": food,
-            "status": true,
-        }
-    }));
+    let body = Json(DataBody::new(food));
     Ok(body)
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/update",
+    tag = "foods",
+    params(StoreScope),
+    request_body = UpdateFoodPayload,
+    responses(
+        (status = 200, description = "Food updated", body = DataBody<FoodToSelect>),
+        (status = 400, description = "No fields given or unit not a base unit"),
+        (status = 404, description = "Food id not found"),
+        (status = 409, description = "Unit change on a food in use"),
+        (status = 500, description = "Update failed"),
+    )
+)]
 async fn api_update_food(
     State(mm): State<ModelController>,
+    Query(scope): Query<StoreScope>,
     Json(body): Json<UpdateFoodPayload>,
-) -> Result<Json<Value>> {
+) -> Result<Json<DataBody<FoodToSelect>>> {
     debug!("{:<12} - api_update_food", "ROUTE_HANDLER");
 
     let UpdateFoodPayload {
@@ -139,40 +382,608 @@ async fn api_update_food(
         stocks,
         price,
         total_quantity,
+        quantity_unit,
     } = body;
     let data = FoodToUpdate {
         id,
+        store_id: scope.store_id,
+        food_name: None,
+        category: None,
         stocks,
         price,
         total_quantity,
+        unit: None,
+        case_size: None,
     };
 
-    let updated_food = FoodModelController::update(mm, data).await?;
+    let updated_food = FoodModelController::update(mm, data, quantity_unit).await?;
 
-    let body = Json(json!({
-        "result": {
-            "data": updated_food,
-            "status": true,
-        }
-    }));
+    let body = Json(DataBody::new(updated_food));
     Ok(body)
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/delete/{id}",
+    tag = "foods",
+    params(("id" = i64, Path, description = "Food id")),
+    responses(
+        (status = 200, description = "Food marked as removed", body = MessageBody),
+        (status = 404, description = "Food id not found or already removed"),
+        (status = 500, description = "Delete failed"),
+    )
+)]
 async fn api_delete_food(
     State(mm): State<ModelController>,
     Path(food_id): Path<i64>,
-) -> Result<Json<Value>> {
+) -> Result<Json<MessageBody>> {
     debug!("{:<12} - api_delete_food", "ROUTE_HANDLER");
 
     let id = food_id;
 
     let food = FoodModelController::delete(mm, id).await?;
 
-    let body = Json(json!({
-        "resutl": {
-            "message": food,
-            "status": true,
-        }
-    }));
+    let body = Json(MessageBody::new(food));
+    Ok(body)
+}
+// endregion: ---- Legacy handlers
+
+// region: ---- v1 handlers
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods",
+    tag = "foods",
+    params(StoreScope, TzParam, PageParams),
+    responses(
+        (status = 200, description = "Foods not removed, newest first, `next_cursor` is null on the last page", body = PageBody<Vec<FoodToSelect>>),
+        (status = 400, description = "Invalid cursor"),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_v1_list_foods(
+    State(mm): State<ModelController>,
+    Query(scope): Query<StoreScope>,
+    Query(tz): Query<TzParam>,
+    Query(page): Query<PageParams>,
+) -> Result<Json<PageBody<Vec<FoodToSelect>>>> {
+    debug!("{:<12} - api_v1_list_foods", "ROUTE_HANDLER");
+
+    let foods =
+        FoodModelController::select(mm, scope.store_id, tz.zone()?, &page, FoodRelations::ALL)
+            .await?;
+    let body = Json(PageBody::new(foods.items, foods.next_cursor));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}",
+    tag = "foods",
+    params(("id" = i64, Path, description = "Food id"), StoreScope, TzParam),
+    responses(
+        (status = 200, description = "Food found", body = DataBody<OneFoodToSelect>),
+        (status = 404, description = "Food id not found"),
+    )
+)]
+async fn api_v1_get_food(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    Query(scope): Query<StoreScope>,
+    Query(tz): Query<TzParam>,
+) -> Result<Json<DataBody<OneFoodToSelect>>> {
+    debug!("{:<12} - api_v1_get_food", "ROUTE_HANDLER");
+
+    let food =
+        FoodModelController::get_by_id(mm, id, scope.store_id, tz.zone()?, FoodRelations::ALL)
+            .await?;
+    let body = Json(DataBody::new(food));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/stamp_code/{stamp_code}",
+    tag = "foods",
+    params(("stamp_code" = String, Path, description = "Food stamp code"), StoreScope, TzParam),
+    responses(
+        (status = 200, description = "Food found", body = DataBody<OneFoodToSelect>),
+        (status = 404, description = "Stamp code not found"),
+    )
+)]
+async fn api_v1_get_food_by_stamp_code(
+    State(mm): State<ModelController>,
+    Path(stamp_code): Path<String>,
+    Query(scope): Query<StoreScope>,
+    Query(tz): Query<TzParam>,
+) -> Result<Json<DataBody<OneFoodToSelect>>> {
+    debug!("{:<12} - api_v1_get_food_by_stamp_code", "ROUTE_HANDLER");
+
+    let food = FoodModelController::get_by_stamp_code(
+        mm,
+        stamp_code,
+        scope.store_id,
+        tz.zone()?,
+        FoodRelations::ALL,
+    )
+    .await?;
+    let body = Json(DataBody::new(food));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/foods",
+    tag = "foods",
+    request_body = CreateFoodPayload,
+    responses(
+        (status = 201, description = "Food created, `Location` points to it", body = FoodCreatedBody,
+            headers(("location" = String, description = "URL of the created food"))),
+        (status = 400, description = "Unit not a base unit"),
+        (status = 500, description = "Create failed"),
+    )
+)]
+async fn api_v1_create_food(
+    State(mm): State<ModelController>,
+    Json(body): Json<CreateFoodPayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_create_food", "ROUTE_HANDLER");
+
+    let CreateFoodPayload {
+        food_name,
+        category,
+        stocks,
+        price,
+        total_quantity,
+        unit,
+        case_size,
+        quantity_unit,
+    } = body;
+    let data = FoodToCreate {
+        food_name,
+        category,
+        stocks,
+        price,
+        total_quantity,
+        unit: unit.unwrap_or_default(),
+        case_size,
+    };
+
+    let food_id = FoodModelController::create(mm, data, quantity_unit).await?;
+    let location = format!("/api/v1/foods/{food_id}");
+    let body = Json(FoodCreatedBody::new(food_id));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/foods/{id}",
+    tag = "foods",
+    params(("id" = i64, Path, description = "Food id"), StoreScope),
+    request_body = PatchFoodPayload,
+    responses(
+        (status = 200, description = "Given fields updated", body = DataBody<FoodToSelect>),
+        (status = 400, description = "No fields given or unit not a base unit"),
+        (status = 404, description = "Food id not found"),
+        (status = 409, description = "Unit change on a food in use"),
+        (status = 500, description = "Update failed"),
+    )
+)]
+async fn api_v1_patch_food(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    Query(scope): Query<StoreScope>,
+    Json(body): Json<PatchFoodPayload>,
+) -> Result<Json<DataBody<FoodToSelect>>> {
+    debug!("{:<12} - api_v1_patch_food", "ROUTE_HANDLER");
+
+    let PatchFoodPayload {
+        food_name,
+        category,
+        stocks,
+        price,
+        total_quantity,
+        unit,
+        case_size,
+        quantity_unit,
+    } = body;
+    let data = FoodToUpdate {
+        id,
+        store_id: scope.store_id,
+        food_name,
+        category,
+        stocks,
+        price,
+        total_quantity,
+        unit,
+        case_size: case_size.map(Some),
+    };
+
+    let updated_food = FoodModelController::update(mm, data, quantity_unit).await?;
+    let body = Json(DataBody::new(updated_food));
+    Ok(body)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/foods/{id}",
+    tag = "foods",
+    params(("id" = i64, Path, description = "Food id"), StoreScope),
+    request_body = CreateFoodPayload,
+    responses(
+        (status = 200, description = "Food replaced", body = DataBody<FoodToSelect>),
+        (status = 400, description = "Unit not a base unit"),
+        (status = 404, description = "Food id not found"),
+        (status = 409, description = "Unit change on a food in use"),
+        (status = 500, description = "Update failed"),
+    )
+)]
+async fn api_v1_put_food(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    Query(scope): Query<StoreScope>,
+    Json(body): Json<CreateFoodPayload>,
+) -> Result<Json<DataBody<FoodToSelect>>> {
+    debug!("{:<12} - api_v1_put_food", "ROUTE_HANDLER");
+
+    let CreateFoodPayload {
+        food_name,
+        category,
+        stocks,
+        price,
+        total_quantity,
+        unit,
+        case_size,
+        quantity_unit,
+    } = body;
+    let data = FoodToUpdate {
+        id,
+        store_id: scope.store_id,
+        food_name: Some(food_name),
+        category: Some(category),
+        stocks: Some(stocks),
+        price: Some(price),
+        total_quantity: Some(total_quantity),
+        unit: Some(unit.unwrap_or_default()),
+        case_size: Some(case_size),
+    };
+
+    let updated_food = FoodModelController::update(mm, data, quantity_unit).await?;
+    let body = Json(DataBody::new(updated_food));
     Ok(body)
 }
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/foods/{id}",
+    tag = "foods",
+    params(("id" = i64, Path, description = "Food id")),
+    responses(
+        (status = 200, description = "Food marked as removed", body = MessageBody),
+        (status = 404, description = "Food id not found or already removed"),
+        (status = 500, description = "Delete failed"),
+    )
+)]
+async fn api_v1_delete_food(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<MessageBody>> {
+    debug!("{:<12} - api_v1_delete_food", "ROUTE_HANDLER");
+
+    let message = FoodModelController::delete(mm, id).await?;
+    let body = Json(MessageBody::new(message));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/inventory",
+    tag = "foods",
+    params(StoreScope),
+    responses(
+        (status = 200, description = "Food counts, optionally for one store", body = DataBody<InventoryStats>),
+        (status = 500, description = "Report failed"),
+    )
+)]
+async fn api_v1_inventory_report(
+    State(mm): State<ModelController>,
+    Query(scope): Query<StoreScope>,
+) -> Result<Json<DataBody<InventoryStats>>> {
+    debug!("{:<12} - api_v1_inventory_report", "ROUTE_HANDLER");
+
+    let stats = FoodModelController::inventory_stats(mm, scope.store_id).await?;
+    let body = Json(DataBody::new(stats));
+    Ok(body)
+}
+
+// endregion: ---- v1 handlers
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::routes_crud;
+    use crate::crud_fns::ModelController;
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let bytes = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+        (status, body)
+    }
+
+    fn adobo() -> Value {
+        json!({
+            "food_name": "Adobo",
+            "category": "main",
+            "stocks": 5,
+            "price": 3.5,
+            "total_quantity": 10,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_create_then_lookup() {
+        let app = routes_crud(ModelController::in_memory());
+
+        let (status, body) = call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        let (status, body) = call(&app, "GET", &format!("/api/v1/foods/{id}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"]["data"]["food_name"], "Adobo");
+
+        let stamp_code = body["result"]["data"]["stamp_code"].as_str().unwrap();
+        let uri = format!("/api/v1/foods/stamp_code/{stamp_code}");
+        let (status, body) = call(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"]["data"]["id"], id);
+    }
+
+    #[tokio::test]
+    async fn test_patch_keeps_unset_fields() {
+        let app = routes_crud(ModelController::in_memory());
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        let uri = format!("/api/v1/foods/{id}");
+        let (status, body) = call(&app, "PATCH", &uri, Some(json!({ "stocks": 2 }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"]["data"]["stocks"], 2);
+        assert_eq!(body["result"]["data"]["price"], 3.5);
+        assert_eq!(body["result"]["data"]["total_quantity"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pages_skip_later_inserts() {
+        let app = routes_crud(ModelController::in_memory());
+        for _ in 0..5 {
+            call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        }
+
+        let (_, body) = call(&app, "GET", "/api/v1/foods?limit=2", None).await;
+        let first: Vec<i64> = ids(&body);
+        let cursor = body["result"]["next_cursor"].as_str().unwrap().to_string();
+
+        // A food created between pages lands before the first page.
+        call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+
+        let uri = format!("/api/v1/foods?limit=2&cursor={cursor}");
+        let (_, body) = call(&app, "GET", &uri, None).await;
+        let second = ids(&body);
+        let cursor = body["result"]["next_cursor"].as_str().unwrap().to_string();
+
+        let uri = format!("/api/v1/foods?limit=2&cursor={cursor}");
+        let (_, body) = call(&app, "GET", &uri, None).await;
+        let third = ids(&body);
+        assert!(body["result"]["next_cursor"].is_null());
+
+        assert_eq!(
+            [first, second, third].concat(),
+            [2004, 2003, 2002, 2001, 2000]
+        );
+
+        let tampered = if cursor.ends_with('A') { "B" } else { "A" };
+        let uri = format!(
+            "/api/v1/foods?cursor={}{tampered}",
+            &cursor[..cursor.len() - 1]
+        );
+        let (status, _) = call(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    fn ids(body: &Value) -> Vec<i64> {
+        body["result"]["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|food| food["id"].as_i64().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_follow_tz() {
+        let app = routes_crud(ModelController::in_memory());
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        let uri = format!("/api/v1/foods/{id}");
+        let (_, body) = call(&app, "GET", &uri, None).await;
+        let created_at = body["result"]["data"]["created_at"].as_str().unwrap();
+        assert!(created_at.ends_with('Z'), "{created_at}");
+        assert!(body["result"]["data"]["updated_at"].is_null());
+
+        let (_, body) = call(&app, "GET", &format!("{uri}?tz=Asia/Kolkata"), None).await;
+        let created_at = body["result"]["data"]["created_at"].as_str().unwrap();
+        assert!(created_at.ends_with("+05:30"), "{created_at}");
+
+        let (status, _) = call(&app, "GET", &format!("{uri}?tz=Nowhere/Else"), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, body) = call(&app, "PATCH", &uri, Some(json!({ "stocks": 1 }))).await;
+        assert!(body["result"]["data"]["updated_at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_quantities_normalized_to_base_unit() {
+        let app = routes_crud(ModelController::in_memory());
+        let mut beer = adobo();
+        beer["unit"] = json!("piece");
+        beer["case_size"] = json!(24);
+        beer["quantity_unit"] = json!("case");
+        let (status, body) = call(&app, "POST", "/api/v1/foods", Some(beer)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        let uri = format!("/api/v1/foods/{id}");
+        let (_, body) = call(&app, "GET", &uri, None).await;
+        assert_eq!(body["result"]["data"]["stocks"], 120);
+        assert_eq!(body["result"]["data"]["total_quantity"], 240);
+        assert_eq!(body["result"]["data"]["unit"], "piece");
+
+        let patch = json!({ "stocks": 1, "quantity_unit": "case" });
+        let (_, body) = call(&app, "PATCH", &uri, Some(patch)).await;
+        assert_eq!(body["result"]["data"]["stocks"], 24);
+
+        let patch = json!({ "stocks": 1, "quantity_unit": "kg" });
+        let (status, _) = call(&app, "PATCH", &uri, Some(patch)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_unit_change_needs_empty_stock() {
+        let app = routes_crud(ModelController::in_memory());
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+        let uri = format!("/api/v1/foods/{id}");
+
+        let patch = json!({ "unit": "g", "total_quantity": 5000 });
+        let (status, body) = call(&app, "PATCH", &uri, Some(patch.clone())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["result"]["error"], "UnitInUse");
+
+        call(&app, "PATCH", &uri, Some(json!({ "stocks": 0 }))).await;
+        let (status, _) = call(&app, "PATCH", &uri, Some(json!({ "unit": "g" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = call(&app, "PATCH", &uri, Some(patch)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["result"]["data"]["unit"], "g");
+        assert_eq!(body["result"]["data"]["total_quantity"], 5000);
+    }
+
+    #[sqlx::test]
+    async fn test_unit_change_with_lots_is_rejected(db: sqlx::PgPool) {
+        let app = routes_crud(ModelController::with_db(db.clone()));
+        let mut food = adobo();
+        food["stocks"] = json!(0);
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(food)).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        // -- Used up, the lot's quantities are still in pieces.
+        let query = "insert into food_lots (food_id, received_quantity, quantity, expiry_date, lot_status) values ($1, 5, 0, current_date, 'depleted')";
+        sqlx::query(query).bind(id).execute(&db).await.unwrap();
+
+        let uri = format!("/api/v1/foods/{id}");
+        let patch = json!({ "unit": "g", "total_quantity": 5000 });
+        let (status, _) = call(&app, "PATCH", &uri, Some(patch)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (_, body) = call(&app, "GET", &uri, None).await;
+        assert_eq!(body["result"]["data"]["unit"], "piece");
+    }
+
+    #[tokio::test]
+    async fn test_delete_is_soft() {
+        let app = routes_crud(ModelController::in_memory());
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        let uri = format!("/api/v1/foods/{id}");
+        let (status, _) = call(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, body) = call(&app, "GET", "/api/v1/foods", None).await;
+        assert_eq!(body["result"]["data"], json!([]));
+
+        // Removed foods can still be looked up by id.
+        let (status, _) = call(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = call(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&app, "DELETE", "/api/v1/foods/999", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn test_delete_unknown_and_put_clears(db: sqlx::PgPool) {
+        let app = routes_crud(ModelController::with_db(db));
+        let mut beer = adobo();
+        beer["unit"] = json!("piece");
+        beer["case_size"] = json!(24);
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(beer)).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+        let uri = format!("/api/v1/foods/{id}");
+
+        let (_, body) = call(&app, "PATCH", &uri, Some(json!({ "stocks": 1 }))).await;
+        assert_eq!(body["result"]["data"]["case_size"], 24);
+
+        // -- PUT replaces the food, so a missing case size clears it.
+        let (status, body) = call(&app, "PUT", &uri, Some(adobo())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["result"]["data"]["case_size"].is_null());
+
+        let (status, _) = call(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = call(&app, "PUT", "/api/v1/foods/999", Some(adobo())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_routes_are_deprecated() {
+        let app = routes_crud(ModelController::in_memory());
+        let (_, body) = call(&app, "POST", "/api/v1/foods", Some(adobo())).await;
+        let id = body["result"]["food_id"].as_i64().unwrap();
+
+        let headers = |uri: String| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                app.oneshot(req).await.unwrap().headers().clone()
+            }
+        };
+
+        let legacy = headers(format!("/api/select/{id}")).await;
+        assert_eq!(legacy["deprecation"], "true");
+        assert_eq!(legacy["link"], "</api/v1/foods>; rel=\"successor-version\"");
+
+        let v1 = headers(format!("/api/v1/foods/{id}")).await;
+        assert!(v1.get("deprecation").is_none());
+        assert!(v1.get("link").is_none());
+    }
+}
@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::{http::StatusCode, response::IntoResponse, Json};
 use serde::Serialize;
+use serde_json::{json, Value};
 use tracing::debug;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -11,23 +12,132 @@ pub enum Error {
     MissingENV(&'static str),
     ENVWrongFormat(&'static str),
     FailToConnectPool(String),
-    FailedToCreateFood,
+    NoDatabase,
     CreateFailed(String),
-    FailedToSelectFood,
     SelectFailed(String),
-    FailedToUpdateFood,
     UpdateFailed(String),
-    FailedToDeleteFood,
+    NothingToUpdate(String),
     DeleteFailed(String),
     FoodIdNotFound(String),
     FoodStampCodeNotFound(String),
+    StoreNotFound(String),
+    TransferFailed(String),
+    InsufficientStock(String),
+    InvalidLot(String),
+    OrderFailed(String),
+    InvalidDuration(String),
+    UnitConversion(String),
+    UnitInUse(String),
+    RecipeFailed(String),
+    ReservationNotFound(String),
+    ReservationFailed(String),
+    InvalidReservation(String),
+    ApiKeyNotFound(String),
+    InvalidApiKey(String),
+    ImageNotFound(String),
+    InvalidImage(String),
+    ImageStoreFailed(String),
+    InvalidTimezone(String),
+    InvalidCursor(String),
+    StockTakeNotFound(String),
+    StockTakeFailed(String),
+    InvalidStockTake(String),
+    InvalidReorderParams(String),
+    MigrationFailed(String),
+    CsvFailed(String),
+    InvalidStockAdjustment(String),
+    ScheduledPriceNotFound(String),
+    InvalidScheduledPrice(String),
+    InvalidSchedule(String),
+    JobNotFound(String),
+    WebhookNotFound(String),
+    InvalidWebhook(String),
+    WebhookDeliveryNotFound(String),
+}
+
+impl Error {
+    /// A missing row becomes `not_found`, any other failure a `SelectFailed`.
+    pub fn row_not_found(err: sqlx::Error, not_found: Error) -> Error {
+        match err {
+            sqlx::Error::RowNotFound => not_found,
+            err => Error::SelectFailed(err.to_string()),
+        }
+    }
+
+    /// The HTTP status the error answers with. Anything the client can fix
+    /// is a 4xx, what is left is a failure on our side.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::FoodIdNotFound(_)
+            | Error::FoodStampCodeNotFound(_)
+            | Error::StoreNotFound(_)
+            | Error::ReservationNotFound(_)
+            | Error::ApiKeyNotFound(_)
+            | Error::ImageNotFound(_)
+            | Error::StockTakeNotFound(_)
+            | Error::ScheduledPriceNotFound(_)
+            | Error::JobNotFound(_)
+            | Error::WebhookNotFound(_)
+            | Error::WebhookDeliveryNotFound(_) => StatusCode::NOT_FOUND,
+            Error::InsufficientStock(_)
+            | Error::UnitInUse(_)
+            | Error::ReservationFailed(_)
+            | Error::StockTakeFailed(_) => StatusCode::CONFLICT,
+            Error::NothingToUpdate(_)
+            | Error::TransferFailed(_)
+            | Error::OrderFailed(_)
+            | Error::InvalidLot(_)
+            | Error::InvalidDuration(_)
+            | Error::UnitConversion(_)
+            | Error::RecipeFailed(_)
+            | Error::InvalidReservation(_)
+            | Error::InvalidApiKey(_)
+            | Error::InvalidImage(_)
+            | Error::InvalidTimezone(_)
+            | Error::InvalidCursor(_)
+            | Error::InvalidStockTake(_)
+            | Error::InvalidReorderParams(_)
+            | Error::CsvFailed(_)
+            | Error::InvalidStockAdjustment(_)
+            | Error::InvalidScheduledPrice(_)
+            | Error::InvalidWebhook(_) => StatusCode::BAD_REQUEST,
+            Error::MissingENV(_)
+            | Error::ENVWrongFormat(_)
+            | Error::FailToConnectPool(_)
+            | Error::NoDatabase
+            | Error::CreateFailed(_)
+            | Error::SelectFailed(_)
+            | Error::UpdateFailed(_)
+            | Error::DeleteFailed(_)
+            | Error::ImageStoreFailed(_)
+            | Error::MigrationFailed(_)
+            | Error::InvalidSchedule(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
         debug!("{:<12} - crud_fns error {self:?}", "INTO_RES");
 
-        let mut response = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        let status = self.status();
+        let mut response = if status.is_server_error() {
+            status.into_response()
+        } else {
+            // -- Serialized as `{"Variant": "message"}`.
+            let (error, message) = match serde_json::to_value(&self) {
+                Ok(Value::Object(map)) => map.into_iter().next().unzip(),
+                _ => (None, None),
+            };
+            let body = json!({
+                "result": {
+                    "error": error,
+                    "message": message,
+                    "status": false
+                }
+            });
+            (status, Json(body)).into_response()
+        };
 
         response.extensions_mut().insert(Arc::new(self));
 
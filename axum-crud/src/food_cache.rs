@@ -0,0 +1,413 @@
+//! Bounded LRU cache, with a TTL, in front of the single-food lookups
+//! `FoodModelController::get_by_id` and `get_by_stamp_code`.
+//!
+//! Entries are dropped by food id, found through an index of each food's
+//! keys rather than a scan of the cache. Writes made through
+//! `FoodModelController` drop them right away. Every change to
+//! `foods_table` or `food_stocks`, on any replica and from any code path, is
+//! also announced on the `food_changed` channel by a trigger, and
+//! `spawn_listener` drops the food on each notification. Until the listener
+//! is connected the cache is bypassed, and it is cleared whenever the
+//! connection drops, as notifications sent meanwhile are lost.
+//!
+//! A lookup missing the cache may read from the replica, which can still
+//! hold the old row when the notification arrives. The food is therefore
+//! dropped a second time once the replica's allowed lag has passed.
+//! Requests asking to read their own writes skip the cache.
+//!
+//! Only the food row is cached, images are still loaded on every lookup.
+
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+use sqlx::postgres::PgListener;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::core_config,
+    crud_fns::{ModelController, OneFoodToSelect},
+    metrics::metrics,
+    replica::Reads,
+};
+
+/// Channel the `notify_food_changed` trigger sends the food id on.
+const CHANNEL: &str = "food_changed";
+
+/// Wait before connecting the listener again.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum FoodKey {
+    Id(i64, Option<i64>),
+    StampCode(String, Option<i64>),
+}
+
+impl FoodKey {
+    fn lookup(&self) -> &'static str {
+        match self {
+            FoodKey::Id(..) => "id",
+            FoodKey::StampCode(..) => "stamp_code",
+        }
+    }
+}
+
+struct Cached {
+    food: OneFoodToSelect,
+    at: Instant,
+}
+
+/// The LRU and, per food id, the keys it is cached under. Kept in step by
+/// going through these methods only.
+struct Entries {
+    lru: LruCache<FoodKey, Cached>,
+    keys: HashMap<i64, HashSet<FoodKey>>,
+}
+
+impl Entries {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Entries {
+            lru: LruCache::new(capacity),
+            keys: HashMap::new(),
+        }
+    }
+
+    fn put(&mut self, key: FoodKey, cached: Cached) {
+        let food_id = cached.food.id;
+        // -- The entry replaced, or the least recently used one evicted.
+        if let Some((old_key, old)) = self.lru.push(key.clone(), cached) {
+            self.unindex(old.food.id, &old_key);
+        }
+        self.keys.entry(food_id).or_default().insert(key);
+    }
+
+    fn pop(&mut self, key: &FoodKey) {
+        if let Some(cached) = self.lru.pop(key) {
+            self.unindex(cached.food.id, key);
+        }
+    }
+
+    fn remove_food(&mut self, food_id: i64) {
+        for key in self.keys.remove(&food_id).unwrap_or_default() {
+            self.lru.pop(&key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.lru.clear();
+        self.keys.clear();
+    }
+
+    fn unindex(&mut self, food_id: i64, key: &FoodKey) {
+        if let Some(keys) = self.keys.get_mut(&food_id) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.keys.remove(&food_id);
+            }
+        }
+    }
+}
+
+pub struct FoodCache {
+    /// `None` when `FOOD_CACHE_CAPACITY` is 0.
+    foods: Option<Mutex<Entries>>,
+    ttl: Duration,
+    /// Whether invalidations are coming in, see `spawn_listener`.
+    live: AtomicBool,
+    /// Bumped on every invalidation, so a lookup that raced one does not
+    /// cache what it read before it.
+    generation: AtomicU64,
+}
+
+impl FoodCache {
+    pub fn new(capacity: usize, ttl: Duration, live: bool) -> Self {
+        FoodCache {
+            foods: NonZeroUsize::new(capacity).map(|cap| Mutex::new(Entries::new(cap))),
+            ttl,
+            live: AtomicBool::new(live),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Sized from `FOOD_CACHE_CAPACITY` and `FOOD_CACHE_TTL_SEC`.
+    pub fn from_config(live: bool) -> Self {
+        let config = core_config();
+
+        FoodCache::new(
+            config.FOOD_CACHE_CAPACITY,
+            Duration::from_secs(config.FOOD_CACHE_TTL_SEC),
+            live,
+        )
+    }
+
+    /// The cached food, unless it expired. Counted as a hit or a miss
+    /// while the cache is in use.
+    pub fn get(&self, key: &FoodKey) -> Option<OneFoodToSelect> {
+        if Reads::current() == Reads::OwnWrites {
+            return None;
+        }
+        let mut foods = self.foods()?;
+
+        let food = match foods.lru.get(key) {
+            Some(cached) if cached.at.elapsed() < self.ttl => Some(cached.food.clone()),
+            Some(_) => {
+                foods.pop(key);
+                None
+            }
+            None => None,
+        };
+        metrics().food_cache_lookup(key.lookup(), food.is_some());
+
+        food
+    }
+
+    /// Taken before loading a food, to be handed to `put` along with it.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Caches a food loaded at `generation`, unless it was invalidated since.
+    pub fn put(&self, key: FoodKey, food: &OneFoodToSelect, generation: u64) {
+        let Some(mut foods) = self.foods() else {
+            return;
+        };
+        // -- Checked under the lock `invalidate` bumps it under.
+        if self.generation() != generation {
+            return;
+        }
+
+        let cached = Cached {
+            food: food.clone(),
+            at: Instant::now(),
+        };
+        foods.put(key, cached);
+    }
+
+    /// Drops every entry of the food, for any store and either key.
+    pub fn invalidate(&self, food_id: i64) {
+        let Some(foods) = &self.foods else {
+            return;
+        };
+        let mut foods = lock(foods);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        foods.remove_food(food_id);
+    }
+
+    pub fn clear(&self) {
+        let Some(foods) = &self.foods else {
+            return;
+        };
+        let mut foods = lock(foods);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        foods.clear();
+    }
+
+    fn set_live(&self, live: bool) {
+        self.live.store(live, Ordering::Release);
+        if !live {
+            self.clear();
+        }
+    }
+
+    /// The entries, unless the cache is off or not live.
+    fn foods(&self) -> Option<MutexGuard<'_, Entries>> {
+        if !self.live.load(Ordering::Acquire) {
+            return None;
+        }
+
+        self.foods.as_ref().map(lock)
+    }
+}
+
+/// A panic while holding the lock cannot leave the cache half updated, so a
+/// poisoned lock is taken as is.
+fn lock(foods: &Mutex<Entries>) -> MutexGuard<'_, Entries> {
+    foods
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Listens on `food_changed` and invalidates the food cache with each
+/// notification. The cache is used only while this is connected. Does
+/// nothing for the in-memory backend, whose cache is always live.
+pub fn spawn_listener(mm: ModelController) {
+    let Ok(db) = mm.db().cloned() else {
+        return;
+    };
+    let replica_lag = mm
+        .replica()
+        .map(|_| Duration::from_millis(core_config().DB_READ_MAX_LAG_MS));
+
+    tokio::spawn(async move {
+        let cache = mm.food_cache();
+
+        loop {
+            let mut listener = match PgListener::connect_with(&db).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    warn!(
+                        "{:<12} - food cache listener not connected {err:?}",
+                        "FOOD_CACHE"
+                    );
+                    sleep(RECONNECT_DELAY).await;
+                    continue;
+                }
+            };
+            if let Err(err) = listener.listen(CHANNEL).await {
+                warn!("{:<12} - food cache listen failed {err:?}", "FOOD_CACHE");
+                sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+            cache.clear();
+            cache.set_live(true);
+            info!("{:<12} - listening on {CHANNEL}", "FOOD_CACHE");
+
+            // -- `try_recv` gives `None` once the connection is lost.
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => match notification.payload().parse::<i64>() {
+                        Ok(food_id) => {
+                            debug!("{:<12} - food {food_id} changed", "FOOD_CACHE");
+                            cache.invalidate(food_id);
+                            if let Some(lag) = replica_lag {
+                                let mm = mm.clone();
+                                tokio::spawn(async move {
+                                    sleep(lag).await;
+                                    mm.food_cache().invalidate(food_id);
+                                });
+                            }
+                        }
+                        Err(_) => cache.clear(),
+                    },
+                    Ok(None) => {
+                        warn!("{:<12} - listener connection lost", "FOOD_CACHE");
+                        break;
+                    }
+                    // -- Shutting down.
+                    Err(_) if db.is_closed() => return,
+                    Err(err) => {
+                        warn!("{:<12} - listener failed {err:?}", "FOOD_CACHE");
+                        break;
+                    }
+                }
+            }
+
+            cache.set_live(false);
+            sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::units::Unit;
+
+    fn food(id: i64) -> OneFoodToSelect {
+        OneFoodToSelect {
+            cid: id.to_string(),
+            mid: id.to_string(),
+            id,
+            stamp_code: format!("S{id}"),
+            food_name: format!("food {id}"),
+            category: "test".to_string(),
+            stocks: 1,
+            price: 1.0,
+            total_quantity: 1,
+            unit: Unit::Piece,
+            case_size: None,
+            created_at: OffsetDateTime::UNIX_EPOCH,
+            updated_at: None,
+            images: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn invalidate_drops_every_key_of_the_food() {
+        let cache = FoodCache::new(10, Duration::from_secs(60), true);
+        let generation = cache.generation();
+        cache.put(FoodKey::Id(1, None), &food(1), generation);
+        cache.put(FoodKey::Id(1, Some(7)), &food(1), generation);
+        cache.put(
+            FoodKey::StampCode("S1".to_string(), None),
+            &food(1),
+            generation,
+        );
+        cache.put(FoodKey::Id(2, None), &food(2), generation);
+
+        cache.invalidate(1);
+
+        assert!(cache.get(&FoodKey::Id(1, None)).is_none());
+        assert!(cache.get(&FoodKey::Id(1, Some(7))).is_none());
+        assert!(cache
+            .get(&FoodKey::StampCode("S1".to_string(), None))
+            .is_none());
+        assert_eq!(
+            cache.get(&FoodKey::Id(2, None)).map(|food| food.id),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn stale_loads_expired_entries_and_overflow_are_not_served() {
+        let cache = FoodCache::new(2, Duration::from_secs(60), true);
+
+        // -- Loaded before an invalidation, so not cached.
+        let generation = cache.generation();
+        cache.invalidate(1);
+        cache.put(FoodKey::Id(1, None), &food(1), generation);
+        assert!(cache.get(&FoodKey::Id(1, None)).is_none());
+
+        // -- Least recently used goes first.
+        let generation = cache.generation();
+        cache.put(FoodKey::Id(1, None), &food(1), generation);
+        cache.put(FoodKey::Id(2, None), &food(2), generation);
+        cache.get(&FoodKey::Id(1, None));
+        cache.put(FoodKey::Id(3, None), &food(3), generation);
+        assert!(cache.get(&FoodKey::Id(2, None)).is_none());
+        assert!(cache.get(&FoodKey::Id(1, None)).is_some());
+
+        let expired = FoodCache::new(2, Duration::ZERO, true);
+        expired.put(FoodKey::Id(1, None), &food(1), expired.generation());
+        assert!(expired.get(&FoodKey::Id(1, None)).is_none());
+
+        let not_live = FoodCache::new(2, Duration::from_secs(60), false);
+        not_live.put(FoodKey::Id(1, None), &food(1), not_live.generation());
+        assert!(not_live.get(&FoodKey::Id(1, None)).is_none());
+    }
+
+    #[test]
+    fn key_index_follows_evictions() {
+        let cache = FoodCache::new(2, Duration::from_secs(60), true);
+        let generation = cache.generation();
+        cache.put(FoodKey::Id(1, None), &food(1), generation);
+        cache.put(FoodKey::Id(2, None), &food(2), generation);
+        cache.put(FoodKey::Id(2, Some(7)), &food(2), generation);
+
+        let indexed = |cache: &FoodCache| {
+            let foods = lock(cache.foods.as_ref().unwrap());
+            let mut ids: Vec<(i64, usize)> = foods
+                .keys
+                .iter()
+                .map(|(id, keys)| (*id, keys.len()))
+                .collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(indexed(&cache), [(2, 2)]);
+
+        cache.invalidate(2);
+        assert_eq!(indexed(&cache), []);
+        assert_eq!(lock(cache.foods.as_ref().unwrap()).lru.len(), 0);
+    }
+}
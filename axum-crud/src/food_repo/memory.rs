@@ -0,0 +1,443 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+use crate::{
+    crud_fns::{FoodToCreate, FoodToSelect, FoodToUpdate, InventoryStats, OneFoodToSelect},
+    error::{Error, Result},
+    pagination::{cid_key, PageRequest},
+    stores_fns::{
+        FoodStoreStock, StockTransfer, StockTransferred, StoreToCreate, StoreToSelect,
+        StoreToUpdate,
+    },
+    timestamps::created_date,
+    units::{check_unit_change, Unit},
+    utils::{b32_hex, b64u},
+};
+
+use super::FoodRepository;
+
+/// First id handed out, as `foods_table`'s identity column.
+const FIRST_ID: i64 = 2000;
+
+#[derive(Clone, Debug)]
+struct FoodRow {
+    cid: String,
+    ctime: OffsetDateTime,
+    mid: String,
+    mtime: Option<OffsetDateTime>,
+    id: i64,
+    stamp_code: String,
+    food_name: String,
+    category: String,
+    stocks: i32,
+    price: f64,
+    total_quantity: i32,
+    unit: Unit,
+    case_size: Option<i32>,
+    removed: bool,
+}
+
+impl FoodRow {
+    fn to_select(&self, stocks: i32) -> FoodToSelect {
+        FoodToSelect {
+            cid: self.cid.clone(),
+            mid: self.mid.clone(),
+            id: self.id,
+            stamp_code: self.stamp_code.clone(),
+            food_name: self.food_name.clone(),
+            category: self.category.clone(),
+            stocks,
+            price: self.price,
+            total_quantity: self.total_quantity,
+            unit: self.unit,
+            case_size: self.case_size,
+            created_at: self.ctime,
+            updated_at: self.mtime,
+            created_date: None,
+            images: Vec::new(),
+        }
+    }
+
+    fn to_one(&self, stocks: i32) -> OneFoodToSelect {
+        OneFoodToSelect {
+            cid: self.cid.clone(),
+            mid: self.mid.clone(),
+            id: self.id,
+            stamp_code: self.stamp_code.clone(),
+            food_name: self.food_name.clone(),
+            category: self.category.clone(),
+            stocks,
+            price: self.price,
+            total_quantity: self.total_quantity,
+            unit: self.unit,
+            case_size: self.case_size,
+            created_at: self.ctime,
+            updated_at: self.mtime,
+            images: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct StoreRow {
+    ctime: OffsetDateTime,
+    id: i64,
+    store_name: String,
+    address: Option<String>,
+}
+
+impl StoreRow {
+    fn to_select(&self) -> StoreToSelect {
+        StoreToSelect {
+            id: self.id,
+            store_name: self.store_name.clone(),
+            address: self.address.clone(),
+            created_date: created_date(self.ctime),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    foods: Vec<FoodRow>,
+    stores: Vec<StoreRow>,
+    /// (food_id, store_id) -> stocks
+    food_stocks: BTreeMap<(i64, i64), i32>,
+}
+
+impl MemoryStore {
+    fn insert_food(&mut self, data: FoodToCreate) -> Result<i64> {
+        let id = FIRST_ID + self.foods.len() as i64;
+        self.foods.push(FoodRow {
+            cid: b64u()?,
+            ctime: OffsetDateTime::now_utc(),
+            mid: b64u()?,
+            mtime: None,
+            id,
+            stamp_code: b32_hex()?,
+            food_name: data.food_name,
+            category: data.category,
+            stocks: data.stocks,
+            price: data.price as f64,
+            total_quantity: data.total_quantity,
+            unit: data.unit,
+            case_size: data.case_size,
+            removed: false,
+        });
+
+        Ok(id)
+    }
+
+    fn check_store(&self, store_id: Option<i64>) -> Result<()> {
+        match store_id {
+            Some(id) if !self.stores.iter().any(|s| s.id == id) => {
+                Err(Error::StoreNotFound(id.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The food's own stock, or the store's when scoped.
+    fn stocks(&self, food: &FoodRow, store_id: Option<i64>) -> i32 {
+        match store_id {
+            Some(store_id) => self
+                .food_stocks
+                .get(&(food.id, store_id))
+                .copied()
+                .unwrap_or(0),
+            None => food.stocks,
+        }
+    }
+}
+
+/// Process-local inventory with the semantics of `PgFoodRepository`.
+#[derive(Clone, Default)]
+pub struct InMemoryFoodRepository {
+    store: Arc<Mutex<MemoryStore>>,
+}
+
+#[async_trait]
+impl FoodRepository for InMemoryFoodRepository {
+    async fn create(&self, data: FoodToCreate) -> Result<i64> {
+        self.store.lock().unwrap().insert_food(data)
+    }
+
+    async fn create_many(&self, foods: Vec<FoodToCreate>) -> Result<Vec<i64>> {
+        let mut store = self.store.lock().unwrap();
+
+        foods
+            .into_iter()
+            .map(|data| store.insert_food(data))
+            .collect()
+    }
+
+    async fn select(&self, store_id: Option<i64>, page: PageRequest) -> Result<Vec<FoodToSelect>> {
+        let store = self.store.lock().unwrap();
+        store.check_store(store_id)?;
+
+        let after = page.after.as_deref().map(cid_key).transpose()?;
+        let mut foods = Vec::new();
+        for f in store.foods.iter().filter(|f| !f.removed) {
+            let key = cid_key(&f.cid)?;
+            if after.as_ref().is_none_or(|after| key < *after) {
+                foods.push((key, f));
+            }
+        }
+        foods.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        let limit = page.limit.map_or(usize::MAX, |limit| limit as usize);
+        let foods = foods
+            .into_iter()
+            .take(limit)
+            .map(|(_, f)| f.to_select(store.stocks(f, store_id)))
+            .collect();
+
+        Ok(foods)
+    }
+
+    async fn get_by_id(&self, id: i64, store_id: Option<i64>) -> Result<OneFoodToSelect> {
+        let store = self.store.lock().unwrap();
+        store.check_store(store_id)?;
+
+        store
+            .foods
+            .iter()
+            .find(|f| f.id == id)
+            .map(|f| f.to_one(store.stocks(f, store_id)))
+            .ok_or(Error::FoodIdNotFound(format!("no food with id {id}")))
+    }
+
+    async fn get_by_stamp_code(
+        &self,
+        stamp_code: String,
+        store_id: Option<i64>,
+    ) -> Result<OneFoodToSelect> {
+        let store = self.store.lock().unwrap();
+        store.check_store(store_id)?;
+
+        store
+            .foods
+            .iter()
+            .find(|f| f.stamp_code == stamp_code)
+            .map(|f| f.to_one(store.stocks(f, store_id)))
+            .ok_or(Error::FoodStampCodeNotFound(format!(
+                "no food with stamp code {stamp_code}"
+            )))
+    }
+
+    async fn update(&self, data: FoodToUpdate) -> Result<FoodToSelect> {
+        let mut guard = self.store.lock().unwrap();
+        let store = &mut *guard;
+        store.check_store(data.store_id)?;
+
+        // -- The in-memory backend keeps no lots, movements or recipes.
+        let store_stock = store
+            .food_stocks
+            .iter()
+            .any(|(&(food_id, _), &stocks)| food_id == data.id && stocks != 0);
+        let food =
+            store
+                .foods
+                .iter_mut()
+                .find(|f| f.id == data.id)
+                .ok_or(Error::FoodIdNotFound(format!(
+                    "no food with id {}",
+                    data.id
+                )))?;
+        check_unit_change(
+            food.unit,
+            data.unit,
+            store_stock || food.stocks != 0,
+            food.total_quantity,
+            data.total_quantity,
+        )?;
+
+        if let Some(food_name) = data.food_name {
+            food.food_name = food_name;
+        }
+        if let Some(category) = data.category {
+            food.category = category;
+        }
+        if let Some(stocks) = data.stocks {
+            match data.store_id {
+                Some(store_id) => {
+                    store.food_stocks.insert((food.id, store_id), stocks);
+                }
+                None => food.stocks = stocks,
+            }
+        }
+        if let Some(price) = data.price {
+            food.price = price as f64;
+        }
+        if let Some(total_quantity) = data.total_quantity {
+            food.total_quantity = total_quantity;
+        }
+        if let Some(unit) = data.unit {
+            food.unit = unit;
+        }
+        if let Some(case_size) = data.case_size {
+            food.case_size = case_size;
+        }
+        food.mtime = Some(OffsetDateTime::now_utc());
+
+        let food = food.clone();
+        Ok(food.to_select(store.stocks(&food, data.store_id)))
+    }
+
+    async fn delete(&self, id: i64) -> Result<String> {
+        let mut store = self.store.lock().unwrap();
+
+        let Some(food) = store.foods.iter_mut().find(|f| f.id == id && !f.removed) else {
+            return Err(Error::FoodIdNotFound(format!("no food with id {id}")));
+        };
+        food.removed = true;
+
+        Ok(String::from("Removed food successfully"))
+    }
+
+    async fn restore(&self, id: i64) -> Result<OneFoodToSelect> {
+        {
+            let mut store = self.store.lock().unwrap();
+            let Some(food) = store.foods.iter_mut().find(|f| f.id == id && f.removed) else {
+                return Err(Error::FoodIdNotFound(format!(
+                    "no removed food with id {id}"
+                )));
+            };
+            food.removed = false;
+        }
+
+        self.get_by_id(id, None).await
+    }
+
+    async fn inventory_stats(&self, store_id: Option<i64>) -> Result<InventoryStats> {
+        let store = self.store.lock().unwrap();
+        store.check_store(store_id)?;
+
+        let active = store.foods.iter().filter(|f| !f.removed);
+        let stats = InventoryStats {
+            total: active.clone().count() as i64,
+            out_of_stock: active.filter(|f| store.stocks(f, store_id) <= 0).count() as i64,
+        };
+
+        Ok(stats)
+    }
+
+    // -- Stores
+
+    async fn create_store(&self, data: StoreToCreate) -> Result<i64> {
+        let mut store = self.store.lock().unwrap();
+
+        if store.stores.iter().any(|s| s.store_name == data.store_name) {
+            return Err(Error::CreateFailed(format!(
+                "store name {} already exists",
+                data.store_name
+            )));
+        }
+
+        let id = store.stores.len() as i64 + 1;
+        store.stores.push(StoreRow {
+            ctime: OffsetDateTime::now_utc(),
+            id,
+            store_name: data.store_name,
+            address: data.address,
+        });
+
+        Ok(id)
+    }
+
+    async fn select_stores(&self) -> Result<Vec<StoreToSelect>> {
+        let store = self.store.lock().unwrap();
+
+        let mut stores: Vec<StoreToSelect> = store.stores.iter().map(StoreRow::to_select).collect();
+        stores.sort_by(|a, b| a.store_name.cmp(&b.store_name));
+
+        Ok(stores)
+    }
+
+    async fn get_store(&self, id: i64) -> Result<StoreToSelect> {
+        let store = self.store.lock().unwrap();
+
+        store
+            .stores
+            .iter()
+            .find(|s| s.id == id)
+            .map(StoreRow::to_select)
+            .ok_or(Error::StoreNotFound(id.to_string()))
+    }
+
+    async fn update_store(&self, data: StoreToUpdate) -> Result<StoreToSelect> {
+        let mut store = self.store.lock().unwrap();
+
+        let row = store
+            .stores
+            .iter_mut()
+            .find(|s| s.id == data.id)
+            .ok_or(Error::UpdateFailed(format!("no store with id {}", data.id)))?;
+
+        if let Some(store_name) = data.store_name {
+            row.store_name = store_name;
+        }
+        if let Some(address) = data.address {
+            row.address = Some(address);
+        }
+
+        Ok(row.to_select())
+    }
+
+    async fn food_stocks(&self, food_id: i64) -> Result<Vec<FoodStoreStock>> {
+        let store = self.store.lock().unwrap();
+
+        let mut stocks: Vec<FoodStoreStock> = store
+            .food_stocks
+            .iter()
+            .filter(|((f_id, _), _)| *f_id == food_id)
+            .filter_map(|((_, store_id), stocks)| {
+                let row = store.stores.iter().find(|s| s.id == *store_id)?;
+                Some(FoodStoreStock {
+                    store_id: *store_id,
+                    store_name: row.store_name.clone(),
+                    stocks: *stocks,
+                })
+            })
+            .collect();
+        stocks.sort_by(|a, b| a.store_name.cmp(&b.store_name));
+
+        Ok(stocks)
+    }
+
+    async fn transfer_stock(&self, data: StockTransfer) -> Result<StockTransferred> {
+        let mut store = self.store.lock().unwrap();
+        store.check_store(Some(data.from_store_id))?;
+        store.check_store(Some(data.to_store_id))?;
+
+        let from_key = (data.food_id, data.from_store_id);
+        let available = store.food_stocks.get(&from_key).copied().unwrap_or(0);
+        if available < data.quantity {
+            return Err(Error::InsufficientStock(format!(
+                "store {} holds less than {} of food {}",
+                data.from_store_id, data.quantity, data.food_id
+            )));
+        }
+
+        let from_stocks = available - data.quantity;
+        store.food_stocks.insert(from_key, from_stocks);
+        let to_stocks = store
+            .food_stocks
+            .entry((data.food_id, data.to_store_id))
+            .or_insert(0);
+        *to_stocks += data.quantity;
+
+        Ok(StockTransferred {
+            food_id: data.food_id,
+            from_store_id: data.from_store_id,
+            from_stocks,
+            to_store_id: data.to_store_id,
+            to_stocks: *to_stocks,
+        })
+    }
+}
@@ -0,0 +1,79 @@
+//! Storage backends for foods and the stores holding their stock.
+//!
+//! `FoodModelController` and `StoreModelController` go through a
+//! `FoodRepository`, so the same handlers run against Postgres
+//! (`PgFoodRepository`) or a process-local store (`InMemoryFoodRepository`,
+//! used when no database is available).
+//!
+//! Food reads, updates and reports take an optional `store_id`. Without it,
+//! `stocks` is the food's own (unscoped) stock; with it, the stock that store
+//! holds of the food (0 when it never had any).
+
+mod memory;
+mod pg;
+
+pub use memory::InMemoryFoodRepository;
+pub use pg::PgFoodRepository;
+
+use async_trait::async_trait;
+
+use crate::{
+    crud_fns::{FoodToCreate, FoodToSelect, FoodToUpdate, InventoryStats, OneFoodToSelect},
+    error::Result,
+    pagination::PageRequest,
+    stores_fns::{
+        FoodStoreStock, StockTransfer, StockTransferred, StoreToCreate, StoreToSelect,
+        StoreToUpdate,
+    },
+};
+
+#[async_trait]
+pub trait FoodRepository: Send + Sync {
+    /// Inserts a food and returns its id.
+    async fn create(&self, data: FoodToCreate) -> Result<i64>;
+
+    /// Inserts the foods all or none, returning their ids in order.
+    async fn create_many(&self, foods: Vec<FoodToCreate>) -> Result<Vec<i64>>;
+
+    /// Foods not removed, newest `cid` first, limited to `page`.
+    async fn select(&self, store_id: Option<i64>, page: PageRequest) -> Result<Vec<FoodToSelect>>;
+
+    /// Looks a food up by id, removed ones included.
+    async fn get_by_id(&self, id: i64, store_id: Option<i64>) -> Result<OneFoodToSelect>;
+
+    async fn get_by_stamp_code(
+        &self,
+        stamp_code: String,
+        store_id: Option<i64>,
+    ) -> Result<OneFoodToSelect>;
+
+    /// Updates the `Some` fields of `data`, leaving the others untouched.
+    /// With `data.store_id`, `stocks` sets that store's stock instead.
+    async fn update(&self, data: FoodToUpdate) -> Result<FoodToSelect>;
+
+    /// Soft delete: the food is flagged `removed`, not dropped.
+    async fn delete(&self, id: i64) -> Result<String>;
+
+    /// Brings a removed food back as `active`.
+    async fn restore(&self, id: i64) -> Result<OneFoodToSelect>;
+
+    async fn inventory_stats(&self, store_id: Option<i64>) -> Result<InventoryStats>;
+
+    // -- Stores
+
+    async fn create_store(&self, data: StoreToCreate) -> Result<i64>;
+
+    async fn select_stores(&self) -> Result<Vec<StoreToSelect>>;
+
+    async fn get_store(&self, id: i64) -> Result<StoreToSelect>;
+
+    async fn update_store(&self, data: StoreToUpdate) -> Result<StoreToSelect>;
+
+    /// Stores holding a stock entry for the food.
+    async fn food_stocks(&self, food_id: i64) -> Result<Vec<FoodStoreStock>>;
+
+    /// Debits `from_store_id` and credits `to_store_id` atomically. Fails
+    /// without changes when the source store holds less than `quantity`
+    /// beyond what its held reservations need.
+    async fn transfer_stock(&self, data: StockTransfer) -> Result<StockTransferred>;
+}
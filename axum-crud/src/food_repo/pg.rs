@@ -0,0 +1,543 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor};
+use tracing::debug;
+
+use crate::{
+    crud_fns::{FoodToCreate, FoodToSelect, FoodToUpdate, InventoryStats, OneFoodToSelect},
+    error::{Error, Result},
+    pagination::PageRequest,
+    replica::{route, Replica},
+    stock_fns::{lock_stock, record_movement},
+    store::Db,
+    stores_fns::{
+        FoodStoreStock, StockTransfer, StockTransferred, StoreToCreate, StoreToSelect,
+        StoreToUpdate,
+    },
+    units::{check_unit_change, Unit},
+    utils::{b32_hex, b64u},
+};
+
+use super::FoodRepository;
+
+#[derive(Debug, Serialize, FromRow)]
+struct FoodsToReturn {
+    id: i64,
+}
+
+#[derive(Clone)]
+pub struct PgFoodRepository {
+    db: Db,
+    replica: Option<Arc<Replica>>,
+}
+
+impl PgFoodRepository {
+    pub fn new(db: Db, replica: Option<Arc<Replica>>) -> Self {
+        PgFoodRepository { db, replica }
+    }
+
+    /// The pool for lookups, listings and reports, see `replica`.
+    fn read_db(&self) -> &Db {
+        route(&self.db, self.replica.as_deref())
+    }
+
+    /// Rejects a scope naming a store that does not exist.
+    async fn check_store(&self, store_id: Option<i64>) -> Result<()> {
+        let Some(store_id) = store_id else {
+            return Ok(());
+        };
+
+        let query = "select exists(select 1 from stores where id = $1)";
+
+        match sqlx::query_scalar::<_, bool>(query)
+            .bind(store_id)
+            .fetch_one(self.read_db())
+            .await
+        {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(Error::StoreNotFound(store_id.to_string())),
+            Err(err) => {
+                debug!("{:<12} - check_store error", "ERROR_CONTROLLER");
+                Err(Error::SelectFailed(err.to_string()))
+            }
+        }
+    }
+}
+
+async fn insert_food<'e>(conn: impl PgExecutor<'e>, data: FoodToCreate) -> Result<i64> {
+    let query = "insert into foods_table (cid, mid, stamp_code, food_name, category, stocks, price, total_quantity, unit, case_size) values ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10) returning id";
+    let cid = b64u().unwrap();
+    let mid = b64u().unwrap();
+    let stamp_code = b32_hex().unwrap();
+
+    let FoodToCreate {
+        food_name,
+        category,
+        stocks,
+        price,
+        total_quantity,
+        unit,
+        case_size,
+    } = data;
+
+    match sqlx::query_as::<_, FoodsToReturn>(query)
+        .bind(cid)
+        .bind(mid)
+        .bind(stamp_code)
+        .bind(food_name)
+        .bind(category)
+        .bind(stocks)
+        .bind(price)
+        .bind(total_quantity)
+        .bind(unit)
+        .bind(case_size)
+        .fetch_one(conn)
+        .await
+    {
+        Ok(food) => Ok(food.id),
+        Err(err) => {
+            debug!("{:<12} - Create failed - error {err:?}", "ERROR_CONTROLLER");
+            Err(Error::CreateFailed(err.to_string()))
+        }
+    }
+}
+
+#[async_trait]
+impl FoodRepository for PgFoodRepository {
+    async fn create(&self, data: FoodToCreate) -> Result<i64> {
+        insert_food(&self.db, data).await
+    }
+
+    async fn create_many(&self, foods: Vec<FoodToCreate>) -> Result<Vec<i64>> {
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::CreateFailed(err.to_string()))?;
+
+        let mut ids = Vec::with_capacity(foods.len());
+        for data in foods {
+            ids.push(insert_food(&mut *tx, data).await?);
+        }
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::CreateFailed(err.to_string()))?;
+        Ok(ids)
+    }
+
+    async fn select(&self, store_id: Option<i64>, page: PageRequest) -> Result<Vec<FoodToSelect>> {
+        self.check_store(store_id).await?;
+
+        let query = "select f.cid, f.mid, f.id, f.stamp_code, f.food_name, f.category, case when $1::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks, f.price, f.total_quantity, f.unit, f.case_size, f.ctime as created_at, f.mtime as updated_at from foods_table f left join food_stocks s on s.food_id = f.id and s.store_id = $1 where f.food_status != 'removed' and ($2::text is null or decode(f.cid || '==', 'base64') < decode($2 || '==', 'base64')) order by decode(f.cid || '==', 'base64') desc limit $3";
+
+        match sqlx::query_as::<_, FoodToSelect>(query)
+            .bind(store_id)
+            .bind(page.after)
+            .bind(page.limit)
+            .fetch_all(self.read_db())
+            .await
+        {
+            Ok(foods) => Ok(foods),
+            Err(err) => {
+                debug!("{:<12} - select handler error", "ERROR_CONTROLLER");
+                Err(Error::SelectFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn get_by_id(&self, id: i64, store_id: Option<i64>) -> Result<OneFoodToSelect> {
+        self.check_store(store_id).await?;
+
+        let query = "select f.cid, f.mid, f.id, f.stamp_code, f.food_name, f.category, case when $2::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks, f.price, f.total_quantity, f.unit, f.case_size, f.ctime as created_at, f.mtime as updated_at from foods_table f left join food_stocks s on s.food_id = f.id and s.store_id = $2 where f.id = $1";
+
+        match sqlx::query_as::<_, OneFoodToSelect>(query)
+            .bind(id)
+            .bind(store_id)
+            .fetch_one(self.read_db())
+            .await
+        {
+            Ok(food) => Ok(food),
+            Err(err) => {
+                debug!("{:<12} - get_by_id error", "ERROR_CONTROLLER");
+
+                Err(Error::row_not_found(
+                    err,
+                    Error::FoodIdNotFound(format!("no food with id {id}")),
+                ))
+            }
+        }
+    }
+
+    async fn get_by_stamp_code(
+        &self,
+        stamp_code: String,
+        store_id: Option<i64>,
+    ) -> Result<OneFoodToSelect> {
+        self.check_store(store_id).await?;
+
+        let query = "select f.cid, f.mid, f.id, f.stamp_code, f.food_name, f.category, case when $2::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks, f.price, f.total_quantity, f.unit, f.case_size, f.ctime as created_at, f.mtime as updated_at from foods_table f left join food_stocks s on s.food_id = f.id and s.store_id = $2 where f.stamp_code = $1";
+
+        match sqlx::query_as::<_, OneFoodToSelect>(query)
+            .bind(&stamp_code)
+            .bind(store_id)
+            .fetch_one(self.read_db())
+            .await
+        {
+            Ok(food) => Ok(food),
+            Err(err) => {
+                debug!("{:<12} - get_by_stamp_code error", "ERROR_CONTROLLER");
+
+                Err(Error::row_not_found(
+                    err,
+                    Error::FoodStampCodeNotFound(format!("no food with stamp code {stamp_code}")),
+                ))
+            }
+        }
+    }
+
+    async fn update(&self, data: FoodToUpdate) -> Result<FoodToSelect> {
+        let FoodToUpdate {
+            id,
+            store_id,
+            food_name,
+            category,
+            stocks,
+            price,
+            total_quantity,
+            unit,
+            case_size,
+        } = data;
+
+        self.check_store(store_id).await?;
+
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+        if unit.is_some() {
+            // -- Locked, so no stock comes in before the unit changes.
+            let query = "select f.unit, f.total_quantity, (f.stocks != 0 or exists (select 1 from food_stocks s where s.food_id = f.id and s.stocks != 0) or exists (select 1 from food_lots l where l.food_id = f.id) or exists (select 1 from stock_movements m where m.food_id = f.id) or exists (select 1 from recipe_items r where r.food_id = f.id or r.ingredient_id = f.id)) as in_use from foods_table f where f.id = $1 for update";
+            let (current, current_total, in_use) = sqlx::query_as::<_, (Unit, i32, bool)>(query)
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|err| {
+                    Error::row_not_found(
+                        err,
+                        Error::FoodIdNotFound(format!("no food with id {id}")),
+                    )
+                })?;
+
+            check_unit_change(current, unit, in_use, current_total, total_quantity)?;
+        }
+
+        // -- The stock before, so the change can go to the ledger.
+        let stocks_before = match (stocks, store_id) {
+            (None, _) => None,
+            (Some(_), Some(_)) => Some(lock_stock(&mut tx, id, store_id).await?),
+            (Some(_), None) => {
+                let query = "select stocks from foods_table where id = $1 for update";
+                let stocks = sqlx::query_scalar::<_, i32>(query)
+                    .bind(id)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(|err| {
+                        Error::row_not_found(
+                            err,
+                            Error::FoodIdNotFound(format!("no food with id {id}")),
+                        )
+                    })?;
+                Some(stocks)
+            }
+        };
+
+        // -- A store-scoped stock goes to `food_stocks`, not the food row.
+        let food_stocks = match store_id {
+            Some(store_id) => {
+                if let Some(stocks) = stocks {
+                    let query = "insert into food_stocks (food_id, store_id, stocks) values ($1, $2, $3) on conflict (food_id, store_id) do update set stocks = excluded.stocks";
+
+                    if let Err(err) = sqlx::query(query)
+                        .bind(id)
+                        .bind(store_id)
+                        .bind(stocks)
+                        .execute(&mut *tx)
+                        .await
+                    {
+                        debug!("{:<12} - update store stock error", "ERROR_CONTROLLER");
+                        return Err(Error::UpdateFailed(err.to_string()));
+                    }
+                }
+                None
+            }
+            None => stocks,
+        };
+
+        // Fields left as `None` keep their current value; `$10` says whether
+        // `case_size` was given, as a given `null` clears it.
+        let query = "update foods_table f set food_name = coalesce($1, food_name), category = coalesce($2, category), stocks = coalesce($3, stocks), price = coalesce($4, price), total_quantity = coalesce($5, total_quantity), unit = coalesce($8, unit), case_size = case when $10 then $9 else case_size end, mtime = now() where id = $6 returning cid, mid, id, stamp_code, food_name, category, case when $7::bigint is null then stocks else coalesce((select s.stocks from food_stocks s where s.food_id = f.id and s.store_id = $7), 0) end as stocks, price, total_quantity, unit, case_size, ctime as created_at, mtime as updated_at";
+
+        let food_updated = match sqlx::query_as::<_, FoodToSelect>(query)
+            .bind(food_name)
+            .bind(category)
+            .bind(food_stocks)
+            .bind(price)
+            .bind(total_quantity)
+            .bind(id)
+            .bind(store_id)
+            .bind(unit)
+            .bind(case_size.flatten())
+            .bind(case_size.is_some())
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(food_updated) => food_updated,
+            Err(sqlx::Error::RowNotFound) => {
+                return Err(Error::FoodIdNotFound(format!("no food with id {id}")));
+            }
+            Err(err) => {
+                debug!("{:<12} - update handler error", "ERROR_CONTROLLER");
+
+                return Err(Error::UpdateFailed(err.to_string()));
+            }
+        };
+
+        if let (Some(after), Some(before)) = (stocks, stocks_before) {
+            if after != before {
+                record_movement(&mut tx, id, store_id, after - before, "update", None).await?;
+            }
+        }
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+        Ok(food_updated)
+    }
+
+    async fn delete(&self, id: i64) -> Result<String> {
+        let query = "update foods_table set food_status = 'removed', removed_at = now() where id = $1 and food_status != 'removed'";
+
+        match sqlx::query(query).bind(id).execute(&self.db).await {
+            Ok(done) if done.rows_affected() == 0 => {
+                Err(Error::FoodIdNotFound(format!("no food with id {id}")))
+            }
+            Ok(_) => Ok(String::from("Removed food successfully")),
+            Err(err) => {
+                debug!("{:<12} - delete handler error", "ERROR_CONTROLLER");
+                Err(Error::DeleteFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn restore(&self, id: i64) -> Result<OneFoodToSelect> {
+        let query = "update foods_table set food_status = 'active', removed_at = null where id = $1 and food_status = 'removed' returning id";
+
+        match sqlx::query_scalar::<_, i64>(query)
+            .bind(id)
+            .fetch_optional(&self.db)
+            .await
+        {
+            Ok(Some(id)) => self.get_by_id(id, None).await,
+            Ok(None) => Err(Error::FoodIdNotFound(format!(
+                "no removed food with id {id}"
+            ))),
+            Err(err) => {
+                debug!("{:<12} - restore error", "ERROR_CONTROLLER");
+                Err(Error::UpdateFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn inventory_stats(&self, store_id: Option<i64>) -> Result<InventoryStats> {
+        self.check_store(store_id).await?;
+
+        let query = "select count(*) as total, count(*) filter (where (case when $1::bigint is null then f.stocks else coalesce(s.stocks, 0) end) <= 0 or f.food_status = 'out of stock') as out_of_stock from foods_table f left join food_stocks s on s.food_id = f.id and s.store_id = $1 where f.food_status != 'removed'";
+
+        match sqlx::query_as::<_, InventoryStats>(query)
+            .bind(store_id)
+            .fetch_one(self.read_db())
+            .await
+        {
+            Ok(stats) => Ok(stats),
+            Err(err) => {
+                debug!("{:<12} - inventory_stats error", "ERROR_CONTROLLER");
+                Err(Error::SelectFailed(err.to_string()))
+            }
+        }
+    }
+
+    // -- Stores
+
+    async fn create_store(&self, data: StoreToCreate) -> Result<i64> {
+        let query = "insert into stores (store_name, address) values ($1, $2) returning id";
+
+        match sqlx::query_scalar::<_, i64>(query)
+            .bind(data.store_name)
+            .bind(data.address)
+            .fetch_one(&self.db)
+            .await
+        {
+            Ok(id) => Ok(id),
+            Err(err) => {
+                debug!("{:<12} - create_store error {err:?}", "ERROR_CONTROLLER");
+                Err(Error::CreateFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn select_stores(&self) -> Result<Vec<StoreToSelect>> {
+        let query = "select id, store_name, address, to_char(ctime, 'Month DD, YYYY') as created_date from stores order by store_name";
+
+        match sqlx::query_as::<_, StoreToSelect>(query)
+            .fetch_all(self.read_db())
+            .await
+        {
+            Ok(stores) => Ok(stores),
+            Err(err) => {
+                debug!("{:<12} - select_stores error", "ERROR_CONTROLLER");
+                Err(Error::SelectFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn get_store(&self, id: i64) -> Result<StoreToSelect> {
+        let query = "select id, store_name, address, to_char(ctime, 'Month DD, YYYY') as created_date from stores where id = $1";
+
+        match sqlx::query_as::<_, StoreToSelect>(query)
+            .bind(id)
+            .fetch_one(self.read_db())
+            .await
+        {
+            Ok(store) => Ok(store),
+            Err(err) => {
+                debug!("{:<12} - get_store error", "ERROR_CONTROLLER");
+                Err(Error::row_not_found(
+                    err,
+                    Error::StoreNotFound(id.to_string()),
+                ))
+            }
+        }
+    }
+
+    async fn update_store(&self, data: StoreToUpdate) -> Result<StoreToSelect> {
+        let query = "update stores set store_name = coalesce($1, store_name), address = coalesce($2, address), mtime = now() where id = $3 returning id, store_name, address, to_char(ctime, 'Month DD, YYYY') as created_date";
+
+        match sqlx::query_as::<_, StoreToSelect>(query)
+            .bind(data.store_name)
+            .bind(data.address)
+            .bind(data.id)
+            .fetch_one(&self.db)
+            .await
+        {
+            Ok(store) => Ok(store),
+            Err(err) => {
+                debug!("{:<12} - update_store error", "ERROR_CONTROLLER");
+                Err(Error::UpdateFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn food_stocks(&self, food_id: i64) -> Result<Vec<FoodStoreStock>> {
+        let query = "select s.store_id, st.store_name, s.stocks from food_stocks s join stores st on st.id = s.store_id where s.food_id = $1 order by st.store_name";
+
+        match sqlx::query_as::<_, FoodStoreStock>(query)
+            .bind(food_id)
+            .fetch_all(self.read_db())
+            .await
+        {
+            Ok(stocks) => Ok(stocks),
+            Err(err) => {
+                debug!("{:<12} - food_stocks error", "ERROR_CONTROLLER");
+                Err(Error::SelectFailed(err.to_string()))
+            }
+        }
+    }
+
+    async fn transfer_stock(&self, data: StockTransfer) -> Result<StockTransferred> {
+        let StockTransfer {
+            food_id,
+            from_store_id,
+            to_store_id,
+            quantity,
+        } = data;
+
+        self.check_store(Some(from_store_id)).await?;
+        self.check_store(Some(to_store_id)).await?;
+
+        let mut tx = self
+            .db
+            .begin()
+            .await
+            .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+        // -- Debit. The row lock serializes concurrent transfers out of the
+        //    same store, the guard keeps the stock from going negative or
+        //    below what held reservations need.
+        let query = "update food_stocks set stocks = stocks - $3 where food_id = $1 and store_id = $2 and stocks - (select coalesce(sum(quantity), 0) from reservations where food_id = $1 and store_id = $2 and reservation_status = 'held' and expires_at > now()) >= $3 returning stocks";
+        let from_stocks = match sqlx::query_scalar::<_, i32>(query)
+            .bind(food_id)
+            .bind(from_store_id)
+            .bind(quantity)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(Some(stocks)) => stocks,
+            Ok(None) => {
+                return Err(Error::InsufficientStock(format!(
+                    "store {from_store_id} has less than {quantity} of food {food_id} available"
+                )))
+            }
+            Err(err) => {
+                debug!("{:<12} - transfer debit error", "ERROR_CONTROLLER");
+                return Err(Error::UpdateFailed(err.to_string()));
+            }
+        };
+
+        // -- Credit.
+        let query = "insert into food_stocks (food_id, store_id, stocks) values ($1, $2, $3) on conflict (food_id, store_id) do update set stocks = food_stocks.stocks + excluded.stocks returning stocks";
+        let to_stocks = match sqlx::query_scalar::<_, i32>(query)
+            .bind(food_id)
+            .bind(to_store_id)
+            .bind(quantity)
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(stocks) => stocks,
+            Err(err) => {
+                debug!("{:<12} - transfer credit error", "ERROR_CONTROLLER");
+                return Err(Error::UpdateFailed(err.to_string()));
+            }
+        };
+
+        let query = "insert into stock_transfers (food_id, from_store_id, to_store_id, quantity) values ($1, $2, $3, $4)";
+        if let Err(err) = sqlx::query(query)
+            .bind(food_id)
+            .bind(from_store_id)
+            .bind(to_store_id)
+            .bind(quantity)
+            .execute(&mut *tx)
+            .await
+        {
+            debug!("{:<12} - transfer log error", "ERROR_CONTROLLER");
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+
+        tx.commit()
+            .await
+            .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+        Ok(StockTransferred {
+            food_id,
+            from_store_id,
+            from_stocks,
+            to_store_id,
+            to_stocks,
+        })
+    }
+}
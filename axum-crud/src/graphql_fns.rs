@@ -0,0 +1,500 @@
+//! GraphQL schema over the food model.
+//!
+//! Resolvers go through the model controllers like the REST handlers.
+//! A food is always read with all of its columns, whatever fields the
+//! query selects; only `Food.images` is left out unless selected, then
+//! batched through `ImageLoader` into one query per round of resolvers.
+//! `categories` is aggregated in SQL. Listings are Relay connections keyed
+//! on the same signed cursors as `/api/v1/foods`.
+//!
+//! Queries need the `read` scope, mutations `write`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{
+    connection::{Connection, Edge},
+    dataloader::{DataLoader, Loader},
+    ComplexObject, Context, EmptySubscription, Enum, Guard, InputObject, Object, Schema,
+    SimpleObject, ID,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    admin_fns::AdminModelController,
+    api_keys_fns::{ApiKeyPrincipal, Scope},
+    crud_fns::{
+        FoodModelController, FoodRelations, FoodToCreate, FoodToSelect, FoodToUpdate,
+        ModelController, OneFoodToSelect,
+    },
+    error::Error,
+    images_fns::{FoodImage, ImageModelController},
+    pagination::{encode_cursor, PageParams, DEFAULT_PAGE_SIZE},
+    timestamps::parse_tz,
+    units::Unit,
+};
+
+pub type FoodSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// Nesting and cost a single query may reach.
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 1000;
+
+pub fn build_schema(mm: ModelController) -> FoodSchema {
+    let images = DataLoader::new(ImageLoader { mm: mm.clone() }, tokio::spawn);
+
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(mm)
+        .data(images)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+// region:    ---- Types
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enum)]
+#[graphql(remote = "crate::units::Unit", name = "Unit")]
+enum GqlUnit {
+    Piece,
+    G,
+    Kg,
+    Ml,
+    Litre,
+    Case,
+}
+
+#[derive(Debug, SimpleObject)]
+#[graphql(complex)]
+struct Food {
+    id: ID,
+    stamp_code: String,
+    food_name: String,
+    category: String,
+    /// In `unit`, the store's stock when a `storeId` was given.
+    stocks: i32,
+    price: f64,
+    total_quantity: i32,
+    unit: GqlUnit,
+    case_size: Option<i32>,
+    /// RFC 3339, at the offset of the requested zone.
+    created_at: String,
+    updated_at: Option<String>,
+    #[graphql(skip)]
+    food_id: i64,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+struct Image {
+    id: ID,
+    content_type: String,
+    width: i32,
+    height: i32,
+    url: String,
+    thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Clone, Debug, SimpleObject)]
+struct Thumbnail {
+    /// Longest side in pixels.
+    size: String,
+    url: String,
+}
+
+#[derive(Debug, SimpleObject)]
+struct Category {
+    name: String,
+    food_count: i64,
+    total_stocks: i64,
+    out_of_stock: i64,
+}
+
+#[derive(Debug, SimpleObject)]
+struct Inventory {
+    total: i64,
+    out_of_stock: i64,
+}
+
+#[derive(Debug, InputObject)]
+struct CreateFoodInput {
+    food_name: String,
+    category: String,
+    stocks: i32,
+    price: f64,
+    total_quantity: i32,
+    /// Base unit, `PIECE` when absent.
+    unit: Option<GqlUnit>,
+    case_size: Option<i32>,
+    /// Unit `stocks` and `totalQuantity` are given in, `unit` when absent.
+    quantity_unit: Option<GqlUnit>,
+}
+
+#[derive(Debug, InputObject)]
+struct UpdateFoodInput {
+    /// Set this store's stock instead of the food's own stock.
+    store_id: Option<i64>,
+    food_name: Option<String>,
+    category: Option<String>,
+    stocks: Option<i32>,
+    price: Option<f64>,
+    total_quantity: Option<i32>,
+    unit: Option<GqlUnit>,
+    case_size: Option<i32>,
+    quantity_unit: Option<GqlUnit>,
+}
+
+fn rfc3339(at: OffsetDateTime) -> String {
+    at.format(&Rfc3339).unwrap_or_default()
+}
+
+impl From<FoodToSelect> for Food {
+    fn from(food: FoodToSelect) -> Self {
+        Food {
+            id: ID::from(food.id),
+            stamp_code: food.stamp_code,
+            food_name: food.food_name,
+            category: food.category,
+            stocks: food.stocks,
+            price: food.price,
+            total_quantity: food.total_quantity,
+            unit: food.unit.into(),
+            case_size: food.case_size,
+            created_at: rfc3339(food.created_at),
+            updated_at: food.updated_at.map(rfc3339),
+            food_id: food.id,
+        }
+    }
+}
+
+impl From<OneFoodToSelect> for Food {
+    fn from(food: OneFoodToSelect) -> Self {
+        Food {
+            id: ID::from(food.id),
+            stamp_code: food.stamp_code,
+            food_name: food.food_name,
+            category: food.category,
+            stocks: food.stocks,
+            price: food.price,
+            total_quantity: food.total_quantity,
+            unit: food.unit.into(),
+            case_size: food.case_size,
+            created_at: rfc3339(food.created_at),
+            updated_at: food.updated_at.map(rfc3339),
+            food_id: food.id,
+        }
+    }
+}
+
+impl From<FoodImage> for Image {
+    fn from(image: FoodImage) -> Self {
+        Image {
+            id: ID::from(image.id),
+            content_type: image.content_type,
+            width: image.width,
+            height: image.height,
+            url: image.url,
+            thumbnails: image
+                .thumbnails
+                .into_iter()
+                .map(|(size, url)| Thumbnail { size, url })
+                .collect(),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Food {
+    /// Uploaded pictures, oldest first. Only loaded when selected.
+    async fn images(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Image>> {
+        let images = ctx
+            .data_unchecked::<DataLoader<ImageLoader>>()
+            .load_one(self.food_id)
+            .await?;
+
+        Ok(images.unwrap_or_default())
+    }
+}
+
+// endregion: ---- Types
+
+// region:    ---- Loaders
+
+/// Images of many foods in one query.
+struct ImageLoader {
+    mm: ModelController,
+}
+
+impl Loader<i64> for ImageLoader {
+    type Value = Vec<Image>;
+    type Error = Arc<Error>;
+
+    async fn load(&self, food_ids: &[i64]) -> Result<HashMap<i64, Vec<Image>>, Arc<Error>> {
+        let images = ImageModelController::images_of(&self.mm, food_ids)
+            .await
+            .map_err(Arc::new)?;
+
+        Ok(images
+            .into_iter()
+            .map(|(food_id, images)| (food_id, images.into_iter().map(Image::from).collect()))
+            .collect())
+    }
+}
+
+// endregion: ---- Loaders
+
+/// Rejects the field unless the request's key has `scope`.
+struct ScopeGuard(Scope);
+
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        match ctx.data_opt::<ApiKeyPrincipal>() {
+            Some(principal) if principal.allows(self.0) => Ok(()),
+            _ => Err(format!("API key lacks the {} scope", self.0.as_str()).into()),
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Foods, newest first.
+    async fn foods(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Page size, 1 to 200.")] first: Option<u32>,
+        #[graphql(desc = "`endCursor` of the previous page.")] after: Option<String>,
+        store_id: Option<i64>,
+        #[graphql(desc = "IANA zone for the timestamps, UTC when absent.")] tz: Option<String>,
+    ) -> async_graphql::Result<Connection<String, Food>> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+        let tz = tz.as_deref().map(parse_tz).transpose()?;
+        let has_previous_page = after.is_some();
+        let page = PageParams {
+            limit: Some(first.unwrap_or(DEFAULT_PAGE_SIZE)),
+            cursor: after,
+        };
+
+        let foods =
+            FoodModelController::select(mm, store_id, tz, &page, FoodRelations::NONE).await?;
+
+        let mut connection = Connection::new(has_previous_page, foods.next_cursor.is_some());
+        for food in foods.items {
+            let cursor = encode_cursor(&food.cid)?;
+            connection.edges.push(Edge::new(cursor, Food::from(food)));
+        }
+        Ok(connection)
+    }
+
+    async fn food(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        store_id: Option<i64>,
+        tz: Option<String>,
+    ) -> async_graphql::Result<Food> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+        let tz = tz.as_deref().map(parse_tz).transpose()?;
+
+        let food =
+            FoodModelController::get_by_id(mm, id, store_id, tz, FoodRelations::NONE).await?;
+        Ok(food.into())
+    }
+
+    async fn food_by_stamp_code(
+        &self,
+        ctx: &Context<'_>,
+        stamp_code: String,
+        store_id: Option<i64>,
+        tz: Option<String>,
+    ) -> async_graphql::Result<Food> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+        let tz = tz.as_deref().map(parse_tz).transpose()?;
+
+        let food = FoodModelController::get_by_stamp_code(
+            mm,
+            stamp_code,
+            store_id,
+            tz,
+            FoodRelations::NONE,
+        )
+        .await?;
+        Ok(food.into())
+    }
+
+    /// Food counts and stock per category, by name.
+    async fn categories(
+        &self,
+        ctx: &Context<'_>,
+        store_id: Option<i64>,
+    ) -> async_graphql::Result<Vec<Category>> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+
+        let categories = AdminModelController::stock_by_category(mm, store_id).await?;
+        Ok(categories
+            .into_iter()
+            .map(|category| Category {
+                name: category.category,
+                food_count: category.foods,
+                total_stocks: category.total_stocks,
+                out_of_stock: category.out_of_stock,
+            })
+            .collect())
+    }
+
+    async fn inventory(
+        &self,
+        ctx: &Context<'_>,
+        store_id: Option<i64>,
+    ) -> async_graphql::Result<Inventory> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+
+        let stats = FoodModelController::inventory_stats(mm, store_id).await?;
+        Ok(Inventory {
+            total: stats.total,
+            out_of_stock: stats.out_of_stock,
+        })
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    #[graphql(guard = "ScopeGuard(Scope::Write)")]
+    async fn create_food(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateFoodInput,
+    ) -> async_graphql::Result<Food> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+
+        let data = FoodToCreate {
+            food_name: input.food_name,
+            category: input.category,
+            stocks: input.stocks,
+            price: input.price as f32,
+            total_quantity: input.total_quantity,
+            unit: input.unit.map(Unit::from).unwrap_or_default(),
+            case_size: input.case_size,
+        };
+        let quantity_unit = input.quantity_unit.map(Unit::from);
+
+        let id = FoodModelController::create(mm.clone(), data, quantity_unit).await?;
+        let food = FoodModelController::get_by_id(mm, id, None, None, FoodRelations::NONE).await?;
+        Ok(food.into())
+    }
+
+    #[graphql(guard = "ScopeGuard(Scope::Write)")]
+    async fn update_food(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        input: UpdateFoodInput,
+    ) -> async_graphql::Result<Food> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+
+        let data = FoodToUpdate {
+            id,
+            store_id: input.store_id,
+            food_name: input.food_name,
+            category: input.category,
+            stocks: input.stocks,
+            price: input.price.map(|price| price as f32),
+            total_quantity: input.total_quantity,
+            unit: input.unit.map(Unit::from),
+            case_size: input.case_size.map(Some),
+        };
+        let quantity_unit = input.quantity_unit.map(Unit::from);
+
+        let food = FoodModelController::update(mm, data, quantity_unit).await?;
+        Ok(food.into())
+    }
+
+    /// Marks the food removed, returns its id.
+    #[graphql(guard = "ScopeGuard(Scope::Write)")]
+    async fn delete_food(&self, ctx: &Context<'_>, id: i64) -> async_graphql::Result<ID> {
+        let mm = ctx.data_unchecked::<ModelController>().clone();
+
+        FoodModelController::delete(mm, id).await?;
+        Ok(ID::from(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{Request, Variables};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    fn principal(scopes: Vec<Scope>) -> ApiKeyPrincipal {
+        ApiKeyPrincipal {
+            id: None,
+            key_name: String::from("test"),
+            scopes,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_graphql_foods_connection(db: PgPool) {
+        let schema = build_schema(ModelController::with_db(db));
+
+        let create = "mutation($name: String!, $stocks: Int!) { createFood(input: {foodName: $name, category: \"bakery\", stocks: $stocks, price: 1.5, totalQuantity: 4}) { foodName } }";
+        for (name, stocks) in [("Bun", 0), ("Roll", 2), ("Loaf", 0)] {
+            let req = Request::new(create)
+                .variables(Variables::from_json(
+                    json!({ "name": name, "stocks": stocks }),
+                ))
+                .data(principal(vec![Scope::Write]));
+            let res = schema.execute(req).await;
+            assert!(res.errors.is_empty(), "{:?}", res.errors);
+        }
+
+        let query = "query($after: String) { foods(first: 2, after: $after) { edges { node { foodName images { url } } } pageInfo { hasNextPage endCursor } } categories { name foodCount totalStocks outOfStock } }";
+        let res = schema
+            .execute(Request::new(query).data(principal(vec![Scope::Read])))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let foods = &res["foods"];
+        assert_eq!(foods["edges"][0]["node"]["foodName"], "Loaf");
+        assert_eq!(foods["edges"][1]["node"]["foodName"], "Roll");
+        assert_eq!(foods["pageInfo"]["hasNextPage"], true);
+        assert_eq!(
+            res["categories"],
+            json!([{"name": "bakery", "foodCount": 3, "totalStocks": 2, "outOfStock": 2}])
+        );
+
+        let after = foods["pageInfo"]["endCursor"].clone();
+        let req = Request::new(query)
+            .variables(Variables::from_json(json!({ "after": after })))
+            .data(principal(vec![Scope::Read]));
+        let res = schema
+            .execute(req)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            res["foods"]["edges"],
+            json!([{"node": {"foodName": "Bun", "images": []}}])
+        );
+        assert_eq!(res["foods"]["pageInfo"]["hasNextPage"], false);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_mutations_need_write() {
+        let schema = build_schema(ModelController::in_memory());
+
+        let req = Request::new("mutation { deleteFood(id: 1) }").data(principal(vec![Scope::Read]));
+        let res = schema.execute(req).await;
+        assert_eq!(res.errors.len(), 1);
+        assert!(res.errors[0].message.contains("write"));
+    }
+}
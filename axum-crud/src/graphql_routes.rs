@@ -0,0 +1,75 @@
+use async_graphql::http::GraphiQLSource;
+use axum::{
+    extract::State,
+    middleware,
+    response::Html,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde::Serialize;
+use serde_json::Value;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    api_keys_fns::ApiKeyPrincipal,
+    auth::mw_require_read,
+    config::core_config,
+    crud_fns::ModelController,
+    graphql_fns::{build_schema, FoodSchema},
+};
+
+pub fn routes_graphql(mm: ModelController) -> Router {
+    // -- Mutations check the write scope themselves, a read key is enough
+    //    to get in.
+    let routes = Router::new()
+        .route("/graphql", post(api_graphql))
+        .route_layer(middleware::from_fn_with_state(mm.clone(), mw_require_read))
+        .with_state(build_schema(mm));
+
+    if core_config().GRAPHIQL_ENABLED {
+        routes.merge(Router::new().route("/graphql", get(graphiql)))
+    } else {
+        routes
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+struct GraphQLPayload {
+    query: String,
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+    variables: Option<Value>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body = GraphQLPayload,
+    responses(
+        (status = 200, description = "GraphQL response, errors are reported in `errors`", body = Object),
+    )
+)]
+async fn api_graphql(
+    State(schema): State<FoodSchema>,
+    principal: Option<Extension<ApiKeyPrincipal>>,
+    Json(req): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    debug!("{:<12} - api_graphql", "ROUTE_HANDLER");
+
+    let req = match principal {
+        Some(Extension(principal)) => req.data(principal),
+        None => req,
+    };
+
+    Json(schema.execute(req).await)
+}
+
+/// GraphiQL IDE, only served while `GRAPHIQL_ENABLED` is on.
+async fn graphiql() -> Html<String> {
+    debug!("{:<12} - graphiql", "ROUTE_HANDLER");
+
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
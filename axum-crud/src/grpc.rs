@@ -0,0 +1,417 @@
+//! gRPC food service for the warehouse scanners, see `proto/food.proto`.
+//!
+//! Served on `GRPC_PORT` next to the HTTP server and backed by the same
+//! `FoodModelController`. Keys and scopes are those of the REST API, sent
+//! as `authorization: Bearer <key>` or `x-api-key` metadata.
+
+use std::future::Future;
+
+use axum::http::StatusCode;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::{net::TcpListener, sync::mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    transport::{server::TcpIncoming, Server},
+    Request, Response, Status,
+};
+use tracing::{debug, error, info};
+
+use crate::{
+    api_keys_fns::Scope,
+    auth::{api_key_from, principal_for},
+    crud_fns::{
+        FoodModelController, FoodRelations, FoodToCreate, FoodToSelect, FoodToUpdate,
+        ModelController, OneFoodToSelect,
+    },
+    error::{self, Error},
+    pagination::{PageParams, DEFAULT_PAGE_SIZE},
+    replica::{with_reads, Reads},
+    units::Unit,
+};
+
+/// Messages, server and client generated from `proto/food.proto`.
+pub mod pb {
+    tonic::include_proto!("axum_crud.food.v1");
+}
+
+use pb::food_service_server::{FoodService, FoodServiceServer};
+
+/// Foods buffered ahead of a slow `ListFoods` reader.
+const LIST_BUFFER: usize = 64;
+
+/// Serves the food service on `listener` until `shutdown` resolves.
+pub async fn serve(
+    mm: ModelController,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("{:<12} - grpc://{addr}", "GRPC");
+    }
+    let incoming = TcpIncoming::from_listener(listener, true, None)
+        .expect("a bound listener has a local address");
+
+    Server::builder()
+        .add_service(FoodServiceServer::new(FoodGrpc { mm }))
+        .serve_with_incoming_shutdown(incoming, shutdown)
+        .await
+}
+
+struct FoodGrpc {
+    mm: ModelController,
+}
+
+impl FoodGrpc {
+    async fn authorize<T>(&self, req: &Request<T>, needed: Scope) -> Result<(), Status> {
+        let headers = req.metadata().clone().into_headers();
+        let Some(api_key) = api_key_from(&headers) else {
+            return Err(Status::unauthenticated("missing API key"));
+        };
+
+        match principal_for(&self.mm, &api_key).await.map_err(status)? {
+            Some(principal) if principal.allows(needed) => Ok(()),
+            Some(_) => Err(Status::permission_denied(format!(
+                "API key lacks the {} scope",
+                needed.as_str()
+            ))),
+            None => Err(Status::unauthenticated(
+                "invalid, expired or revoked API key",
+            )),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl FoodService for FoodGrpc {
+    async fn create_food(
+        &self,
+        req: Request<pb::CreateFoodRequest>,
+    ) -> Result<Response<pb::Food>, Status> {
+        debug!("{:<12} - create_food", "GRPC");
+        self.authorize(&req, Scope::Write).await?;
+
+        let req = req.into_inner();
+        let data = FoodToCreate {
+            food_name: req.food_name,
+            category: req.category,
+            stocks: req.stocks,
+            price: req.price as f32,
+            total_quantity: req.total_quantity,
+            unit: unit_of(req.unit).map_err(status)?.unwrap_or_default(),
+            case_size: req.case_size,
+        };
+        let quantity_unit = unit_of(req.quantity_unit).map_err(status)?;
+
+        let mm = self.mm.clone();
+        let id = FoodModelController::create(mm.clone(), data, quantity_unit)
+            .await
+            .map_err(status)?;
+        let food = FoodModelController::get_by_id(mm, id, None, None, FoodRelations::NONE)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(food.into()))
+    }
+
+    async fn get_food_by_stamp_code(
+        &self,
+        req: Request<pb::GetFoodByStampCodeRequest>,
+    ) -> Result<Response<pb::Food>, Status> {
+        debug!("{:<12} - get_food_by_stamp_code", "GRPC");
+        self.authorize(&req, Scope::Read).await?;
+
+        let reads = Reads::for_request(true, &req.metadata().clone().into_headers());
+        let req = req.into_inner();
+        let food = with_reads(
+            reads,
+            FoodModelController::get_by_stamp_code(
+                self.mm.clone(),
+                req.stamp_code,
+                req.store_id,
+                None,
+                FoodRelations::NONE,
+            ),
+        )
+        .await
+        .map_err(status)?;
+
+        Ok(Response::new(food.into()))
+    }
+
+    async fn update_stock(
+        &self,
+        req: Request<pb::UpdateStockRequest>,
+    ) -> Result<Response<pb::Food>, Status> {
+        debug!("{:<12} - update_stock", "GRPC");
+        self.authorize(&req, Scope::Write).await?;
+
+        let req = req.into_inner();
+        let data = FoodToUpdate {
+            id: req.id,
+            store_id: req.store_id,
+            food_name: None,
+            category: None,
+            stocks: Some(req.stocks),
+            price: None,
+            total_quantity: None,
+            unit: None,
+            case_size: None,
+        };
+        let quantity_unit = unit_of(req.quantity_unit).map_err(status)?;
+
+        let food = FoodModelController::update(self.mm.clone(), data, quantity_unit)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(food.into()))
+    }
+
+    type ListFoodsStream = ReceiverStream<Result<pb::Food, Status>>;
+
+    async fn list_foods(
+        &self,
+        req: Request<pb::ListFoodsRequest>,
+    ) -> Result<Response<Self::ListFoodsStream>, Status> {
+        debug!("{:<12} - list_foods", "GRPC");
+        self.authorize(&req, Scope::Read).await?;
+
+        let reads = Reads::for_request(true, &req.metadata().clone().into_headers());
+        let req = req.into_inner();
+        let mut page = PageParams {
+            limit: Some(req.page_size.unwrap_or(DEFAULT_PAGE_SIZE)),
+            cursor: None,
+        };
+        // -- Checked up front, so a bad page size fails the call instead of
+        //    ending the stream with an error.
+        page.request().map_err(status)?;
+
+        let (tx, rx) = mpsc::channel(LIST_BUFFER);
+        let mm = self.mm.clone();
+        tokio::spawn(with_reads(reads, async move {
+            loop {
+                let foods = match FoodModelController::select(
+                    mm.clone(),
+                    req.store_id,
+                    None,
+                    &page,
+                    FoodRelations::NONE,
+                )
+                .await
+                {
+                    Ok(foods) => foods,
+                    Err(err) => {
+                        let _ = tx.send(Err(status(err))).await;
+                        return;
+                    }
+                };
+
+                for food in foods.items {
+                    // -- The client went away.
+                    if tx.send(Ok(food.into())).await.is_err() {
+                        return;
+                    }
+                }
+
+                match foods.next_cursor {
+                    Some(cursor) => page.cursor = Some(cursor),
+                    None => return,
+                }
+            }
+        }));
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+impl From<FoodToSelect> for pb::Food {
+    fn from(food: FoodToSelect) -> Self {
+        pb::Food {
+            id: food.id,
+            stamp_code: food.stamp_code,
+            food_name: food.food_name,
+            category: food.category,
+            stocks: food.stocks,
+            price: food.price,
+            total_quantity: food.total_quantity,
+            unit: pb_unit(food.unit),
+            case_size: food.case_size,
+            created_at: rfc3339(food.created_at),
+            updated_at: food.updated_at.map(rfc3339),
+        }
+    }
+}
+
+impl From<OneFoodToSelect> for pb::Food {
+    fn from(food: OneFoodToSelect) -> Self {
+        pb::Food {
+            id: food.id,
+            stamp_code: food.stamp_code,
+            food_name: food.food_name,
+            category: food.category,
+            stocks: food.stocks,
+            price: food.price,
+            total_quantity: food.total_quantity,
+            unit: pb_unit(food.unit),
+            case_size: food.case_size,
+            created_at: rfc3339(food.created_at),
+            updated_at: food.updated_at.map(rfc3339),
+        }
+    }
+}
+
+fn rfc3339(at: OffsetDateTime) -> String {
+    at.format(&Rfc3339).unwrap_or_default()
+}
+
+/// `None` for `UNIT_UNSPECIFIED`.
+fn unit_of(unit: i32) -> error::Result<Option<Unit>> {
+    match pb::Unit::try_from(unit) {
+        Ok(pb::Unit::Unspecified) => Ok(None),
+        Ok(pb::Unit::Piece) => Ok(Some(Unit::Piece)),
+        Ok(pb::Unit::G) => Ok(Some(Unit::G)),
+        Ok(pb::Unit::Kg) => Ok(Some(Unit::Kg)),
+        Ok(pb::Unit::Ml) => Ok(Some(Unit::Ml)),
+        Ok(pb::Unit::Litre) => Ok(Some(Unit::Litre)),
+        Ok(pb::Unit::Case) => Ok(Some(Unit::Case)),
+        Err(_) => Err(Error::UnitConversion(format!("unknown unit {unit}"))),
+    }
+}
+
+fn pb_unit(unit: Unit) -> i32 {
+    let unit = match unit {
+        Unit::Piece => pb::Unit::Piece,
+        Unit::G => pb::Unit::G,
+        Unit::Kg => pb::Unit::Kg,
+        Unit::Ml => pb::Unit::Ml,
+        Unit::Litre => pb::Unit::Litre,
+        Unit::Case => pb::Unit::Case,
+    };
+    unit as i32
+}
+
+fn status(err: Error) -> Status {
+    debug!("{:<12} - grpc error {err:?}", "ERROR_CONTROLLER");
+
+    if let Error::NoDatabase = err {
+        return Status::unavailable(err.to_string());
+    }
+    match err.status() {
+        StatusCode::NOT_FOUND => Status::not_found(err.to_string()),
+        StatusCode::BAD_REQUEST => Status::invalid_argument(err.to_string()),
+        StatusCode::CONFLICT => Status::failed_precondition(err.to_string()),
+        // -- Like the HTTP 500s, the details stay in the log.
+        _ => {
+            error!("{:<12} - {err}", "GRPC");
+            Status::internal("internal error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+    use tonic::{transport::Channel, Code};
+
+    use super::*;
+    use crate::api_keys_fns::{ApiKeyModelController, ApiKeyToCreate};
+    use pb::food_service_client::FoodServiceClient;
+
+    /// Serves on an ephemeral port and connects a client to it.
+    async fn client(mm: ModelController) -> FoodServiceClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(mm, listener, std::future::pending()));
+
+        FoodServiceClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+
+    async fn api_key(mm: &ModelController, scopes: Vec<Scope>) -> String {
+        let data = ApiKeyToCreate {
+            key_name: String::from("scanner"),
+            scopes,
+            ttl_sec: None,
+        };
+        ApiKeyModelController::issue(mm.clone(), data)
+            .await
+            .unwrap()
+            .api_key
+    }
+
+    fn with_key<T>(message: T, api_key: &str) -> Request<T> {
+        let mut req = Request::new(message);
+        req.metadata_mut()
+            .insert("x-api-key", api_key.parse().unwrap());
+        req
+    }
+
+    fn bun() -> pb::CreateFoodRequest {
+        pb::CreateFoodRequest {
+            food_name: String::from("Bun"),
+            category: String::from("bakery"),
+            stocks: 4,
+            price: 1.5,
+            total_quantity: 4,
+            ..Default::default()
+        }
+    }
+
+    #[sqlx::test]
+    async fn calls_without_a_fitting_key_are_rejected(db: PgPool) {
+        let mm = ModelController::with_db(db);
+        let read_key = api_key(&mm, vec![Scope::Read]).await;
+        let mut client = client(mm).await;
+
+        let err = client.create_food(bun()).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+
+        let err = client
+            .create_food(with_key(bun(), "ack_not_a_key"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+
+        let err = client
+            .create_food(with_key(bun(), &read_key))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+    }
+
+    #[sqlx::test]
+    async fn created_food_is_found_by_stamp_code(db: PgPool) {
+        let mm = ModelController::with_db(db);
+        let key = api_key(&mm, vec![Scope::Read, Scope::Write]).await;
+        let mut client = client(mm).await;
+
+        let created = client
+            .create_food(with_key(bun(), &key))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(created.food_name, "Bun");
+        assert_eq!(created.unit(), pb::Unit::Piece);
+
+        let lookup = pb::GetFoodByStampCodeRequest {
+            stamp_code: created.stamp_code.clone(),
+            store_id: None,
+        };
+        let found = client
+            .get_food_by_stamp_code(with_key(lookup, &key))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(found, created);
+
+        let lookup = pb::GetFoodByStampCodeRequest {
+            stamp_code: String::from("no-such-code"),
+            store_id: None,
+        };
+        let err = client
+            .get_food_by_stamp_code(with_key(lookup, &key))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+}
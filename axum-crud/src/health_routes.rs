@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::time::timeout;
+use tracing::debug;
+
+use crate::{config::core_config, crud_fns::ModelController, store::MIGRATOR};
+
+pub fn routes_health(mm: ModelController) -> Router {
+    Router::new()
+        .route("/health/live", get(api_health_live))
+        .route("/health/ready", get(api_health_ready))
+        .with_state(mm)
+}
+
+#[derive(Debug, Serialize)]
+struct DependencyStatus {
+    status: &'static str,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    fn is_up(&self) -> bool {
+        self.status != "down"
+    }
+}
+
+async fn api_health_live() -> Json<Value> {
+    Json(json!({ "status": "live" }))
+}
+
+async fn api_health_ready(State(mm): State<ModelController>) -> (StatusCode, Json<Value>) {
+    debug!("{:<12} - api_health_ready", "ROUTE_HANDLER");
+
+    let limit = Duration::from_millis(core_config().HEALTH_CHECK_TIMEOUT_MS);
+
+    let database = check_database(&mm, limit).await;
+    let migrations = check_migrations(&mm, limit).await;
+    let replica = check_replica(&mm);
+
+    // -- Reads fall back to the primary, so a replica down does not make
+    //    the service unready.
+    let ready = database.is_up() && migrations.is_up();
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let body = Json(json!({
+        "status": if ready { "ready" } else { "unavailable" },
+        "checks": {
+            "database": database,
+            "migrations": migrations,
+            "replica": replica,
+        }
+    }));
+
+    (status, body)
+}
+
+async fn check_database(mm: &ModelController, limit: Duration) -> DependencyStatus {
+    let start = Instant::now();
+    let Ok(db) = mm.db() else {
+        return skipped(start);
+    };
+    let res = timeout(limit, sqlx::query("select 1").execute(db)).await;
+
+    let error = match res {
+        Ok(Ok(_)) => None,
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some(format!("timed out after {}ms", limit.as_millis())),
+    };
+
+    dependency_status(start, error)
+}
+
+/// Every migration embedded in the binary must be recorded as successfully
+/// applied in `_sqlx_migrations`.
+async fn check_migrations(mm: &ModelController, limit: Duration) -> DependencyStatus {
+    let start = Instant::now();
+    let Ok(db) = mm.db() else {
+        return skipped(start);
+    };
+    let query = "select version from _sqlx_migrations where success = true";
+    let res = timeout(limit, sqlx::query_scalar::<_, i64>(query).fetch_all(db)).await;
+
+    let error = match res {
+        Ok(Ok(applied)) => {
+            let pending: Vec<String> = MIGRATOR
+                .iter()
+                .filter(|m| !applied.contains(&m.version))
+                .map(|m| format!("{}_{}", m.version, m.description))
+                .collect();
+
+            if pending.is_empty() {
+                None
+            } else {
+                Some(format!("pending migrations: {}", pending.join(", ")))
+            }
+        }
+        Ok(Err(err)) => Some(err.to_string()),
+        Err(_) => Some(format!("timed out after {}ms", limit.as_millis())),
+    };
+
+    dependency_status(start, error)
+}
+
+/// As last seen by the replica health check, without querying it again.
+fn check_replica(mm: &ModelController) -> DependencyStatus {
+    let start = Instant::now();
+    let Some(replica) = mm.replica() else {
+        return skipped(start);
+    };
+    let error = (!replica.is_healthy()).then(|| String::from("reads on the primary"));
+
+    dependency_status(start, error)
+}
+
+fn dependency_status(start: Instant, error: Option<String>) -> DependencyStatus {
+    DependencyStatus {
+        status: if error.is_none() { "up" } else { "down" },
+        latency_ms: start.elapsed().as_millis(),
+        error,
+    }
+}
+
+/// The in-memory backend has no database to check, and no replica may be
+/// configured.
+fn skipped(start: Instant) -> DependencyStatus {
+    DependencyStatus {
+        status: "skipped",
+        latency_ms: start.elapsed().as_millis(),
+        error: None,
+    }
+}
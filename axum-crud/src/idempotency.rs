@@ -0,0 +1,507 @@
+//! `Idempotency-Key` support for mutating endpoints.
+//!
+//! The first request with a key runs as usual and its response is stored
+//! for `IDEMPOTENCY_TTL_SEC`. A retry with the same key and the same method,
+//! URI and body gets the stored response back, with `Idempotent-Replayed:
+//! true`, instead of running again. A key reused for a different request is
+//! rejected with 422, and a retry racing the first request with 409.
+//! Keys are per API key, two callers may pick the same one.
+//!
+//! Client errors are not stored, the request was turned away before it
+//! wrote anything and retrying runs it again. Server errors are, as is a
+//! 503 for a request cut off after `IDEMPOTENCY_REQUEST_TIMEOUT_SEC`: the
+//! request may have written before failing, so running it again could
+//! apply it twice. A claimed key is held a few seconds past the timeout, so
+//! a key whose request was lost with the process can be retried once that
+//! passed. Expired keys are purged by the `purge_idempotency_keys` job.
+
+use std::{future::Future, time::Duration};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::State,
+    http::{HeaderName, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{prelude::FromRow, types::Json as SqlJson};
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+use crate::{
+    api_keys_fns::ApiKeyPrincipal,
+    config::core_config,
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+    store::Db,
+};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
+/// Same as axum's default request body limit.
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+const MAX_KEY_LEN: usize = 255;
+
+/// Owner of the keys sent with `API_ADMIN_KEY`, which has no id.
+const ADMIN_KEY_OWNER: i64 = 0;
+
+/// Added to `IDEMPOTENCY_REQUEST_TIMEOUT_SEC` for the time a claimed key is
+/// held, covering the storing of the response.
+const LEASE_MARGIN: Duration = Duration::from_secs(5);
+
+#[derive(FromRow)]
+struct StoredKey {
+    fingerprint: String,
+    completed: bool,
+    response_status: Option<i32>,
+    response_headers: Option<SqlJson<Vec<(String, String)>>>,
+    response_body: Option<Vec<u8>>,
+}
+
+enum Claim {
+    /// First request with this key, it is ours to run.
+    New,
+    Replay(StoredKey),
+    InProgress,
+    Mismatch,
+}
+
+pub async fn mw_idempotency(
+    State(mm): State<ModelController>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    if matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::DELETE
+    ) {
+        return next.run(req).await;
+    }
+    let Some(key) = req.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(req).await;
+    };
+    // Without a database there is nowhere to keep responses.
+    let Ok(db) = mm.db() else {
+        return next.run(req).await;
+    };
+
+    debug!("{:<12} - mw_idempotency", "MIDDLEWARE");
+
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return reject(
+                StatusCode::BAD_REQUEST,
+                "Idempotency-Key must be 1 to 255 visible ASCII characters",
+            )
+        }
+    };
+    let key = Key {
+        owner: req
+            .extensions()
+            .get::<ApiKeyPrincipal>()
+            .and_then(|principal| principal.id)
+            .unwrap_or(ADMIN_KEY_OWNER),
+        key,
+    };
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return reject(StatusCode::PAYLOAD_TOO_LARGE, "request body is too large");
+    };
+    let fingerprint = fingerprint(&parts.method, &parts.uri.to_string(), &body);
+
+    let request_timeout = Duration::from_secs(core_config().IDEMPOTENCY_REQUEST_TIMEOUT_SEC);
+    match claim(db, &key, &fingerprint, request_timeout + LEASE_MARGIN).await {
+        Ok(Claim::New) => {}
+        Ok(Claim::Replay(stored)) => return replay(stored),
+        Ok(Claim::InProgress) => {
+            return reject(
+                StatusCode::CONFLICT,
+                "a request with this Idempotency-Key is still in progress",
+            )
+        }
+        Ok(Claim::Mismatch) => {
+            return reject(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request",
+            )
+        }
+        Err(err) => return err.into_response(),
+    }
+
+    let req = Request::from_parts(parts, Body::from(body));
+    settle(db, &key, next.run(req), request_timeout).await
+}
+
+/// Runs the request holding `key` and stores its response, unless it is a
+/// client error, in which case the key is dropped.
+async fn settle(
+    db: &Db,
+    key: &Key,
+    run: impl Future<Output = Response>,
+    request_timeout: Duration,
+) -> Response {
+    let res = match timeout(request_timeout, run).await {
+        Ok(res) => res,
+        Err(_) => {
+            warn!("{:<12} - request timed out", "IDEMPOTENCY");
+            reject(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "request timed out, it may have been applied",
+            )
+        }
+    };
+
+    if res.status().is_client_error() {
+        forget(db, key).await;
+        return res;
+    }
+
+    // -- Past this point the request may have written, the key is kept
+    //    held until its lease ends if the response cannot be stored.
+    let (parts, body) = res.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => return Error::SelectFailed(err.to_string()).into_response(),
+    };
+
+    let headers: Vec<(String, String)> = parts
+        .headers
+        .iter()
+        .filter(|(name, _)| *name != "content-length")
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    if let Err(err) = complete(db, key, parts.status, headers, &body).await {
+        warn!("{:<12} - storing response failed {err:?}", "IDEMPOTENCY");
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn fingerprint(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b"\n");
+    hasher.update(uri);
+    hasher.update(b"\n");
+    hasher.update(body);
+
+    data_encoding::HEXLOWER.encode(&hasher.finalize())
+}
+
+/// An idempotency key as its owner sent it.
+struct Key {
+    owner: i64,
+    key: String,
+}
+
+async fn claim(db: &Db, key: &Key, fingerprint: &str, lease: Duration) -> Result<Claim> {
+    // -- An expired key is free to be used again, and so is one whose
+    //    request was lost, when retried with the same request.
+    let query = "insert into idempotency_keys (api_key_id, idempotency_key, fingerprint, expires_at, locked_until) values ($1, $2, $3, now() + make_interval(secs => $4), now() + make_interval(secs => $5)) \
+        on conflict (api_key_id, idempotency_key) do update set fingerprint = excluded.fingerprint, completed = false, response_status = null, response_headers = null, response_body = null, expires_at = excluded.expires_at, locked_until = excluded.locked_until \
+        where idempotency_keys.expires_at <= now() or (not idempotency_keys.completed and idempotency_keys.locked_until <= now() and idempotency_keys.fingerprint = excluded.fingerprint)";
+    let claimed = sqlx::query(query)
+        .bind(key.owner)
+        .bind(&key.key)
+        .bind(fingerprint)
+        .bind(core_config().IDEMPOTENCY_TTL_SEC as f64)
+        .bind(lease.as_secs_f64())
+        .execute(db)
+        .await
+        .map_err(|err| Error::CreateFailed(err.to_string()))?;
+    if claimed.rows_affected() == 1 {
+        return Ok(Claim::New);
+    }
+
+    let query = "select fingerprint, completed, response_status, response_headers, response_body from idempotency_keys where api_key_id = $1 and idempotency_key = $2";
+    let stored = sqlx::query_as::<_, StoredKey>(query)
+        .bind(key.owner)
+        .bind(&key.key)
+        .fetch_optional(db)
+        .await
+        .map_err(|err| Error::SelectFailed(err.to_string()))?;
+
+    Ok(match stored {
+        Some(stored) if stored.fingerprint != fingerprint => Claim::Mismatch,
+        Some(stored) if stored.completed => Claim::Replay(stored),
+        // Still running, or just forgotten by a failed first attempt.
+        _ => Claim::InProgress,
+    })
+}
+
+async fn complete(
+    db: &Db,
+    key: &Key,
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: &[u8],
+) -> Result<()> {
+    let query = "update idempotency_keys set completed = true, response_status = $3, response_headers = $4, response_body = $5 where api_key_id = $1 and idempotency_key = $2";
+
+    sqlx::query(query)
+        .bind(key.owner)
+        .bind(&key.key)
+        .bind(i32::from(status.as_u16()))
+        .bind(SqlJson(headers))
+        .bind(body)
+        .execute(db)
+        .await
+        .map(|_| ())
+        .map_err(|err| Error::UpdateFailed(err.to_string()))
+}
+
+/// Drops the key so the request can be retried, once it is known to have
+/// written nothing.
+async fn forget(db: &Db, key: &Key) {
+    let query = "delete from idempotency_keys where api_key_id = $1 and idempotency_key = $2 and completed = false";
+
+    if let Err(err) = sqlx::query(query)
+        .bind(key.owner)
+        .bind(&key.key)
+        .execute(db)
+        .await
+    {
+        warn!("{:<12} - releasing key failed {err:?}", "IDEMPOTENCY");
+    }
+}
+
+/// Deletes the expired keys, returning how many.
+pub async fn purge_expired(mm: &ModelController) -> Result<u64> {
+    let timer = metrics().query_timer("purge_idempotency_keys");
+    let query = "delete from idempotency_keys where expires_at <= now()";
+
+    let res = sqlx::query(query)
+        .execute(mm.db()?)
+        .await
+        .map(|done| done.rows_affected())
+        .map_err(|err| {
+            debug!("{:<12} - purge_idempotency_keys error", "ERROR_CONTROLLER");
+            Error::DeleteFailed(err.to_string())
+        });
+    timer.observe(res)
+}
+
+fn replay(stored: StoredKey) -> Response {
+    debug!("{:<12} - replaying stored response", "IDEMPOTENCY");
+
+    let status = stored
+        .response_status
+        .and_then(|status| StatusCode::from_u16(status as u16).ok())
+        .unwrap_or(StatusCode::OK);
+    let mut res = (status, stored.response_body.unwrap_or_default()).into_response();
+
+    let headers = res.headers_mut();
+    for (name, value) in stored.response_headers.map(|h| h.0).unwrap_or_default() {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+
+    res
+}
+
+fn reject(status: StatusCode, message: &str) -> Response {
+    let body = Json(json!({
+        "result": {
+            "message": message,
+            "status": false,
+        }
+    }));
+
+    (status, body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{middleware, routing::post, Extension, Router};
+    use sqlx::PgPool;
+    use tokio::time::sleep;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::api_keys_fns::Scope;
+
+    /// Counts the requests that got through to the handler.
+    fn app(mm: ModelController, owner: i64, runs: Arc<AtomicUsize>) -> Router {
+        let principal = ApiKeyPrincipal {
+            id: Some(owner),
+            key_name: format!("key {owner}"),
+            scopes: vec![Scope::Write],
+        };
+
+        Router::new()
+            .route(
+                "/things",
+                post(move |body: String| async move {
+                    let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                    (StatusCode::CREATED, format!("run {run}: {body}"))
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(mm, mw_idempotency))
+            .layer(Extension(principal))
+    }
+
+    async fn send(app: &Router, key: &str, body: &str) -> (StatusCode, bool, String) {
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/things")
+            .header(IDEMPOTENCY_KEY, key)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let res = app.clone().oneshot(req).await.unwrap();
+        let status = res.status();
+        let replayed = res.headers().contains_key(IDEMPOTENT_REPLAYED);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+
+        (
+            status,
+            replayed,
+            String::from_utf8_lossy(&body).into_owned(),
+        )
+    }
+
+    #[sqlx::test]
+    async fn retries_replay_and_reused_keys_are_rejected(db: PgPool) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(ModelController::with_db(db), 1, runs.clone());
+
+        let first = send(&app, "k1", "a").await;
+        assert_eq!(first, (StatusCode::CREATED, false, "run 1: a".to_string()));
+
+        let retry = send(&app, "k1", "a").await;
+        assert_eq!(retry, (StatusCode::CREATED, true, "run 1: a".to_string()));
+
+        let (status, _, _) = send(&app, "k1", "b").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test]
+    async fn keys_are_per_api_key(db: PgPool) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mm = ModelController::with_db(db);
+        let first = app(mm.clone(), 1, runs.clone());
+        let second = app(mm, 2, runs.clone());
+
+        assert_eq!(send(&first, "k1", "a").await.2, "run 1: a");
+        assert_eq!(send(&second, "k1", "b").await.2, "run 2: b");
+        assert_eq!(send(&second, "k1", "b").await.2, "run 2: b");
+    }
+
+    #[sqlx::test]
+    async fn held_keys_conflict_until_their_lease_ends(db: PgPool) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(ModelController::with_db(db.clone()), 1, runs.clone());
+        let key = Key {
+            owner: 1,
+            key: "k1".to_string(),
+        };
+        let fingerprint = fingerprint(&Method::POST, "/things", b"a");
+
+        // -- Claimed by a request that is still running, or was lost.
+        let claimed = claim(&db, &key, &fingerprint, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(matches!(claimed, Claim::New));
+
+        let (status, _, _) = send(&app, "k1", "a").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        sqlx::query("update idempotency_keys set locked_until = now()")
+            .execute(&db)
+            .await
+            .unwrap();
+        let taken_over = send(&app, "k1", "a").await;
+        assert_eq!(
+            taken_over,
+            (StatusCode::CREATED, false, "run 1: a".to_string())
+        );
+    }
+
+    #[sqlx::test]
+    async fn timed_out_requests_are_not_run_again(db: PgPool) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(ModelController::with_db(db.clone()), 1, runs.clone());
+        let key = Key {
+            owner: 1,
+            key: "k1".to_string(),
+        };
+        let fingerprint = fingerprint(&Method::POST, "/things", b"a");
+        let claimed = claim(&db, &key, &fingerprint, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(matches!(claimed, Claim::New));
+
+        // -- Commits its write, then runs past the timeout.
+        let slow = async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            sleep(Duration::from_secs(5)).await;
+            StatusCode::CREATED.into_response()
+        };
+        let res = settle(&db, &key, slow, Duration::from_millis(50)).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // -- Not even once the lease is over.
+        sqlx::query("update idempotency_keys set locked_until = now()")
+            .execute(&db)
+            .await
+            .unwrap();
+        let (status, replayed, _) = send(&app, "k1", "a").await;
+        assert_eq!((status, replayed), (StatusCode::SERVICE_UNAVAILABLE, true));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test]
+    async fn client_errors_can_be_retried(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let key = Key {
+            owner: 1,
+            key: "k1".to_string(),
+        };
+        let fingerprint = fingerprint(&Method::POST, "/things", b"a");
+        claim(&db, &key, &fingerprint, Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let rejected = async { StatusCode::CONFLICT.into_response() };
+        let res = settle(&db, &key, rejected, Duration::from_secs(1)).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let retry = send(&app(mm, 1, runs), "k1", "a").await;
+        assert_eq!(retry, (StatusCode::CREATED, false, "run 1: a".to_string()));
+    }
+
+    #[sqlx::test]
+    async fn expired_keys_are_purged(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let app = app(mm.clone(), 1, Arc::new(AtomicUsize::new(0)));
+        send(&app, "k1", "a").await;
+        send(&app, "k2", "a").await;
+
+        sqlx::query("update idempotency_keys set expires_at = now() where idempotency_key = 'k1'")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        assert_eq!(purge_expired(&mm).await.unwrap(), 1);
+    }
+}
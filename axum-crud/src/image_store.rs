@@ -0,0 +1,85 @@
+//! Storage backends for image files.
+//!
+//! Image metadata lives in Postgres (`food_images`), the bytes behind an
+//! `ImageStore`. Files are addressed by a flat name such as `42.png` or
+//! `42_160.jpg`, so a store only has to map names to bytes.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use tokio::fs;
+
+use crate::error::{Error, Result};
+
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+    /// Writes `bytes` under `name`, replacing any previous file.
+    async fn put(&self, name: &str, bytes: Vec<u8>) -> Result<()>;
+
+    async fn get(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Removes `name`, missing files are not an error.
+    async fn delete(&self, name: &str) -> Result<()>;
+}
+
+/// Files kept in a local directory, created on first write.
+pub struct LocalDirImageStore {
+    dir: PathBuf,
+}
+
+impl LocalDirImageStore {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        LocalDirImageStore {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        // Names are generated by us, this only guards against mistakes.
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(Error::ImageStoreFailed(format!("bad file name {name:?}")));
+        }
+
+        Ok(self.dir.join(name))
+    }
+}
+
+#[async_trait]
+impl ImageStore for LocalDirImageStore {
+    async fn put(&self, name: &str, bytes: Vec<u8>) -> Result<()> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|err| Error::ImageStoreFailed(err.to_string()))?;
+
+        // -- Written aside and renamed, so readers never see half a file.
+        let tmp = self.dir.join(format!(".{name}.tmp"));
+        fs::write(&tmp, bytes)
+            .await
+            .map_err(|err| Error::ImageStoreFailed(err.to_string()))?;
+        fs::rename(&tmp, &path)
+            .await
+            .map_err(|err| Error::ImageStoreFailed(err.to_string()))
+    }
+
+    async fn get(&self, name: &str) -> Result<Vec<u8>> {
+        match fs::read(self.path(name)?).await {
+            Ok(bytes) => Ok(bytes),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                Err(Error::ImageNotFound(name.to_string()))
+            }
+            Err(err) => Err(Error::ImageStoreFailed(err.to_string())),
+        }
+    }
+
+    async fn delete(&self, name: &str) -> Result<()> {
+        match fs::remove_file(self.path(name)?).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::ImageStoreFailed(err.to_string())),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Cursor,
+};
+
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat, ImageReader,
+    Limits, Rgb, RgbImage,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+};
+
+/// Image metadata is kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct ImageModelController;
+
+/// Longest side of the generated thumbnails, in pixels.
+pub const THUMBNAIL_SIZES: [u32; 2] = [160, 640];
+
+/// Larger uploads are refused before they are decoded.
+const MAX_DIMENSION: u32 = 8192;
+const THUMBNAIL_QUALITY: u8 = 85;
+
+/// An image of a food, as listed with the food.
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct FoodImage {
+    pub id: i64,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    /// The image as uploaded. Image URLs need no API key, they are not
+    /// guessable instead.
+    pub url: String,
+    /// JPEG thumbnails, keyed by their longest side in pixels.
+    pub thumbnails: BTreeMap<String, String>,
+}
+
+/// Bytes of an image or thumbnail, ready to be served.
+#[derive(Debug)]
+pub struct ImageFile {
+    pub content_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(FromRow)]
+struct ImageRow {
+    id: i64,
+    food_id: i64,
+    public_token: String,
+    content_type: String,
+    width: i32,
+    height: i32,
+}
+
+impl From<ImageRow> for FoodImage {
+    fn from(row: ImageRow) -> Self {
+        let url = format!("/api/v1/images/{}", row.public_token);
+        let thumbnails = THUMBNAIL_SIZES
+            .iter()
+            .map(|size| (size.to_string(), format!("{url}/thumbnails/{size}")))
+            .collect();
+
+        FoodImage {
+            id: row.id,
+            content_type: row.content_type,
+            width: row.width,
+            height: row.height,
+            url,
+            thumbnails,
+        }
+    }
+}
+
+const IMAGE_COLUMNS: &str = "id, food_id, public_token, content_type, width, height";
+
+impl ImageModelController {
+    /// Checks `bytes` is a JPEG, PNG or WebP image, stores it and its
+    /// thumbnails.
+    pub async fn upload(mm: ModelController, food_id: i64, bytes: Vec<u8>) -> Result<FoodImage> {
+        debug!("{:<12} - upload_image", "HANDLER");
+
+        let timer = metrics().query_timer("upload_image");
+        timer.observe(upload(&mm, food_id, bytes).await)
+    }
+
+    pub async fn select(mm: ModelController, food_id: i64) -> Result<Vec<FoodImage>> {
+        debug!("{:<12} - select_images", "HANDLER");
+
+        Self::images_of_food(&mm, food_id).await
+    }
+
+    /// The uploaded image with that public token, or its thumbnail of
+    /// `size`.
+    pub async fn file(mm: ModelController, token: &str, size: Option<u32>) -> Result<ImageFile> {
+        debug!("{:<12} - get_image", "HANDLER");
+
+        let timer = metrics().query_timer("get_image");
+        timer.observe(file(&mm, token, size).await)
+    }
+
+    pub async fn delete(mm: ModelController, food_id: i64, id: i64) -> Result<FoodImage> {
+        debug!("{:<12} - delete_image", "HANDLER");
+
+        let timer = metrics().query_timer("delete_image");
+        let query = format!(
+            "delete from food_images where id = $1 and food_id = $2 returning {IMAGE_COLUMNS}"
+        );
+
+        let row = match sqlx::query_as::<_, ImageRow>(&query)
+            .bind(id)
+            .bind(food_id)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(row)) => row,
+            Ok(None) => {
+                return timer.observe(Err(Error::ImageNotFound(format!(
+                    "no image {id} of food {food_id}"
+                ))))
+            }
+            Err(err) => {
+                debug!("{:<12} - delete_image error", "ERROR_CONTROLLER");
+                return timer.observe(Err(Error::DeleteFailed(err.to_string())));
+            }
+        };
+
+        // -- The row is gone, leftover files are only wasted space.
+        for name in file_names(row.id, &row.content_type) {
+            if let Err(err) = mm.images().delete(&name).await {
+                warn!("{:<12} - removing {name} failed {err:?}", "IMAGES");
+            }
+        }
+
+        timer.observe(Ok(row.into()))
+    }
+
+    /// Images of each of `food_ids`, oldest first. Empty without a database.
+    pub(crate) async fn images_of(
+        mm: &ModelController,
+        food_ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<FoodImage>>> {
+        let Ok(db) = mm.read_db() else {
+            return Ok(HashMap::new());
+        };
+
+        let timer = metrics().query_timer("select_images");
+        let query =
+            format!("select {IMAGE_COLUMNS} from food_images where food_id = any($1) order by id");
+
+        let rows = timer.observe(
+            sqlx::query_as::<_, ImageRow>(&query)
+                .bind(food_ids)
+                .fetch_all(db)
+                .await
+                .map_err(|err| {
+                    debug!("{:<12} - select_images error", "ERROR_CONTROLLER");
+                    Error::SelectFailed(err.to_string())
+                }),
+        )?;
+
+        let mut images: HashMap<i64, Vec<FoodImage>> = HashMap::new();
+        for row in rows {
+            images.entry(row.food_id).or_default().push(row.into());
+        }
+
+        Ok(images)
+    }
+
+    pub(crate) async fn images_of_food(
+        mm: &ModelController,
+        food_id: i64,
+    ) -> Result<Vec<FoodImage>> {
+        let mut images = Self::images_of(mm, &[food_id]).await?;
+
+        Ok(images.remove(&food_id).unwrap_or_default())
+    }
+}
+
+async fn upload(mm: &ModelController, food_id: i64, bytes: Vec<u8>) -> Result<FoodImage> {
+    // -- Decoding and resizing are CPU bound, keep them off the runtime.
+    let (bytes, processed) = tokio::task::spawn_blocking(move || {
+        let processed = process(&bytes);
+        (bytes, processed)
+    })
+    .await
+    .map_err(|err| Error::ImageStoreFailed(err.to_string()))?;
+    let processed = processed?;
+
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::CreateFailed(err.to_string()))?;
+
+    let query = format!("insert into food_images (food_id, content_type, byte_size, width, height) select $1, $2, $3, $4, $5 where exists (select 1 from foods_table where id = $1 and food_status != 'removed') returning {IMAGE_COLUMNS}");
+    let row = match sqlx::query_as::<_, ImageRow>(&query)
+        .bind(food_id)
+        .bind(processed.content_type)
+        .bind(bytes.len() as i32)
+        .bind(processed.width as i32)
+        .bind(processed.height as i32)
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return Err(Error::FoodIdNotFound(format!("no food with id {food_id}"))),
+        Err(err) => {
+            debug!("{:<12} - upload_image error", "ERROR_CONTROLLER");
+            return Err(Error::CreateFailed(err.to_string()));
+        }
+    };
+
+    // -- Files first, so a committed row always has them. On failure the
+    //    row is rolled back and the files written so far removed.
+    let mut files = vec![(file_name(row.id, &row.content_type), bytes)];
+    for (size, thumbnail) in processed.thumbnails {
+        files.push((thumbnail_name(row.id, size), thumbnail));
+    }
+    let mut written: Vec<String> = Vec::new();
+    for (name, bytes) in files {
+        if let Err(err) = mm.images().put(&name, bytes).await {
+            for name in written {
+                let _ = mm.images().delete(&name).await;
+            }
+            return Err(err);
+        }
+        written.push(name);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::CreateFailed(err.to_string()))?;
+
+    Ok(row.into())
+}
+
+async fn file(mm: &ModelController, token: &str, size: Option<u32>) -> Result<ImageFile> {
+    let query = "select id, content_type from food_images where public_token = $1";
+    let (id, content_type) = sqlx::query_as::<_, (i64, String)>(query)
+        .bind(token)
+        .fetch_optional(mm.db()?)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - get_image error", "ERROR_CONTROLLER");
+            Error::SelectFailed(err.to_string())
+        })?
+        .ok_or_else(|| Error::ImageNotFound(String::from("no image with that token")))?;
+
+    match size {
+        None => Ok(ImageFile {
+            content_type: static_content_type(&content_type)?,
+            bytes: mm.images().get(&file_name(id, &content_type)).await?,
+        }),
+        Some(size) if THUMBNAIL_SIZES.contains(&size) => Ok(ImageFile {
+            content_type: "image/jpeg",
+            bytes: mm.images().get(&thumbnail_name(id, size)).await?,
+        }),
+        Some(size) => Err(Error::ImageNotFound(format!(
+            "no {size} px thumbnail, sizes are {THUMBNAIL_SIZES:?}"
+        ))),
+    }
+}
+
+struct Processed {
+    content_type: &'static str,
+    width: u32,
+    height: u32,
+    thumbnails: Vec<(u32, Vec<u8>)>,
+}
+
+fn process(bytes: &[u8]) -> Result<Processed> {
+    let format = image::guess_format(bytes)
+        .map_err(|_| Error::InvalidImage(String::from("not an image")))?;
+    let content_type = match format {
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Png => "image/png",
+        ImageFormat::WebP => "image/webp",
+        other => {
+            return Err(Error::InvalidImage(format!(
+                "{other:?} images are not supported, use JPEG, PNG or WebP"
+            )))
+        }
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader
+        .decode()
+        .map_err(|err| Error::InvalidImage(err.to_string()))?;
+
+    let mut thumbnails = Vec::new();
+    for size in THUMBNAIL_SIZES {
+        thumbnails.push((size, thumbnail(&image, size)?));
+    }
+
+    Ok(Processed {
+        content_type,
+        width: image.width(),
+        height: image.height(),
+        thumbnails,
+    })
+}
+
+/// `image` scaled down to fit `size` x `size` as a JPEG, transparent
+/// parts turned white.
+fn thumbnail(image: &DynamicImage, size: u32) -> Result<Vec<u8>> {
+    let scaled = if image.width() > size || image.height() > size {
+        image.resize(size, size, FilterType::Lanczos3)
+    } else {
+        image.clone()
+    };
+
+    let rgba = scaled.to_rgba8();
+    let rgb = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let over_white = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        Rgb([over_white(r), over_white(g), over_white(b)])
+    });
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY)
+        .encode_image(&rgb)
+        .map_err(|err| Error::ImageStoreFailed(err.to_string()))?;
+
+    Ok(jpeg)
+}
+
+fn static_content_type(content_type: &str) -> Result<&'static str> {
+    match content_type {
+        "image/jpeg" => Ok("image/jpeg"),
+        "image/png" => Ok("image/png"),
+        "image/webp" => Ok("image/webp"),
+        other => Err(Error::ImageStoreFailed(format!(
+            "unexpected content type {other}"
+        ))),
+    }
+}
+
+fn file_name(id: i64, content_type: &str) -> String {
+    let ext = match content_type {
+        "image/png" => "png",
+        "image/webp" => "webp",
+        _ => "jpg",
+    };
+
+    format!("{id}.{ext}")
+}
+
+fn thumbnail_name(id: i64, size: u32) -> String {
+    format!("{id}_{size}.jpg")
+}
+
+/// Every file stored for an image.
+pub(crate) fn file_names(id: i64, content_type: &str) -> Vec<String> {
+    let mut names = vec![file_name(id, content_type)];
+    names.extend(THUMBNAIL_SIZES.iter().map(|size| thumbnail_name(id, *size)));
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnails_fit_their_size() {
+        let image = DynamicImage::new_rgba8(1200, 300);
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let processed = process(&png).unwrap();
+        assert_eq!(processed.content_type, "image/png");
+        assert_eq!((processed.width, processed.height), (1200, 300));
+
+        for (size, jpeg) in processed.thumbnails {
+            let thumb = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+            assert_eq!(thumb.width(), size);
+            assert_eq!(thumb.height(), size / 4);
+        }
+
+        assert!(process(b"not an image").is_err());
+    }
+}
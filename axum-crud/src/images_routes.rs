@@ -0,0 +1,249 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::Serialize;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    config::core_config,
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::{Error, Result},
+    images_fns::{FoodImage, ImageFile, ImageModelController},
+};
+
+pub fn routes_images(mm: ModelController) -> Router {
+    // -- Room for the multipart framing around the image.
+    let body_limit = core_config().IMAGE_MAX_BYTES + 64 * 1024;
+
+    Router::new()
+        .route(
+            "/api/v1/foods/:id/images",
+            get(api_v1_list_images)
+                .post(api_v1_upload_image)
+                .layer(DefaultBodyLimit::max(body_limit)),
+        )
+        .route(
+            "/api/v1/foods/:id/images/:image_id",
+            delete(api_v1_delete_image),
+        )
+        .with_state(mm)
+}
+
+/// The image files, to be mounted outside the API key layer: menu boards
+/// and `<img>` tags cannot send a key. An image is addressed by a random
+/// 128-bit token rather than its id, so only clients that were handed a
+/// food's image URLs can load it, as with a signed URL that never expires.
+pub fn routes_image_files(mm: ModelController) -> Router {
+    Router::new()
+        .route("/api/v1/images/:token", get(api_v1_get_image))
+        .route(
+            "/api/v1/images/:token/thumbnails/:size",
+            get(api_v1_get_thumbnail),
+        )
+        .with_state(mm)
+}
+
+/// Images never change once uploaded, a new upload gets a new token. Shared
+/// caches may keep them, they are served to anyone holding the URL anyway.
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+struct UploadImagePayload {
+    /// JPEG, PNG or WebP.
+    #[schema(value_type = String, format = Binary)]
+    image: Vec<u8>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/images",
+    tag = "images",
+    params(("id" = i64, Path, description = "Food id")),
+    responses(
+        (status = 200, description = "Images of the food, oldest first", body = DataBody<Vec<FoodImage>>),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_v1_list_images(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+) -> Result<Json<DataBody<Vec<FoodImage>>>> {
+    debug!("{:<12} - api_v1_list_images", "ROUTE_HANDLER");
+
+    let images = ImageModelController::select(mm, food_id).await?;
+    let body = Json(DataBody::new(images));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/foods/{id}/images",
+    tag = "images",
+    params(("id" = i64, Path, description = "Food id")),
+    request_body(content = UploadImagePayload, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Image stored and thumbnails generated", body = DataBody<FoodImage>,
+            headers(("location" = String, description = "URL of the image"))),
+        (status = 413, description = "Image larger than `IMAGE_MAX_BYTES`"),
+        (status = 400, description = "Not a JPEG, PNG or WebP image"),
+        (status = 404, description = "Food not found"),
+    )
+)]
+async fn api_v1_upload_image(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    mut multipart: Multipart,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_upload_image", "ROUTE_HANDLER");
+
+    // -- Multipart errors carry their own status, 413 past the body limit.
+    let mut bytes = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return Ok(err.into_response()),
+        };
+        if field.name() == Some("image") {
+            match field.bytes().await {
+                Ok(data) => bytes = Some(data.to_vec()),
+                Err(err) => return Ok(err.into_response()),
+            }
+            break;
+        }
+    }
+    let bytes =
+        bytes.ok_or_else(|| Error::InvalidImage(String::from("missing `image` form field")))?;
+    if bytes.len() > core_config().IMAGE_MAX_BYTES {
+        return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+    }
+
+    let image = ImageModelController::upload(mm, food_id, bytes).await?;
+    let location = image.url.clone();
+    let body = Json(DataBody::new(image));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/foods/{id}/images/{image_id}",
+    tag = "images",
+    params(
+        ("id" = i64, Path, description = "Food id"),
+        ("image_id" = i64, Path, description = "Image id"),
+    ),
+    responses(
+        (status = 200, description = "Image and its thumbnails removed", body = DataBody<FoodImage>),
+        (status = 404, description = "Image not found"),
+    )
+)]
+async fn api_v1_delete_image(
+    State(mm): State<ModelController>,
+    Path((food_id, image_id)): Path<(i64, i64)>,
+) -> Result<Json<DataBody<FoodImage>>> {
+    debug!("{:<12} - api_v1_delete_image", "ROUTE_HANDLER");
+
+    let image = ImageModelController::delete(mm, food_id, image_id).await?;
+    let body = Json(DataBody::new(image));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/images/{token}",
+    tag = "images",
+    params(("token" = String, Path, description = "Token of the image, from its `url`")),
+    security(()),
+    responses(
+        (status = 200, description = "The image as uploaded, cacheable for a year. No API key needed", content_type = "image/*"),
+        (status = 304, description = "`If-None-Match` matched"),
+        (status = 404, description = "Image not found"),
+    )
+)]
+async fn api_v1_get_image(
+    State(mm): State<ModelController>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_get_image", "ROUTE_HANDLER");
+
+    let etag = format!("\"image-{token}\"");
+    if if_none_match(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+
+    let file = ImageModelController::file(mm, &token, None).await?;
+    Ok(image_response(file, &etag))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/images/{token}/thumbnails/{size}",
+    tag = "images",
+    params(
+        ("token" = String, Path, description = "Token of the image, from its `url`"),
+        ("size" = u32, Path, description = "Longest side in pixels, 160 or 640"),
+    ),
+    security(()),
+    responses(
+        (status = 200, description = "JPEG thumbnail, cacheable for a year. No API key needed", content_type = "image/jpeg"),
+        (status = 304, description = "`If-None-Match` matched"),
+        (status = 404, description = "Image or size not found"),
+    )
+)]
+async fn api_v1_get_thumbnail(
+    State(mm): State<ModelController>,
+    Path((token, size)): Path<(String, u32)>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_get_thumbnail", "ROUTE_HANDLER");
+
+    let etag = format!("\"image-{token}-{size}\"");
+    if if_none_match(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+
+    let file = ImageModelController::file(mm, &token, Some(size)).await?;
+    Ok(image_response(file, &etag))
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag))
+}
+
+fn cache_headers(etag: &str) -> [(header::HeaderName, HeaderValue); 2] {
+    [
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(IMAGE_CACHE_CONTROL),
+        ),
+        (
+            header::ETAG,
+            HeaderValue::from_str(etag).expect("etag is ASCII"),
+        ),
+    ]
+}
+
+fn not_modified(etag: &str) -> Response {
+    (StatusCode::NOT_MODIFIED, cache_headers(etag)).into_response()
+}
+
+fn image_response(file: ImageFile, etag: &str) -> Response {
+    (
+        cache_headers(etag),
+        [(header::CONTENT_TYPE, file.content_type)],
+        file.bytes,
+    )
+        .into_response()
+}
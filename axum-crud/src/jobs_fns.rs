@@ -0,0 +1,521 @@
+//! In-process scheduler for the periodic jobs.
+//!
+//! Schedules are cron expressions with a seconds field, in UTC, read from
+//! the `JOB_*_CRON` settings; `off` disables a job. Every replica runs the
+//! scheduler. A run first takes a Postgres advisory lock on the job's name,
+//! so a job never runs on two replicas at once, then claims its tick in
+//! `job_runs`, so each tick runs once. Replicas losing either race skip it.
+//! Holding the lock also means no run of the job is alive anywhere, so
+//! `running` rows left by a replica that died mid-run are marked `failed`.
+
+use std::{str::FromStr, sync::OnceLock};
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+use tokio::{
+    sync::watch,
+    task::{JoinHandle, JoinSet},
+    time::sleep,
+};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    admin_fns::AdminModelController,
+    config::core_config,
+    crud_fns::{FoodModelController, ModelController},
+    error::{Error, Result},
+    idempotency,
+    metrics::metrics,
+    prices_fns::PriceModelController,
+    reorder_fns::{ReorderModelController, ReorderPolicy},
+    reservations_fns::ReservationModelController,
+};
+
+/// Runs kept per job, older ones are dropped after each run.
+const RUNS_KEPT: i64 = 200;
+
+/// Names in the low stock scan's detail, the rest are counted.
+const LOW_STOCK_NAMED: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobKind {
+    ExpireReservations,
+    ApplyScheduledPrices,
+    PurgeRemovedFoods,
+    LowStockScan,
+    PurgeIdempotencyKeys,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 5] = [
+        JobKind::ExpireReservations,
+        JobKind::ApplyScheduledPrices,
+        JobKind::PurgeRemovedFoods,
+        JobKind::LowStockScan,
+        JobKind::PurgeIdempotencyKeys,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            JobKind::ExpireReservations => "expire_reservations",
+            JobKind::ApplyScheduledPrices => "apply_scheduled_prices",
+            JobKind::PurgeRemovedFoods => "purge_removed_foods",
+            JobKind::LowStockScan => "low_stock_scan",
+            JobKind::PurgeIdempotencyKeys => "purge_idempotency_keys",
+        }
+    }
+
+    fn cron(self) -> &'static str {
+        match self {
+            JobKind::ExpireReservations => &core_config().JOB_EXPIRE_RESERVATIONS_CRON,
+            JobKind::ApplyScheduledPrices => &core_config().JOB_APPLY_SCHEDULED_PRICES_CRON,
+            JobKind::PurgeRemovedFoods => &core_config().JOB_PURGE_REMOVED_FOODS_CRON,
+            JobKind::LowStockScan => &core_config().JOB_LOW_STOCK_SCAN_CRON,
+            JobKind::PurgeIdempotencyKeys => &core_config().JOB_PURGE_IDEMPOTENCY_KEYS_CRON,
+        }
+    }
+
+    /// Does the work, returning a line on what was done.
+    async fn run(self, mm: &ModelController) -> Result<String> {
+        match self {
+            JobKind::ExpireReservations => {
+                let expired = ReservationModelController::sweep(mm).await?;
+                Ok(format!("expired {expired} reservations"))
+            }
+            JobKind::ApplyScheduledPrices => {
+                let repriced = PriceModelController::apply_due(mm).await?;
+                Ok(format!("repriced {repriced} foods"))
+            }
+            JobKind::PurgeRemovedFoods => {
+                let after_days = core_config().PURGE_REMOVED_FOODS_AFTER_DAYS;
+                let purged = AdminModelController::purge_removed_foods(mm, after_days).await?;
+                Ok(format!(
+                    "purged {purged} foods removed over {after_days} days ago"
+                ))
+            }
+            JobKind::LowStockScan => low_stock_scan(mm).await,
+            JobKind::PurgeIdempotencyKeys => {
+                let purged = idempotency::purge_expired(mm).await?;
+                Ok(format!("purged {purged} expired idempotency keys"))
+            }
+        }
+    }
+}
+
+pub struct Job {
+    pub kind: JobKind,
+    /// `None` when the job is off.
+    schedule: Option<Schedule>,
+}
+
+/// The jobs with their schedules, parsed once. Panics on a malformed
+/// schedule, like `core_config` on a malformed setting.
+pub fn jobs() -> &'static [Job] {
+    static INSTANCE: OnceLock<Vec<Job>> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        JobKind::ALL
+            .into_iter()
+            .map(|kind| Job {
+                kind,
+                schedule: parse_schedule(kind.cron())
+                    .unwrap_or_else(|ex| panic!("FATAL - while loading - error: {ex:?}")),
+            })
+            .collect()
+    })
+}
+
+fn parse_schedule(expr: &str) -> Result<Option<Schedule>> {
+    if expr.trim() == "off" {
+        return Ok(None);
+    }
+
+    Schedule::from_str(expr)
+        .map(Some)
+        .map_err(|err| Error::InvalidSchedule(format!("{expr}: {err}")))
+}
+
+fn job(name: &str) -> Result<&'static Job> {
+    jobs()
+        .iter()
+        .find(|job| job.kind.name() == name)
+        .ok_or(Error::JobNotFound(name.to_string()))
+}
+
+#[derive(Clone, Debug)]
+pub struct JobModelController;
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct JobRunToSelect {
+    pub id: i64,
+    pub job_name: String,
+    /// The tick the run was scheduled for, RFC 3339, UTC.
+    pub scheduled_at: String,
+    /// `running`, `succeeded` or `failed`.
+    pub job_run_status: String,
+    /// RFC 3339, UTC.
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    /// What the run did.
+    pub detail: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobToSelect {
+    pub name: String,
+    /// Cron expression with seconds, UTC, or `off`.
+    pub schedule: String,
+    pub enabled: bool,
+    /// RFC 3339, UTC. Any replica may take that run.
+    pub next_run_at: Option<String>,
+    pub last_run: Option<JobRunToSelect>,
+    /// Latest failed run, even when later runs succeeded.
+    pub last_failure: Option<JobRunToSelect>,
+}
+
+const JOB_RUN_COLUMNS: &str = "id, job_name, to_char(scheduled_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as scheduled_at, job_run_status::text as job_run_status, to_char(started_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as started_at, to_char(finished_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as finished_at, detail, error";
+
+impl JobModelController {
+    /// Every job with its schedule and latest runs, across replicas.
+    pub async fn select(mm: ModelController) -> Result<Vec<JobToSelect>> {
+        debug!("{:<12} - select_jobs", "HANDLER");
+
+        let db = mm.db()?;
+        let timer = metrics().query_timer("select_jobs");
+        let res = async {
+            let query = format!("select distinct on (job_name) {JOB_RUN_COLUMNS} from job_runs order by job_name, id desc");
+            let last_runs = sqlx::query_as::<_, JobRunToSelect>(&query)
+                .fetch_all(db)
+                .await?;
+            let query = format!("select distinct on (job_name) {JOB_RUN_COLUMNS} from job_runs where job_run_status = 'failed' order by job_name, id desc");
+            let last_failures = sqlx::query_as::<_, JobRunToSelect>(&query)
+                .fetch_all(db)
+                .await?;
+            Ok((last_runs, last_failures))
+        }
+        .await
+        .map_err(|err: sqlx::Error| {
+            debug!("{:<12} - select_jobs error", "ERROR_CONTROLLER");
+            Error::SelectFailed(err.to_string())
+        });
+        let (mut last_runs, mut last_failures) = timer.observe(res)?;
+
+        let now = Utc::now();
+        let jobs = jobs()
+            .iter()
+            .map(|job| {
+                let name = job.kind.name();
+                let last_run = take_run(&mut last_runs, name);
+                let last_failure = take_run(&mut last_failures, name);
+
+                JobToSelect {
+                    name: name.to_string(),
+                    schedule: job.kind.cron().to_string(),
+                    enabled: job.schedule.is_some(),
+                    next_run_at: job
+                        .schedule
+                        .as_ref()
+                        .and_then(|schedule| schedule.after(&now).next())
+                        .map(|at| at.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+                    last_run,
+                    last_failure,
+                }
+            })
+            .collect();
+
+        Ok(jobs)
+    }
+
+    /// A job's runs, newest first.
+    pub async fn runs(mm: ModelController, name: &str, limit: i64) -> Result<Vec<JobRunToSelect>> {
+        debug!("{:<12} - select_job_runs", "HANDLER");
+
+        let job = job(name)?;
+        let timer = metrics().query_timer("select_job_runs");
+        let query = format!(
+            "select {JOB_RUN_COLUMNS} from job_runs where job_name = $1 order by id desc limit $2"
+        );
+
+        let res = sqlx::query_as::<_, JobRunToSelect>(&query)
+            .bind(job.kind.name())
+            .bind(limit)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - select_job_runs error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+}
+
+fn take_run(runs: &mut Vec<JobRunToSelect>, name: &str) -> Option<JobRunToSelect> {
+    runs.iter()
+        .position(|run| run.job_name == name)
+        .map(|i| runs.remove(i))
+}
+
+/// Stops the scheduler between runs.
+pub struct Scheduler {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Scheduler {
+    /// Waits for the runs in progress to finish.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+}
+
+/// Runs the enabled jobs on their schedules until `Scheduler::shutdown`.
+/// Each due job runs in a task of its own, so a slow job does not hold the
+/// others back; a tick reached while the job's previous run still holds the
+/// lock is skipped.
+pub fn spawn_scheduler(mm: ModelController) -> Scheduler {
+    let (stop, mut stopped) = watch::channel(false);
+
+    let task = tokio::spawn(async move {
+        let mut next: Vec<(&Job, DateTime<Utc>)> = jobs()
+            .iter()
+            .filter_map(|job| Some((job, upcoming(job)?)))
+            .collect();
+        for (job, at) in &next {
+            info!("{:<12} - {} next at {at}", "SCHEDULER", job.kind.name());
+        }
+
+        let mut running = JoinSet::new();
+        while let Some(due) = next.iter().map(|(_, at)| *at).min() {
+            let wait = (due - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = sleep(wait) => {}
+                _ = stopped.changed() => break,
+            }
+            while running.try_join_next().is_some() {}
+
+            let mut ahead = Vec::with_capacity(next.len());
+            for (job, at) in next {
+                if at > Utc::now() {
+                    ahead.push((job, at));
+                    continue;
+                }
+                running.spawn(run_locked(mm.clone(), job.kind, at));
+                ahead.extend(upcoming(job).map(|at| (job, at)));
+            }
+            next = ahead;
+        }
+
+        while running.join_next().await.is_some() {}
+    });
+
+    Scheduler { stop, task }
+}
+
+fn upcoming(job: &Job) -> Option<DateTime<Utc>> {
+    job.schedule.as_ref()?.upcoming(Utc).next()
+}
+
+/// Runs the job for the tick unless another replica holds its lock or
+/// already ran that tick, recording the run.
+async fn run_locked(mm: ModelController, kind: JobKind, tick: DateTime<Utc>) {
+    let name = kind.name();
+
+    match try_run_locked(&mm, kind, tick).await {
+        Ok(None) => debug!("{:<12} - {name} tick taken elsewhere", "SCHEDULER"),
+        Ok(Some(Ok(detail))) => info!("{:<12} - {name}: {detail}", "SCHEDULER"),
+        Ok(Some(Err(err))) => warn!("{:<12} - {name} failed {err:?}", "SCHEDULER"),
+        Err(err) => warn!("{:<12} - {name} not run {err:?}", "SCHEDULER"),
+    }
+}
+
+/// `None` when the lock or the tick is taken. The outer error is about the
+/// lock or the run record, the inner one is the job's own.
+async fn try_run_locked(
+    mm: &ModelController,
+    kind: JobKind,
+    tick: DateTime<Utc>,
+) -> Result<Option<Result<String>>> {
+    let db = mm.db()?;
+    let tick = OffsetDateTime::from_unix_timestamp(tick.timestamp())
+        .map_err(|err| Error::InvalidSchedule(err.to_string()))?;
+
+    // -- The lock lives as long as this transaction, an aborted run
+    //    releases it with the connection.
+    let mut lock = db
+        .begin()
+        .await
+        .map_err(|err| Error::SelectFailed(err.to_string()))?;
+    let query = "select pg_try_advisory_xact_lock(hashtext('axum_crud.jobs'), hashtext($1))";
+    let locked = sqlx::query_scalar::<_, bool>(query)
+        .bind(kind.name())
+        .fetch_one(&mut *lock)
+        .await
+        .map_err(|err| Error::SelectFailed(err.to_string()))?;
+    if !locked {
+        return Ok(None);
+    }
+
+    let query = "update job_runs set job_run_status = 'failed', finished_at = now(), error = 'interrupted before finishing' where job_name = $1 and job_run_status = 'running'";
+    let stale = sqlx::query(query)
+        .bind(kind.name())
+        .execute(db)
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?
+        .rows_affected();
+    if stale > 0 {
+        warn!(
+            "{:<12} - {} marked {stale} interrupted runs failed",
+            "SCHEDULER",
+            kind.name()
+        );
+    }
+
+    // -- Committed straight away, a replica reaching the same tick after
+    //    this run released the lock finds it claimed.
+    let query = "insert into job_runs (job_name, scheduled_at) values ($1, $2) on conflict (job_name, scheduled_at) do nothing returning id";
+    let Some(run_id) = sqlx::query_scalar::<_, i64>(query)
+        .bind(kind.name())
+        .bind(tick)
+        .fetch_optional(db)
+        .await
+        .map_err(|err| Error::CreateFailed(err.to_string()))?
+    else {
+        return Ok(None);
+    };
+
+    let res = kind.run(mm).await;
+
+    let (status, detail, error) = match &res {
+        Ok(detail) => ("succeeded", Some(detail.clone()), None),
+        Err(err) => ("failed", None, Some(err.to_string())),
+    };
+    let query = "update job_runs set job_run_status = $2::job_run_stat, finished_at = now(), detail = $3, error = $4 where id = $1";
+    sqlx::query(query)
+        .bind(run_id)
+        .bind(status)
+        .bind(detail)
+        .bind(error)
+        .execute(db)
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let query = "delete from job_runs where job_name = $1 and id not in (select id from job_runs where job_name = $1 order by id desc limit $2)";
+    sqlx::query(query)
+        .bind(kind.name())
+        .bind(RUNS_KEPT)
+        .execute(db)
+        .await
+        .map_err(|err| Error::DeleteFailed(err.to_string()))?;
+
+    lock.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+    Ok(Some(res))
+}
+
+/// Foods whose stock no longer covers the reorder lead time, see
+/// `reorder_fns`.
+async fn low_stock_scan(mm: &ModelController) -> Result<String> {
+    let stats = FoodModelController::inventory_stats(mm.clone(), None).await?;
+    let low =
+        ReorderModelController::suggestions(mm.clone(), ReorderPolicy::default(), None).await?;
+
+    if low.is_empty() {
+        return Ok(format!(
+            "{} foods out of stock, none to reorder",
+            stats.out_of_stock
+        ));
+    }
+
+    let names: Vec<&str> = low
+        .iter()
+        .take(LOW_STOCK_NAMED)
+        .map(|s| s.food_name.as_str())
+        .collect();
+    let more = low.len().saturating_sub(LOW_STOCK_NAMED);
+    let more = if more > 0 {
+        format!(" and {more} more")
+    } else {
+        String::new()
+    };
+    warn!(
+        "{:<12} - {} foods running low: {}{more}",
+        "SCHEDULER",
+        low.len(),
+        names.join(", ")
+    );
+
+    Ok(format!(
+        "{} foods out of stock, {} to reorder: {}{more}",
+        stats.out_of_stock,
+        low.len(),
+        names.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_schedule() {
+        assert!(parse_schedule("off").unwrap().is_none());
+
+        let schedule = parse_schedule("0 */5 * * * *").unwrap().unwrap();
+        let at = DateTime::parse_from_rfc3339("2026-10-19T08:01:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let next = schedule.after(&at).next().unwrap();
+        assert_eq!(next.to_rfc3339(), "2026-10-19T08:05:00+00:00");
+
+        assert!(matches!(
+            parse_schedule("every minute"),
+            Err(Error::InvalidSchedule(_))
+        ));
+    }
+
+    #[test]
+    fn test_jobs_follow_config() {
+        assert_eq!(jobs().len(), JobKind::ALL.len());
+        assert!(job("expire_reservations").is_ok());
+        assert!(matches!(job("nope"), Err(Error::JobNotFound(_))));
+    }
+
+    #[sqlx::test]
+    async fn interrupted_runs_are_failed_by_the_next_one(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        sqlx::query("insert into job_runs (job_name, scheduled_at) values ('low_stock_scan', now() - interval '1 hour')")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let tick = Utc::now();
+        let res = try_run_locked(&mm, JobKind::LowStockScan, tick)
+            .await
+            .unwrap();
+        assert!(matches!(res, Some(Ok(_))));
+
+        let runs = JobModelController::runs(mm, "low_stock_scan", 10)
+            .await
+            .unwrap();
+        let statuses: Vec<(&str, Option<&str>)> = runs
+            .iter()
+            .map(|run| (run.job_run_status.as_str(), run.error.as_deref()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("succeeded", None),
+                ("failed", Some("interrupted before finishing"))
+            ]
+        );
+    }
+}
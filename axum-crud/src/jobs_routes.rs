@@ -0,0 +1,79 @@
+use axum::{
+    extract::{Path, Query, State},
+    middleware,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::IntoParams;
+
+use crate::{
+    auth::mw_require_admin,
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::Result,
+    jobs_fns::{JobModelController, JobRunToSelect, JobToSelect},
+};
+
+/// Runs listed when no `limit` is given.
+const DEFAULT_RUNS: i64 = 20;
+
+pub fn routes_jobs(mm: ModelController) -> Router {
+    Router::new()
+        .route("/api/v1/admin/jobs", get(api_v1_list_jobs))
+        .route("/api/v1/admin/jobs/:name/runs", get(api_v1_list_job_runs))
+        .route_layer(middleware::from_fn_with_state(mm.clone(), mw_require_admin))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct RunsQuery {
+    /// Runs to return, newest first, 20 by default.
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs",
+    tag = "jobs",
+    responses(
+        (status = 200, description = "Scheduled jobs with their last run and last failure", body = DataBody<Vec<JobToSelect>>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+    )
+)]
+async fn api_v1_list_jobs(
+    State(mm): State<ModelController>,
+) -> Result<Json<DataBody<Vec<JobToSelect>>>> {
+    debug!("{:<12} - api_v1_list_jobs", "ROUTE_HANDLER");
+
+    let jobs = JobModelController::select(mm).await?;
+    let body = Json(DataBody::new(jobs));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/jobs/{name}/runs",
+    tag = "jobs",
+    params(("name" = String, Path, description = "Job name"), RunsQuery),
+    responses(
+        (status = 200, description = "Run history, newest first", body = DataBody<Vec<JobRunToSelect>>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+        (status = 404, description = "No job with that name"),
+    )
+)]
+async fn api_v1_list_job_runs(
+    State(mm): State<ModelController>,
+    Path(name): Path<String>,
+    Query(query): Query<RunsQuery>,
+) -> Result<Json<DataBody<Vec<JobRunToSelect>>>> {
+    debug!("{:<12} - api_v1_list_job_runs", "ROUTE_HANDLER");
+
+    let limit = query.limit.map_or(DEFAULT_RUNS, i64::from);
+    let runs = JobModelController::runs(mm, &name, limit).await?;
+    let body = Json(DataBody::new(runs));
+    Ok(body)
+}
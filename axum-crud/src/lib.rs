@@ -0,0 +1,52 @@
+//! Library half of the service, shared by the `axum-crud` server and the
+//! `axum-crud-admin` CLI.
+
+pub mod admin_fns;
+pub mod api_keys_fns;
+pub mod api_keys_routes;
+pub mod auth;
+pub mod config;
+pub mod crud_fns;
+pub mod crud_routes;
+pub mod envs;
+pub mod error;
+pub mod food_cache;
+pub mod food_repo;
+pub mod graphql_fns;
+pub mod graphql_routes;
+pub mod grpc;
+pub mod health_routes;
+pub mod idempotency;
+pub mod image_store;
+pub mod images_fns;
+pub mod images_routes;
+pub mod jobs_fns;
+pub mod jobs_routes;
+pub mod lots_fns;
+pub mod lots_routes;
+pub mod metrics;
+pub mod openapi;
+pub mod orders_fns;
+pub mod orders_routes;
+pub mod pagination;
+pub mod prices_fns;
+pub mod prices_routes;
+pub mod recipes_fns;
+pub mod recipes_routes;
+pub mod reorder_fns;
+pub mod reorder_routes;
+pub mod replica;
+pub mod reservations_fns;
+pub mod reservations_routes;
+pub mod shutdown;
+pub mod stock_fns;
+pub mod stock_takes_fns;
+pub mod stock_takes_routes;
+pub mod store;
+pub mod stores_fns;
+pub mod stores_routes;
+pub mod timestamps;
+pub mod units;
+pub mod utils;
+pub mod webhooks_fns;
+pub mod webhooks_routes;
@@ -0,0 +1,425 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, PgConnection};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+    stock_fns::{put_stock, record_movement, take_stock, take_stock_upto},
+};
+
+/// Widest window `expiring` reports on, about ten years.
+const MAX_EXPIRING_WINDOW_SECS: i64 = 10 * 366 * 24 * 60 * 60;
+
+/// Lots are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct LotModelController;
+
+#[derive(Debug, Serialize)]
+pub struct LotToReceive {
+    pub food_id: i64,
+    pub store_id: Option<i64>,
+    pub lot_code: Option<String>,
+    pub quantity: i32,
+    /// `YYYY-MM-DD`, today when absent.
+    pub received_date: Option<String>,
+    /// `YYYY-MM-DD`
+    pub expiry_date: String,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct LotToSelect {
+    pub id: i64,
+    pub food_id: i64,
+    pub store_id: Option<i64>,
+    pub lot_code: Option<String>,
+    pub received_quantity: i32,
+    /// What is left of the lot.
+    pub quantity: i32,
+    pub received_date: String,
+    pub expiry_date: String,
+    pub lot_status: String,
+}
+
+/// A lot that expires within the reported window, or already has.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct ExpiringLot {
+    pub id: i64,
+    pub food_id: i64,
+    pub food_name: String,
+    pub store_id: Option<i64>,
+    pub lot_code: Option<String>,
+    pub quantity: i32,
+    pub expiry_date: String,
+    /// Negative once the lot has expired.
+    pub days_left: i32,
+}
+
+/// Quantity taken out of one lot.
+#[derive(Clone, Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct LotPicked {
+    pub lot_id: i64,
+    pub expiry_date: String,
+    pub quantity: i32,
+}
+
+/// An expired lot written off as waste.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct LotWrittenOff {
+    pub lot_id: i64,
+    pub food_id: i64,
+    pub store_id: Option<i64>,
+    /// Remaining quantity of the lot when written off.
+    pub quantity: i32,
+    /// Stock actually taken, less than `quantity` if the stock had drifted.
+    pub stocks_removed: i32,
+}
+
+const LOT_COLUMNS: &str = "id, food_id, store_id, lot_code, received_quantity, quantity, to_char(received_date, 'YYYY-MM-DD') as received_date, to_char(expiry_date, 'YYYY-MM-DD') as expiry_date, lot_status::text as lot_status";
+
+impl LotModelController {
+    /// Books a delivery: adds the lot and its quantity to the stock.
+    pub async fn receive(mm: ModelController, data: LotToReceive) -> Result<LotToSelect> {
+        debug!("{:<12} - receive_lot", "HANDLER");
+
+        if data.quantity <= 0 {
+            return Err(Error::InvalidLot(String::from("quantity must be positive")));
+        }
+        let expiry_date = parse_date("expiry_date", &data.expiry_date)?;
+        if let Some(received_date) = &data.received_date {
+            if parse_date("received_date", received_date)? > expiry_date {
+                return Err(Error::InvalidLot(String::from(
+                    "expiry_date is before received_date",
+                )));
+            }
+        }
+
+        let timer = metrics().query_timer("receive_lot");
+        timer.observe(receive(&mm, data).await)
+    }
+
+    /// Active lots of a food, first to expire first.
+    pub async fn select(
+        mm: ModelController,
+        food_id: i64,
+        store_id: Option<i64>,
+    ) -> Result<Vec<LotToSelect>> {
+        debug!("{:<12} - select_lots", "HANDLER");
+
+        let timer = metrics().query_timer("select_lots");
+        let query = format!("select {LOT_COLUMNS} from food_lots where food_id = $1 and store_id is not distinct from $2 and lot_status = 'active' order by expiry_date, received_date, id");
+
+        let res = sqlx::query_as::<_, LotToSelect>(&query)
+            .bind(food_id)
+            .bind(store_id)
+            .fetch_all(mm.read_db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - select_lots error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// Lots with stock left that expire within `within_secs`, including the
+    /// ones already expired and not yet written off.
+    pub async fn expiring(
+        mm: ModelController,
+        within_secs: i64,
+        store_id: Option<i64>,
+    ) -> Result<Vec<ExpiringLot>> {
+        debug!("{:<12} - expiring_lots", "HANDLER");
+
+        if !(0..=MAX_EXPIRING_WINDOW_SECS).contains(&within_secs) {
+            return Err(Error::InvalidDuration(format!(
+                "{within_secs}s is not a window between now and ten years out"
+            )));
+        }
+
+        let timer = metrics().query_timer("expiring_lots");
+        let query = "select l.id, l.food_id, f.food_name, l.store_id, l.lot_code, l.quantity, to_char(l.expiry_date, 'YYYY-MM-DD') as expiry_date, (l.expiry_date - current_date) as days_left from food_lots l join foods_table f on f.id = l.food_id where l.lot_status = 'active' and l.quantity > 0 and l.expiry_date <= (now() + make_interval(secs => $1))::date and ($2::bigint is null or l.store_id = $2) and f.food_status != 'removed' order by l.expiry_date, f.food_name, l.id";
+
+        let res = sqlx::query_as::<_, ExpiringLot>(query)
+            .bind(within_secs as f64)
+            .bind(store_id)
+            .fetch_all(mm.read_db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - expiring_lots error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// Writes off every expired lot with quantity left, taking that
+    /// quantity out of the stock and booking it as waste.
+    pub async fn write_off_expired(
+        mm: ModelController,
+        store_id: Option<i64>,
+    ) -> Result<Vec<LotWrittenOff>> {
+        debug!("{:<12} - write_off_expired", "HANDLER");
+
+        let timer = metrics().query_timer("write_off_expired");
+        timer.observe(write_off_expired(&mm, store_id).await)
+    }
+}
+
+async fn receive(mm: &ModelController, data: LotToReceive) -> Result<LotToSelect> {
+    let LotToReceive {
+        food_id,
+        store_id,
+        lot_code,
+        quantity,
+        received_date,
+        expiry_date,
+    } = data;
+
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::CreateFailed(err.to_string()))?;
+
+    let query = format!("insert into food_lots (food_id, store_id, lot_code, received_quantity, quantity, received_date, expiry_date) values ($1, $2, $3, $4, $4, coalesce($5::date, current_date), $6::date) returning {LOT_COLUMNS}");
+    let lot = match sqlx::query_as::<_, LotToSelect>(&query)
+        .bind(food_id)
+        .bind(store_id)
+        .bind(lot_code)
+        .bind(quantity)
+        .bind(received_date)
+        .bind(expiry_date)
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(lot) => lot,
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            return Err(match err.constraint() {
+                Some("food_lots_store_id_fkey") => {
+                    Error::StoreNotFound(store_id.unwrap_or_default().to_string())
+                }
+                _ => Error::FoodIdNotFound(format!("no food with id {food_id}")),
+            });
+        }
+        Err(err) => {
+            debug!("{:<12} - receive_lot error {err:?}", "ERROR_CONTROLLER");
+            return Err(Error::CreateFailed(err.to_string()));
+        }
+    };
+
+    put_stock(&mut tx, food_id, store_id, quantity).await?;
+    record_movement(
+        &mut tx,
+        food_id,
+        store_id,
+        quantity,
+        "receive",
+        Some(lot.id),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::CreateFailed(err.to_string()))?;
+
+    Ok(lot)
+}
+
+/// A `YYYY-MM-DD` date of a lot.
+fn parse_date(field: &str, date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidLot(format!("{field} {date} is not a YYYY-MM-DD date")))
+}
+
+async fn write_off_expired(
+    mm: &ModelController,
+    store_id: Option<i64>,
+) -> Result<Vec<LotWrittenOff>> {
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let query = "update food_lots l set lot_status = 'written off', quantity = 0, mtime = now() from (select id, quantity from food_lots where lot_status = 'active' and quantity > 0 and expiry_date < current_date and ($1::bigint is null or store_id = $1) for update) old where l.id = old.id returning l.id, l.food_id, l.store_id, old.quantity";
+    let expired = match sqlx::query_as::<_, (i64, i64, Option<i64>, i32)>(query)
+        .bind(store_id)
+        .fetch_all(&mut *tx)
+        .await
+    {
+        Ok(expired) => expired,
+        Err(err) => {
+            debug!("{:<12} - write_off_expired error", "ERROR_CONTROLLER");
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+    };
+
+    let mut written_off = Vec::with_capacity(expired.len());
+    for (lot_id, food_id, store_id, quantity) in expired {
+        let stocks_removed = take_stock_upto(&mut tx, food_id, store_id, quantity).await?;
+        record_movement(
+            &mut tx,
+            food_id,
+            store_id,
+            -stocks_removed,
+            "waste",
+            Some(lot_id),
+        )
+        .await?;
+
+        written_off.push(LotWrittenOff {
+            lot_id,
+            food_id,
+            store_id,
+            quantity,
+            stocks_removed,
+        });
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(written_off)
+}
+
+/// Consumes up to `quantity` from the food's unexpired lots, first to
+/// expire first (FEFO). Stock received without a lot is not tracked here,
+/// so less than `quantity` may be picked.
+async fn pick_fefo(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    quantity: i32,
+) -> Result<Vec<LotPicked>> {
+    let query = "select id, quantity, to_char(expiry_date, 'YYYY-MM-DD') from food_lots where food_id = $1 and store_id is not distinct from $2 and lot_status = 'active' and quantity > 0 and expiry_date >= current_date order by expiry_date, received_date, id for update";
+    let lots = match sqlx::query_as::<_, (i64, i32, String)>(query)
+        .bind(food_id)
+        .bind(store_id)
+        .fetch_all(&mut *conn)
+        .await
+    {
+        Ok(lots) => lots,
+        Err(err) => {
+            debug!("{:<12} - pick_fefo error", "ERROR_CONTROLLER");
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+    };
+
+    let mut picked = Vec::new();
+    let mut left = quantity;
+    for (lot_id, lot_quantity, expiry_date) in lots {
+        if left == 0 {
+            break;
+        }
+        let take = left.min(lot_quantity);
+
+        let query = "update food_lots set quantity = quantity - $2, lot_status = case when quantity = $2 then 'depleted'::lot_stat else lot_status end, mtime = now() where id = $1";
+        if let Err(err) = sqlx::query(query)
+            .bind(lot_id)
+            .bind(take)
+            .execute(&mut *conn)
+            .await
+        {
+            debug!("{:<12} - pick_fefo update error", "ERROR_CONTROLLER");
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+
+        picked.push(LotPicked {
+            lot_id,
+            expiry_date,
+            quantity: take,
+        });
+        left -= take;
+    }
+
+    Ok(picked)
+}
+
+/// Takes `quantity` out of the stock, first-expiring lots first, and books
+/// it in the ledger under `reason`. Returns the stock left and the lots
+/// picked.
+pub(crate) async fn take_fefo(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    quantity: i32,
+    reason: &str,
+) -> Result<(i32, Vec<LotPicked>)> {
+    let stocks = take_stock(conn, food_id, store_id, quantity).await?;
+    let lots = pick_fefo(conn, food_id, store_id, quantity).await?;
+
+    let from_lots: i32 = lots.iter().map(|lot| lot.quantity).sum();
+    for lot in &lots {
+        record_movement(
+            conn,
+            food_id,
+            store_id,
+            -lot.quantity,
+            reason,
+            Some(lot.lot_id),
+        )
+        .await?;
+    }
+    if from_lots < quantity {
+        record_movement(conn, food_id, store_id, from_lots - quantity, reason, None).await?;
+    }
+
+    Ok((stocks, lots))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::crud_fns::{FoodModelController, FoodToCreate};
+
+    fn lot(food_id: i64, quantity: i32, expiry_date: &str) -> LotToReceive {
+        LotToReceive {
+            food_id,
+            store_id: None,
+            lot_code: None,
+            quantity,
+            received_date: None,
+            expiry_date: expiry_date.to_string(),
+        }
+    }
+
+    fn status<T>(res: Result<T>) -> StatusCode {
+        res.err().map(|err| err.status()).unwrap_or(StatusCode::OK)
+    }
+
+    #[sqlx::test]
+    async fn invalid_lots_are_client_errors(db: PgPool) {
+        let mm = ModelController::with_db(db);
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Milk", 0), None)
+                .await
+                .unwrap();
+        let receive = |data| LotModelController::receive(mm.clone(), data);
+
+        let res = receive(lot(food_id, 0, "2030-01-01")).await;
+        assert_eq!(status(res), StatusCode::BAD_REQUEST);
+        let res = receive(lot(food_id, 5, "2030-02-30")).await;
+        assert_eq!(status(res), StatusCode::BAD_REQUEST);
+        let mut early = lot(food_id, 5, "2030-01-01");
+        early.received_date = Some(String::from("2030-01-02"));
+        assert_eq!(status(receive(early).await), StatusCode::BAD_REQUEST);
+
+        let res = receive(lot(food_id + 1000, 5, "2030-01-01")).await;
+        assert_eq!(status(res), StatusCode::NOT_FOUND);
+        let mut elsewhere = lot(food_id, 5, "2030-01-01");
+        elsewhere.store_id = Some(1000);
+        assert_eq!(status(receive(elsewhere).await), StatusCode::NOT_FOUND);
+
+        let res = LotModelController::expiring(mm.clone(), i64::MAX / 2, None).await;
+        assert_eq!(status(res), StatusCode::BAD_REQUEST);
+
+        let res = receive(lot(food_id, 5, "2030-01-01")).await;
+        assert_eq!(status(res), StatusCode::OK);
+    }
+}
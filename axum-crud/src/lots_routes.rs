@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    crud_fns::{FoodModelController, ModelController},
+    crud_routes::DataBody,
+    error::{Error, Result},
+    lots_fns::{ExpiringLot, LotModelController, LotToReceive, LotToSelect, LotWrittenOff},
+    units::Unit,
+};
+
+pub fn routes_lots(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/foods/:id/lots",
+            get(api_v1_list_lots).post(api_v1_receive_lot),
+        )
+        .route("/api/v1/reports/expiring", get(api_v1_expiring_report))
+        // Short form asked for by the kitchen dashboards.
+        .route("/api/expiring", get(api_v1_expiring_report))
+        .route(
+            "/api/v1/lots/write-off-expired",
+            post(api_v1_write_off_expired),
+        )
+        .with_state(mm)
+}
+
+/// `?store_id=` limiting lots to one store.
+#[derive(Debug, Deserialize, IntoParams)]
+struct LotScope {
+    /// Lots held by this store instead of the food's own lots.
+    store_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct ExpiringQuery {
+    /// Window such as `3d`, `12h` or `2w`, a bare number counts days.
+    #[param(example = "3d")]
+    within: Option<String>,
+    /// Only lots held by this store.
+    store_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct ReceiveLotPayload {
+    store_id: Option<i64>,
+    lot_code: Option<String>,
+    quantity: i32,
+    /// Unit `quantity` is given in, the food's base unit when absent.
+    quantity_unit: Option<Unit>,
+    /// `YYYY-MM-DD`, today when absent.
+    received_date: Option<String>,
+    /// `YYYY-MM-DD`
+    expiry_date: String,
+}
+
+/// Parses a `within` window into seconds.
+fn parse_within(within: &str) -> Result<i64> {
+    let within = within.trim();
+    let (count, unit_secs) = match within.char_indices().last() {
+        Some((i, 'm')) => (&within[..i], 60),
+        Some((i, 'h')) => (&within[..i], 60 * 60),
+        Some((i, 'd')) => (&within[..i], 24 * 60 * 60),
+        Some((i, 'w')) => (&within[..i], 7 * 24 * 60 * 60),
+        _ => (within, 24 * 60 * 60),
+    };
+
+    match count.parse::<i64>() {
+        Ok(count) if count >= 0 => count
+            .checked_mul(unit_secs)
+            .ok_or(Error::InvalidDuration(within.to_string())),
+        _ => Err(Error::InvalidDuration(within.to_string())),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/lots",
+    tag = "lots",
+    params(("id" = i64, Path, description = "Food id"), LotScope),
+    responses(
+        (status = 200, description = "Active lots, first to expire first", body = DataBody<Vec<LotToSelect>>),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_v1_list_lots(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    Query(scope): Query<LotScope>,
+) -> Result<Json<DataBody<Vec<LotToSelect>>>> {
+    debug!("{:<12} - api_v1_list_lots", "ROUTE_HANDLER");
+
+    let lots = LotModelController::select(mm, food_id, scope.store_id).await?;
+    let body = Json(DataBody::new(lots));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/foods/{id}/lots",
+    tag = "lots",
+    params(("id" = i64, Path, description = "Food id")),
+    request_body = ReceiveLotPayload,
+    responses(
+        (status = 201, description = "Lot received and added to the stock", body = DataBody<LotToSelect>,
+            headers(("location" = String, description = "Lots of the food"))),
+        (status = 400, description = "Quantity not positive or invalid dates"),
+        (status = 404, description = "Food or store not found"),
+        (status = 500, description = "Create failed"),
+    )
+)]
+async fn api_v1_receive_lot(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    Json(body): Json<ReceiveLotPayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_receive_lot", "ROUTE_HANDLER");
+
+    let ReceiveLotPayload {
+        store_id,
+        lot_code,
+        quantity,
+        quantity_unit,
+        received_date,
+        expiry_date,
+    } = body;
+    let (quantity, _) =
+        FoodModelController::to_base_quantity(&mm, food_id, quantity, quantity_unit).await?;
+    let data = LotToReceive {
+        food_id,
+        store_id,
+        lot_code,
+        quantity,
+        received_date,
+        expiry_date,
+    };
+
+    let lot = LotModelController::receive(mm, data).await?;
+    let location = format!("/api/v1/foods/{food_id}/lots");
+    let body = Json(DataBody::new(lot));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/expiring",
+    tag = "lots",
+    params(ExpiringQuery),
+    responses(
+        (status = 200, description = "Lots expiring within the window, expired ones included", body = DataBody<Vec<ExpiringLot>>),
+        (status = 400, description = "Invalid window"),
+        (status = 500, description = "Report failed"),
+    )
+)]
+async fn api_v1_expiring_report(
+    State(mm): State<ModelController>,
+    Query(query): Query<ExpiringQuery>,
+) -> Result<Json<DataBody<Vec<ExpiringLot>>>> {
+    debug!("{:<12} - api_v1_expiring_report", "ROUTE_HANDLER");
+
+    let within_secs = parse_within(query.within.as_deref().unwrap_or("3d"))?;
+
+    let lots = LotModelController::expiring(mm, within_secs, query.store_id).await?;
+    let body = Json(DataBody::new(lots));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/lots/write-off-expired",
+    tag = "lots",
+    params(LotScope),
+    responses(
+        (status = 200, description = "Expired lots written off as waste", body = DataBody<Vec<LotWrittenOff>>),
+        (status = 500, description = "Write-off failed"),
+    )
+)]
+async fn api_v1_write_off_expired(
+    State(mm): State<ModelController>,
+    Query(scope): Query<LotScope>,
+) -> Result<Json<DataBody<Vec<LotWrittenOff>>>> {
+    debug!("{:<12} - api_v1_write_off_expired", "ROUTE_HANDLER");
+
+    let written_off = LotModelController::write_off_expired(mm, scope.store_id).await?;
+    let body = Json(DataBody::new(written_off));
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_within() {
+        assert_eq!(parse_within("3d").unwrap(), 3 * 24 * 60 * 60);
+        assert_eq!(parse_within("12h").unwrap(), 12 * 60 * 60);
+        assert_eq!(parse_within("2w").unwrap(), 14 * 24 * 60 * 60);
+        assert_eq!(parse_within("5").unwrap(), 5 * 24 * 60 * 60);
+        assert!(parse_within("d").is_err());
+        assert!(parse_within("-1d").is_err());
+        assert!(parse_within("3y").is_err());
+    }
+}
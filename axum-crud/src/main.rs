@@ -1,20 +1,27 @@
-use axum::{routing::get, serve, Json, Router};
-use config::core_config;
-use crud_fns::ModelController;
-use error::Result;
+use std::{future::IntoFuture, time::Duration};
+
+use axum::{middleware, routing::get, serve, Json, Router};
+use axum_crud::{
+    api_keys_routes, auth,
+    config::core_config,
+    crud_fns::ModelController,
+    crud_routes,
+    error::Result,
+    food_cache, graphql_routes, grpc, health_routes, images_routes, jobs_fns, jobs_routes,
+    lots_routes, metrics, openapi, orders_routes, prices_routes, recipes_routes, reorder_routes,
+    replica, reservations_routes,
+    shutdown::{drain_deadline, flush_logs, shutdown_signal},
+    stock_takes_routes, stores_routes, webhooks_fns, webhooks_routes,
+};
 use serde_json::{json, Value};
-use tokio::net::TcpListener;
-use tracing::info;
+use tokio::{
+    net::TcpListener,
+    sync::{oneshot, watch},
+    time::{timeout_at, Instant},
+};
+use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-mod config;
-mod crud_fns;
-mod crud_routes;
-mod envs;
-mod error;
-mod store;
-mod utils;
-
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -24,10 +31,43 @@ async fn main() -> Result<()> {
         .init();
 
     let mm = ModelController::new().await?;
+    let scheduler = jobs_fns::spawn_scheduler(mm.clone());
+    food_cache::spawn_listener(mm.clone());
+    replica::spawn_health_check(mm.clone());
+    metrics::spawn_refresh(mm.clone());
+    let dispatcher = webhooks_fns::spawn_dispatcher(mm.clone());
+
+    // -- Every API route group needs a key, the health, metrics and docs
+    //    routes and the image files stay open.
+    let routes_api = Router::new()
+        .merge(crud_routes::routes_crud(mm.clone()))
+        .merge(stores_routes::routes_stores(mm.clone()))
+        .merge(lots_routes::routes_lots(mm.clone()))
+        .merge(orders_routes::routes_orders(mm.clone()))
+        .merge(recipes_routes::routes_recipes(mm.clone()))
+        .merge(reservations_routes::routes_reservations(mm.clone()))
+        .merge(images_routes::routes_images(mm.clone()))
+        .merge(stock_takes_routes::routes_stock_takes(mm.clone()))
+        .merge(reorder_routes::routes_reorder(mm.clone()))
+        .merge(prices_routes::routes_prices(mm.clone()))
+        .route_layer(middleware::from_fn_with_state(
+            mm.clone(),
+            auth::mw_require_api_key,
+        ));
 
     let app = Router::new()
         .route("/", get(greet))
-        .merge(crud_routes::routes_crud(mm.clone()));
+        .merge(health_routes::routes_health(mm.clone()))
+        .merge(metrics::routes_metrics(mm.clone()))
+        .merge(openapi::routes_openapi())
+        .merge(images_routes::routes_image_files(mm.clone()))
+        .merge(routes_api)
+        .merge(api_keys_routes::routes_api_keys(mm.clone()))
+        .merge(jobs_routes::routes_jobs(mm.clone()))
+        .merge(webhooks_routes::routes_webhooks(mm.clone()))
+        .merge(graphql_routes::routes_graphql(mm.clone()))
+        .layer(middleware::from_fn(replica::mw_route_reads))
+        .layer(middleware::from_fn(metrics::mw_track_metrics));
 
     let app_addr = format!(
         "{}:{}",
@@ -37,7 +77,60 @@ async fn main() -> Result<()> {
     let tcp_listener = TcpListener::bind(app_addr.clone()).await.unwrap();
 
     info!("{:<12} - Server is live!", format!("http://{}", app_addr));
-    serve(tcp_listener, app).await.unwrap();
+
+    // -- The gRPC server stops along with the HTTP one.
+    let grpc_addr = format!("{}:{}", &core_config().SERVER_URL, &core_config().GRPC_PORT);
+    let grpc_listener = TcpListener::bind(grpc_addr).await.unwrap();
+    let (grpc_stop_tx, grpc_stop_rx) = oneshot::channel::<()>();
+    let grpc_server = grpc::serve(mm.clone(), grpc_listener, async move {
+        let _ = grpc_stop_rx.await;
+    });
+    let grpc = tokio::spawn(async move {
+        if let Err(err) = grpc_server.await {
+            error!("{:<12} - gRPC server failed: {err}", "GRPC");
+        }
+    });
+
+    // -- Stop accepting on SIGINT/SIGTERM, then let in-flight requests drain
+    //    until the deadline. Every step of the shutdown shares that one
+    //    deadline.
+    let drain_timeout = Duration::from_secs(core_config().SHUTDOWN_TIMEOUT_SEC);
+    let (deadline_tx, deadline_rx) = watch::channel(None);
+    let server = serve(tcp_listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        let _ = grpc_stop_tx.send(());
+        deadline_tx.send_replace(Some(Instant::now() + drain_timeout));
+    });
+
+    tokio::select! {
+        res = server.into_future() => res.unwrap(),
+        _ = drain_deadline(deadline_rx.clone()) => {
+            warn!("{:<12} - dropping remaining connections", "SHUTDOWN");
+        }
+    }
+    let deadline = deadline_rx
+        .borrow()
+        .unwrap_or_else(|| Instant::now() + drain_timeout);
+
+    // -- Streams still open get what is left of the drain window.
+    if timeout_at(deadline, grpc).await.is_err() {
+        warn!("{:<12} - dropping remaining gRPC streams", "SHUTDOWN");
+    }
+    // -- A job already running gets to finish and record its run.
+    if timeout_at(deadline, scheduler.shutdown()).await.is_err() {
+        warn!("{:<12} - abandoning running job", "SHUTDOWN");
+    }
+    // -- Attempts in flight are recorded, or retried once their lease ends.
+    if timeout_at(deadline, dispatcher.shutdown()).await.is_err() {
+        warn!("{:<12} - abandoning webhook attempts", "SHUTDOWN");
+    }
+    // -- Connections still held by dropped requests or abandoned work are
+    //    not waited for past the deadline.
+    if timeout_at(deadline, mm.close()).await.is_err() {
+        warn!("{:<12} - closing pools with connections in use", "SHUTDOWN");
+    }
+    info!("{:<12} - Server stopped", "SHUTDOWN");
+    flush_logs();
 
     Ok(())
 }
@@ -0,0 +1,272 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+
+use crate::{
+    config::core_config,
+    crud_fns::{FoodModelController, ModelController},
+    replica::{with_reads, Reads},
+};
+
+pub fn metrics() -> &'static Metrics {
+    static INSTANCE: OnceLock<Metrics> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        Metrics::register()
+            .unwrap_or_else(|ex| panic!("FATAL - while registering metrics - error: {ex:?}"))
+    })
+}
+
+pub struct Metrics {
+    registry: Registry,
+
+    http_requests_total: IntCounterVec,
+    http_request_duration_seconds: HistogramVec,
+
+    db_query_duration_seconds: HistogramVec,
+    db_queries_in_flight: IntGauge,
+    db_pool: IntGaugeVec,
+    db_replica_healthy: IntGauge,
+
+    food_cache_lookups_total: IntCounterVec,
+
+    webhook_attempts_total: IntCounterVec,
+
+    foods: IntGaugeVec,
+}
+
+impl Metrics {
+    fn register() -> prometheus::Result<Metrics> {
+        let registry = Registry::new();
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "Total HTTP requests handled"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route", "status"],
+        )?;
+        let db_query_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "db_query_duration_seconds",
+                "Database query latency in seconds",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+            ]),
+            &["query", "outcome"],
+        )?;
+        let db_queries_in_flight =
+            IntGauge::new("db_queries_in_flight", "Database queries currently running")?;
+        let db_pool = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "sqlx pool connections by state"),
+            &["state"],
+        )?;
+        let db_replica_healthy =
+            IntGauge::new("db_replica_healthy", "Whether reads go to the read replica")?;
+        let food_cache_lookups_total = IntCounterVec::new(
+            Opts::new(
+                "food_cache_lookups_total",
+                "Single-food lookups answered from the food cache or not",
+            ),
+            &["lookup", "result"],
+        )?;
+        let webhook_attempts_total = IntCounterVec::new(
+            Opts::new(
+                "webhook_attempts_total",
+                "Webhook delivery attempts by outcome",
+            ),
+            &["outcome"],
+        )?;
+        let foods = IntGaugeVec::new(
+            Opts::new("inventory_foods", "Foods in the inventory by stock state"),
+            &["state"],
+        )?;
+
+        registry.register(Box::new(http_requests_total.clone()))?;
+        registry.register(Box::new(http_request_duration_seconds.clone()))?;
+        registry.register(Box::new(db_query_duration_seconds.clone()))?;
+        registry.register(Box::new(db_queries_in_flight.clone()))?;
+        registry.register(Box::new(db_pool.clone()))?;
+        registry.register(Box::new(db_replica_healthy.clone()))?;
+        registry.register(Box::new(food_cache_lookups_total.clone()))?;
+        registry.register(Box::new(webhook_attempts_total.clone()))?;
+        registry.register(Box::new(foods.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_query_duration_seconds,
+            db_queries_in_flight,
+            db_pool,
+            db_replica_healthy,
+            food_cache_lookups_total,
+            webhook_attempts_total,
+            foods,
+        })
+    }
+
+    /// Starts timing a `FoodModelController` query. The timing is recorded
+    /// when the timer drops, as `error` unless `QueryTimer::observe` saw `Ok`.
+    pub fn query_timer(&self, query: &'static str) -> QueryTimer {
+        self.db_queries_in_flight.inc();
+
+        QueryTimer {
+            query,
+            start: Instant::now(),
+            outcome: "error",
+        }
+    }
+
+    pub fn set_replica_healthy(&self, healthy: bool) {
+        self.db_replica_healthy.set(healthy.into());
+    }
+
+    /// Counts a `food_cache` lookup, `lookup` being `id` or `stamp_code`.
+    pub fn food_cache_lookup(&self, lookup: &'static str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.food_cache_lookups_total
+            .with_label_values(&[lookup, result])
+            .inc();
+    }
+
+    /// Counts a webhook attempt, `outcome` being `delivered`, `retry` or
+    /// `dead`.
+    pub fn webhook_attempt(&self, outcome: &'static str) {
+        self.webhook_attempts_total
+            .with_label_values(&[outcome])
+            .inc();
+    }
+}
+
+pub struct QueryTimer {
+    query: &'static str,
+    start: Instant,
+    outcome: &'static str,
+}
+
+impl QueryTimer {
+    /// Records the outcome from the query result and hands it back.
+    pub fn observe<T, E>(mut self, res: core::result::Result<T, E>) -> core::result::Result<T, E> {
+        self.outcome = if res.is_ok() { "ok" } else { "error" };
+        res
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let m = metrics();
+
+        m.db_queries_in_flight.dec();
+        m.db_query_duration_seconds
+            .with_label_values(&[self.query, self.outcome])
+            .observe(self.start.elapsed().as_secs_f64());
+    }
+}
+
+pub fn routes_metrics(mm: ModelController) -> Router {
+    Router::new()
+        .route("/metrics", get(api_metrics))
+        .with_state(mm)
+}
+
+/// Records count and latency for every request, labelled by the matched
+/// route template (not the raw path) to keep label cardinality bounded.
+pub async fn mw_track_metrics(req: Request<Body>, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let res = next.run(req).await;
+
+    let status = res.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    let m = metrics();
+    m.http_requests_total.with_label_values(&labels).inc();
+    m.http_request_duration_seconds
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+
+    res
+}
+
+/// Counts the foods for the business gauges every
+/// `METRICS_REFRESH_INTERVAL_MS`, on the replica when it is healthy, so a
+/// scrape never queries the database. A failed count keeps the previous
+/// values.
+pub fn spawn_refresh(mm: ModelController) {
+    tokio::spawn(async move {
+        let every = Duration::from_millis(core_config().METRICS_REFRESH_INTERVAL_MS);
+
+        loop {
+            let stats = with_reads(
+                Reads::Replica,
+                FoodModelController::inventory_stats(mm.clone(), None),
+            )
+            .await;
+            match stats {
+                Ok(stats) => {
+                    let m = metrics();
+                    m.foods.with_label_values(&["total"]).set(stats.total);
+                    m.foods
+                        .with_label_values(&["out_of_stock"])
+                        .set(stats.out_of_stock);
+                }
+                Err(err) => warn!("{:<12} - refresh failed {err:?}", "METRICS"),
+            }
+
+            sleep(every).await;
+        }
+    });
+}
+
+async fn api_metrics(State(mm): State<ModelController>) -> Response {
+    debug!("{:<12} - api_metrics", "ROUTE_HANDLER");
+
+    let m = metrics();
+
+    // -- Pool gauges. sqlx does not expose its wait queue, so waiters are
+    //    the queries in flight that do not hold a connection.
+    if let Ok(db) = mm.db() {
+        let size = db.size() as i64;
+        let idle = db.num_idle() as i64;
+        let waiters = (m.db_queries_in_flight.get() - (size - idle)).max(0);
+        m.db_pool.with_label_values(&["size"]).set(size);
+        m.db_pool.with_label_values(&["idle"]).set(idle);
+        m.db_pool.with_label_values(&["waiters"]).set(waiters);
+    }
+
+    let mut buffer = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&m.registry.gather(), &mut buffer) {
+        debug!("{:<12} - metrics encode error {err:?}", "ERROR_CONTROLLER");
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], buffer).into_response()
+}
@@ -0,0 +1,154 @@
+use axum::{routing::get, Json, Router};
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Deprecated,
+    },
+    Modify, OpenApi,
+};
+use utoipa_redoc::{Redoc, Servable};
+
+use crate::{
+    api_keys_routes, crud_routes, graphql_routes, images_routes, jobs_routes, lots_routes,
+    orders_routes, prices_routes, recipes_routes, reorder_routes, reservations_routes,
+    stock_takes_routes, stores_routes, webhooks_routes,
+};
+
+/// OpenAPI 3 document, generated from the `#[utoipa::path]` annotations on
+/// the handlers and the `ToSchema` derives on their payloads.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "axum-crud", description = "Food inventory API"),
+    paths(
+        crud_routes::api_v1_list_foods,
+        crud_routes::api_v1_get_food,
+        crud_routes::api_v1_get_food_by_stamp_code,
+        crud_routes::api_v1_create_food,
+        crud_routes::api_v1_patch_food,
+        crud_routes::api_v1_put_food,
+        crud_routes::api_v1_delete_food,
+        crud_routes::api_v1_inventory_report,
+        stores_routes::api_v1_list_stores,
+        stores_routes::api_v1_create_store,
+        stores_routes::api_v1_get_store,
+        stores_routes::api_v1_patch_store,
+        stores_routes::api_v1_food_stocks,
+        stores_routes::api_v1_transfer_stock,
+        lots_routes::api_v1_list_lots,
+        lots_routes::api_v1_receive_lot,
+        lots_routes::api_v1_expiring_report,
+        lots_routes::api_v1_write_off_expired,
+        orders_routes::api_v1_place_order,
+        recipes_routes::api_v1_get_recipe,
+        recipes_routes::api_v1_put_recipe,
+        recipes_routes::api_v1_recipe_capacity,
+        recipes_routes::api_v1_produce_food,
+        reservations_routes::api_v1_create_reservation,
+        reservations_routes::api_v1_get_reservation,
+        reservations_routes::api_v1_release_reservation,
+        reservations_routes::api_v1_confirm_reservation,
+        reservations_routes::api_v1_food_availability,
+        images_routes::api_v1_list_images,
+        images_routes::api_v1_upload_image,
+        images_routes::api_v1_delete_image,
+        images_routes::api_v1_get_image,
+        images_routes::api_v1_get_thumbnail,
+        stock_takes_routes::api_v1_open_stock_take,
+        stock_takes_routes::api_v1_get_stock_take,
+        stock_takes_routes::api_v1_submit_stock_take_counts,
+        stock_takes_routes::api_v1_approve_stock_take,
+        stock_takes_routes::api_v1_cancel_stock_take,
+        reorder_routes::api_v1_reorder_suggestions,
+        prices_routes::api_v1_schedule_price,
+        prices_routes::api_v1_list_scheduled_prices,
+        prices_routes::api_v1_cancel_scheduled_price,
+        graphql_routes::api_graphql,
+        api_keys_routes::api_v1_list_api_keys,
+        api_keys_routes::api_v1_issue_api_key,
+        api_keys_routes::api_v1_revoke_api_key,
+        jobs_routes::api_v1_list_jobs,
+        jobs_routes::api_v1_list_job_runs,
+        webhooks_routes::api_v1_list_webhooks,
+        webhooks_routes::api_v1_create_webhook,
+        webhooks_routes::api_v1_delete_webhook,
+        webhooks_routes::api_v1_list_webhook_deliveries,
+        webhooks_routes::api_v1_retry_webhook_delivery,
+        crud_routes::api_create_food,
+        crud_routes::api_select_food,
+        crud_routes::api_select_food_by_id,
+        crud_routes::api_select_food_by_stamp_code,
+        crud_routes::api_update_food,
+        crud_routes::api_delete_food,
+    ),
+    tags(
+        (name = "foods", description = "Food inventory"),
+        (name = "stores", description = "Kitchens and their per-food stock"),
+        (name = "lots", description = "Received batches and their expiry"),
+        (name = "orders", description = "Stock taken out by sales"),
+        (name = "recipes", description = "Composite foods made from other foods"),
+        (name = "reservations", description = "Stock held for orders awaiting payment"),
+        (name = "images", description = "Pictures of foods and their thumbnails"),
+        (name = "stock-takes", description = "Cycle counts and their variances"),
+        (name = "reorder", description = "What to buy, from past consumption"),
+        (name = "prices", description = "Price changes taking effect later"),
+        (name = "graphql", description = "Foods, categories and aggregates in one round trip"),
+        (name = "api-keys", description = "Keys for the API, admin scope only"),
+        (name = "jobs", description = "Background jobs and their runs, admin scope only"),
+        (name = "webhooks", description = "Food events pushed to subscribers, admin scope only"),
+    ),
+    security(("bearer" = []), ("api_key" = [])),
+    modifiers(&ApiKeySecurity, &LegacyDeprecation)
+)]
+pub struct ApiDoc;
+
+/// The two ways to send a key, see `auth`.
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+        );
+    }
+}
+
+/// Flags every operation outside `/api/v1` as deprecated.
+struct LegacyDeprecation;
+
+impl Modify for LegacyDeprecation {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if path.starts_with("/api/v1/") {
+                continue;
+            }
+            for op in [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                op.deprecated = Some(Deprecated::True);
+            }
+        }
+    }
+}
+
+pub fn routes_openapi() -> Router {
+    Router::new()
+        .route("/api/openapi.json", get(api_openapi_json))
+        .merge(Redoc::with_url("/api/docs", ApiDoc::openapi()))
+}
+
+async fn api_openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::{FoodModelController, ModelController},
+    error::{Error, Result},
+    lots_fns::{take_fefo, LotPicked},
+    metrics::metrics,
+    recipes_fns::{consume_ingredients, has_recipe, IngredientUsed},
+    stock_fns::{lock_stock, reserved_stock},
+    units::Unit,
+};
+
+/// Orders are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct OrderModelController;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OrderToPlace {
+    pub store_id: Option<i64>,
+    pub lines: Vec<OrderLine>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct OrderLine {
+    pub food_id: i64,
+    pub quantity: i32,
+    /// Unit `quantity` is given in, the food's base unit when absent.
+    pub quantity_unit: Option<Unit>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct OrderPlaced {
+    pub order_id: i64,
+    pub store_id: Option<i64>,
+    pub lines: Vec<OrderLinePlaced>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct OrderLinePlaced {
+    pub food_id: i64,
+    /// In the food's base unit.
+    pub quantity: i32,
+    pub unit: Unit,
+    /// Stock left once the order is placed.
+    pub stocks: i32,
+    /// Lots the quantity was taken from, first to expire first. Any
+    /// remainder came from stock received without a lot.
+    pub lots: Vec<LotPicked>,
+    /// Made to order from the recipe, once the food's own stock ran out.
+    pub made: i32,
+    pub ingredients: Vec<IngredientUsed>,
+}
+
+impl OrderModelController {
+    /// Takes every line out of the stock, all or nothing.
+    pub async fn place(mm: ModelController, data: OrderToPlace) -> Result<OrderPlaced> {
+        debug!("{:<12} - place_order", "HANDLER");
+
+        if data.lines.is_empty() {
+            return Err(Error::OrderFailed(String::from("order has no lines")));
+        }
+        if data.lines.iter().any(|line| line.quantity <= 0) {
+            return Err(Error::OrderFailed(String::from(
+                "quantity must be positive",
+            )));
+        }
+        let mut food_ids: Vec<i64> = data.lines.iter().map(|line| line.food_id).collect();
+        food_ids.sort_unstable();
+        food_ids.dedup();
+        if food_ids.len() != data.lines.len() {
+            return Err(Error::OrderFailed(String::from(
+                "a food appears on more than one line",
+            )));
+        }
+
+        let timer = metrics().query_timer("place_order");
+        timer.observe(place(&mm, data).await)
+    }
+}
+
+async fn place(mm: &ModelController, data: OrderToPlace) -> Result<OrderPlaced> {
+    let OrderToPlace { store_id, lines } = data;
+
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let order_id = insert_order(&mut tx, store_id).await?;
+
+    let mut placed = Vec::with_capacity(lines.len());
+    for OrderLine {
+        food_id,
+        quantity,
+        quantity_unit,
+    } in lines
+    {
+        let (quantity, unit) =
+            FoodModelController::to_base_quantity(mm, food_id, quantity, quantity_unit).await?;
+        placed.push(place_line(&mut tx, order_id, store_id, food_id, quantity, unit).await?);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(OrderPlaced {
+        order_id,
+        store_id,
+        lines: placed,
+    })
+}
+
+pub(crate) async fn insert_order(conn: &mut PgConnection, store_id: Option<i64>) -> Result<i64> {
+    let query = "insert into orders (store_id) values ($1) returning id";
+
+    sqlx::query_scalar::<_, i64>(query)
+        .bind(store_id)
+        .fetch_one(conn)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - place_order error {err:?}", "ERROR_CONTROLLER");
+            Error::CreateFailed(err.to_string())
+        })
+}
+
+/// Takes one line of an order out of the stock, `quantity` being in the
+/// food's base unit.
+pub(crate) async fn place_line(
+    conn: &mut PgConnection,
+    order_id: i64,
+    store_id: Option<i64>,
+    food_id: i64,
+    quantity: i32,
+    unit: Unit,
+) -> Result<OrderLinePlaced> {
+    // -- A composite food sells what is prepared first, the rest is made
+    //    from its ingredients.
+    let (stocks, lots, made, ingredients) = if has_recipe(conn, food_id).await? {
+        let stocks = lock_stock(conn, food_id, store_id).await?;
+        let on_hand = (stocks - reserved_stock(conn, food_id, store_id).await?).max(0);
+        let from_stock = on_hand.min(quantity);
+        let made = quantity - from_stock;
+
+        let (stocks, lots) = match from_stock {
+            0 => (stocks, Vec::new()),
+            _ => take_fefo(conn, food_id, store_id, from_stock, "order").await?,
+        };
+        let ingredients = match made {
+            0 => Vec::new(),
+            _ => consume_ingredients(conn, food_id, store_id, made, "order").await?,
+        };
+        (stocks, lots, made, ingredients)
+    } else {
+        let (stocks, lots) = take_fefo(conn, food_id, store_id, quantity, "order").await?;
+        (stocks, lots, 0, Vec::new())
+    };
+
+    let query = "insert into order_lines (order_id, food_id, quantity) values ($1, $2, $3)";
+    if let Err(err) = sqlx::query(query)
+        .bind(order_id)
+        .bind(food_id)
+        .bind(quantity)
+        .execute(&mut *conn)
+        .await
+    {
+        debug!("{:<12} - place_order line error", "ERROR_CONTROLLER");
+        return Err(Error::UpdateFailed(err.to_string()));
+    }
+
+    Ok(OrderLinePlaced {
+        food_id,
+        quantity,
+        unit,
+        stocks,
+        lots,
+        made,
+        ingredients,
+    })
+}
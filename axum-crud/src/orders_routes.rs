@@ -0,0 +1,60 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::Result,
+    idempotency::mw_idempotency,
+    orders_fns::{OrderLine, OrderModelController, OrderPlaced, OrderToPlace},
+};
+
+pub fn routes_orders(mm: ModelController) -> Router {
+    Router::new()
+        .route("/api/v1/orders", post(api_v1_place_order))
+        .route_layer(middleware::from_fn_with_state(mm.clone(), mw_idempotency))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct PlaceOrderPayload {
+    /// Take the stock from this store instead of the food's own stock.
+    store_id: Option<i64>,
+    lines: Vec<OrderLine>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/orders",
+    tag = "orders",
+    request_body = PlaceOrderPayload,
+    responses(
+        (status = 201, description = "Stock taken for every line, first-expiring lots first", body = DataBody<OrderPlaced>),
+        (status = 400, description = "No lines, quantity not positive or a food on two lines"),
+        (status = 409, description = "Insufficient stock"),
+        (status = 500, description = "Order failed"),
+    )
+)]
+async fn api_v1_place_order(
+    State(mm): State<ModelController>,
+    Json(body): Json<PlaceOrderPayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_place_order", "ROUTE_HANDLER");
+
+    let PlaceOrderPayload { store_id, lines } = body;
+    let data = OrderToPlace { store_id, lines };
+
+    let order = OrderModelController::place(mm, data).await?;
+    let body = Json(DataBody::new(order));
+
+    Ok((StatusCode::CREATED, body).into_response())
+}
@@ -0,0 +1,202 @@
+//! Cursor pagination for listings.
+//!
+//! Pages are keyed on a food's `cid`, a UUIDv7 and so ordered by creation
+//! time. The key is compared as raw bytes, since its base64 text does not
+//! sort in time order. A page ends with a `next_cursor` naming the last
+//! `cid` seen, and the next page holds the rows strictly before it, so
+//! rows inserted meanwhile never shift, skip or repeat a page.
+//!
+//! Cursors are opaque to clients and signed with `CURSOR_SECRET`, a
+//! tampered cursor is rejected instead of silently paging elsewhere. Without
+//! a secret a random one is made at startup, so cursors only hold within
+//! one process.
+
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::warn;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{
+    config::core_config,
+    error::{Error, Result},
+};
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+const CURSOR_VERSION: u8 = 1;
+const KEY_LEN: usize = 16;
+const MAC_LEN: usize = 16;
+
+/// `?limit=&cursor=` of a listing.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct PageParams {
+    /// Rows per page, 1 to 200, 50 by default. Without `limit` and
+    /// `cursor` the whole listing is returned at once.
+    pub limit: Option<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+/// A page to fetch, as passed to the repositories.
+#[derive(Debug, Default)]
+pub struct PageRequest {
+    /// Only rows whose key sorts before this `cid`.
+    pub after: Option<String>,
+    /// Rows to fetch, all when absent.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl PageParams {
+    pub fn is_paged(&self) -> bool {
+        self.limit.is_some() || self.cursor.is_some()
+    }
+
+    /// The rows to fetch for this page: one more than its size, to tell
+    /// whether another page follows.
+    pub fn request(&self) -> Result<PageRequest> {
+        if !self.is_paged() {
+            return Ok(PageRequest::default());
+        }
+
+        let size = self.size()?;
+        let after = self.cursor.as_deref().map(decode_cursor).transpose()?;
+
+        Ok(PageRequest {
+            after,
+            limit: Some(size as i64 + 1),
+        })
+    }
+
+    /// Cuts the rows fetched by `request` down to the page and signs the
+    /// key of its last row.
+    pub fn page<T>(&self, mut items: Vec<T>, key: impl Fn(&T) -> &str) -> Result<Page<T>> {
+        if !self.is_paged() {
+            return Ok(Page {
+                items,
+                next_cursor: None,
+            });
+        }
+
+        let size = self.size()? as usize;
+        let next_cursor = if items.len() > size {
+            items.truncate(size);
+            items
+                .last()
+                .map(|item| encode_cursor(key(item)))
+                .transpose()?
+        } else {
+            None
+        };
+
+        Ok(Page { items, next_cursor })
+    }
+
+    fn size(&self) -> Result<u32> {
+        match self.limit.unwrap_or(DEFAULT_PAGE_SIZE) {
+            size @ 1..=MAX_PAGE_SIZE => Ok(size),
+            size => Err(Error::InvalidCursor(format!(
+                "limit {size} is not between 1 and {MAX_PAGE_SIZE}"
+            ))),
+        }
+    }
+}
+
+/// Raw bytes of a `cid`, which sort in creation order.
+pub fn cid_key(cid: &str) -> Result<Vec<u8>> {
+    data_encoding::BASE64_NOPAD
+        .decode(cid.as_bytes())
+        .ok()
+        .filter(|key| key.len() == KEY_LEN)
+        .ok_or_else(|| Error::InvalidCursor(format!("{cid} is not a cid")))
+}
+
+fn mac() -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(cursor_secret()).expect("HMAC takes keys of any length")
+}
+
+fn cursor_secret() -> &'static [u8] {
+    static INSTANCE: OnceLock<Vec<u8>> = OnceLock::new();
+
+    INSTANCE.get_or_init(|| {
+        let secret = &core_config().CURSOR_SECRET;
+        if !secret.is_empty() {
+            return secret.as_bytes().to_vec();
+        }
+
+        warn!(
+            "{:<12} - CURSOR_SECRET is empty, cursors hold until restart",
+            "PAGINATION"
+        );
+        [Uuid::new_v4(), Uuid::new_v4()]
+            .iter()
+            .flat_map(|uuid| uuid.into_bytes())
+            .collect()
+    })
+}
+
+/// Cursor of the page that follows the row keyed `cid`.
+pub(crate) fn encode_cursor(cid: &str) -> Result<String> {
+    let mut token = vec![CURSOR_VERSION];
+    token.extend(cid_key(cid)?);
+
+    let mut mac = mac();
+    mac.update(&token);
+    token.extend(&mac.finalize().into_bytes()[..MAC_LEN]);
+
+    Ok(data_encoding::BASE64URL_NOPAD.encode(&token))
+}
+
+fn decode_cursor(cursor: &str) -> Result<String> {
+    let invalid = || Error::InvalidCursor(String::from("malformed or tampered cursor"));
+
+    let token = data_encoding::BASE64URL_NOPAD
+        .decode(cursor.as_bytes())
+        .map_err(|_| invalid())?;
+    if token.len() != 1 + KEY_LEN + MAC_LEN || token[0] != CURSOR_VERSION {
+        return Err(invalid());
+    }
+
+    let (payload, tag) = token.split_at(1 + KEY_LEN);
+    let mut mac = mac();
+    mac.update(payload);
+    mac.verify_truncated_left(tag).map_err(|_| invalid())?;
+
+    Ok(data_encoding::BASE64_NOPAD.encode(&payload[1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::b64u;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cid = b64u().unwrap();
+        let cursor = encode_cursor(&cid).unwrap();
+        assert_eq!(decode_cursor(&cursor).unwrap(), cid);
+
+        let mut tampered = cursor.into_bytes();
+        tampered[4] = if tampered[4] == b'A' { b'B' } else { b'A' };
+        assert!(decode_cursor(&String::from_utf8(tampered).unwrap()).is_err());
+        assert!(decode_cursor("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_cid_keys_sort_in_creation_order() {
+        let older = b64u().unwrap();
+        let newer = b64u().unwrap();
+
+        assert!(cid_key(&older).unwrap() < cid_key(&newer).unwrap());
+    }
+}
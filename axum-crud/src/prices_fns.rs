@@ -0,0 +1,147 @@
+//! Prices set ahead of time. A scheduled price replaces the food's price
+//! once its `effective_at` has passed, on the next run of the
+//! `apply_scheduled_prices` job.
+
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+};
+
+/// Scheduled prices are kept in Postgres only, the in-memory backend has
+/// none.
+#[derive(Clone, Debug)]
+pub struct PriceModelController;
+
+#[derive(Debug, Serialize)]
+pub struct PriceToSchedule {
+    pub food_id: i64,
+    pub price: f64,
+    pub effective_at: OffsetDateTime,
+    pub created_by: Option<String>,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct ScheduledPriceToSelect {
+    pub id: i64,
+    pub food_id: i64,
+    pub price: f64,
+    /// RFC 3339, UTC.
+    pub effective_at: String,
+    /// When the job applied it, absent while pending.
+    pub applied_at: Option<String>,
+    /// Name of the API key that scheduled it.
+    pub created_by: Option<String>,
+}
+
+const SCHEDULED_PRICE_COLUMNS: &str = "id, food_id, price, to_char(effective_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as effective_at, to_char(applied_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as applied_at, created_by";
+
+impl PriceModelController {
+    pub async fn schedule(
+        mm: ModelController,
+        data: PriceToSchedule,
+    ) -> Result<ScheduledPriceToSelect> {
+        debug!("{:<12} - schedule_price", "HANDLER");
+
+        if !data.price.is_finite() || data.price < 0.0 {
+            return Err(Error::InvalidScheduledPrice(format!(
+                "price {} must be a non-negative amount",
+                data.price
+            )));
+        }
+
+        let timer = metrics().query_timer("schedule_price");
+        let query = format!("insert into scheduled_prices (food_id, price, effective_at, created_by) select id, $2, $3, $4 from foods_table where id = $1 and food_status != 'removed' returning {SCHEDULED_PRICE_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, ScheduledPriceToSelect>(&query)
+            .bind(data.food_id)
+            .bind(data.price)
+            .bind(data.effective_at)
+            .bind(data.created_by)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(price)) => Ok(price),
+            Ok(None) => Err(Error::FoodIdNotFound(format!(
+                "no food with id {}",
+                data.food_id
+            ))),
+            Err(err) => {
+                debug!("{:<12} - schedule_price error", "ERROR_CONTROLLER");
+                Err(Error::CreateFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+
+    /// A food's scheduled prices, latest `effective_at` first.
+    pub async fn select(mm: ModelController, food_id: i64) -> Result<Vec<ScheduledPriceToSelect>> {
+        debug!("{:<12} - select_scheduled_prices", "HANDLER");
+
+        let timer = metrics().query_timer("select_scheduled_prices");
+        let query = format!("select {SCHEDULED_PRICE_COLUMNS} from scheduled_prices where food_id = $1 order by scheduled_prices.effective_at desc, id desc");
+
+        let res = sqlx::query_as::<_, ScheduledPriceToSelect>(&query)
+            .bind(food_id)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - select_scheduled_prices error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// Drops a price that has not been applied yet.
+    pub async fn cancel(
+        mm: ModelController,
+        food_id: i64,
+        id: i64,
+    ) -> Result<ScheduledPriceToSelect> {
+        debug!("{:<12} - cancel_scheduled_price", "HANDLER");
+
+        let timer = metrics().query_timer("cancel_scheduled_price");
+        let query = format!("delete from scheduled_prices where id = $1 and food_id = $2 and applied_at is null returning {SCHEDULED_PRICE_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, ScheduledPriceToSelect>(&query)
+            .bind(id)
+            .bind(food_id)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(price)) => Ok(price),
+            Ok(None) => Err(Error::ScheduledPriceNotFound(format!(
+                "no pending price {id} of food {food_id}"
+            ))),
+            Err(err) => {
+                debug!("{:<12} - cancel_scheduled_price error", "ERROR_CONTROLLER");
+                Err(Error::DeleteFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+
+    /// Applies the prices that are due. When several are due for a food the
+    /// latest wins, the others are marked applied along with it. Returns
+    /// the number of foods repriced.
+    pub async fn apply_due(mm: &ModelController) -> Result<u64> {
+        let timer = metrics().query_timer("apply_scheduled_prices");
+        let query = "with due as (update scheduled_prices set applied_at = now() where applied_at is null and effective_at <= now() returning id, food_id, price, effective_at), latest as (select distinct on (food_id) food_id, price from due order by food_id, effective_at desc, id desc) update foods_table f set price = l.price, mtime = now() from latest l where f.id = l.food_id and f.food_status != 'removed'";
+
+        let res = sqlx::query(query)
+            .execute(mm.db()?)
+            .await
+            .map(|done| done.rows_affected())
+            .map_err(|err| {
+                debug!("{:<12} - apply_scheduled_prices error", "ERROR_CONTROLLER");
+                Error::UpdateFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+}
@@ -0,0 +1,121 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    api_keys_fns::ApiKeyPrincipal,
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::Result,
+    prices_fns::{PriceModelController, PriceToSchedule, ScheduledPriceToSelect},
+};
+
+pub fn routes_prices(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/foods/:id/scheduled-prices",
+            get(api_v1_list_scheduled_prices).post(api_v1_schedule_price),
+        )
+        .route(
+            "/api/v1/foods/:id/scheduled-prices/:price_id",
+            delete(api_v1_cancel_scheduled_price),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct SchedulePricePayload {
+    price: f64,
+    /// RFC 3339. The price is applied by the next `apply_scheduled_prices`
+    /// run after it.
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    effective_at: OffsetDateTime,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/foods/{id}/scheduled-prices",
+    tag = "prices",
+    params(("id" = i64, Path, description = "Food id")),
+    request_body = SchedulePricePayload,
+    responses(
+        (status = 201, description = "Price scheduled", body = DataBody<ScheduledPriceToSelect>,
+            headers(("location" = String, description = "URL of the food's scheduled prices"))),
+        (status = 400, description = "Negative price"),
+        (status = 404, description = "Food not found"),
+    )
+)]
+async fn api_v1_schedule_price(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    principal: Option<Extension<ApiKeyPrincipal>>,
+    Json(body): Json<SchedulePricePayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_schedule_price", "ROUTE_HANDLER");
+
+    let data = PriceToSchedule {
+        food_id: id,
+        price: body.price,
+        effective_at: body.effective_at,
+        created_by: principal.map(|Extension(principal)| principal.key_name),
+    };
+
+    let price = PriceModelController::schedule(mm, data).await?;
+    let location = format!("/api/v1/foods/{id}/scheduled-prices");
+    let body = Json(DataBody::new(price));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/scheduled-prices",
+    tag = "prices",
+    params(("id" = i64, Path, description = "Food id")),
+    responses(
+        (status = 200, description = "Pending and applied prices, latest first", body = DataBody<Vec<ScheduledPriceToSelect>>),
+    )
+)]
+async fn api_v1_list_scheduled_prices(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<Vec<ScheduledPriceToSelect>>>> {
+    debug!("{:<12} - api_v1_list_scheduled_prices", "ROUTE_HANDLER");
+
+    let prices = PriceModelController::select(mm, id).await?;
+    let body = Json(DataBody::new(prices));
+    Ok(body)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/foods/{id}/scheduled-prices/{price_id}",
+    tag = "prices",
+    params(
+        ("id" = i64, Path, description = "Food id"),
+        ("price_id" = i64, Path, description = "Scheduled price id"),
+    ),
+    responses(
+        (status = 200, description = "Pending price dropped", body = DataBody<ScheduledPriceToSelect>),
+        (status = 404, description = "No pending price with that id"),
+    )
+)]
+async fn api_v1_cancel_scheduled_price(
+    State(mm): State<ModelController>,
+    Path((id, price_id)): Path<(i64, i64)>,
+) -> Result<Json<DataBody<ScheduledPriceToSelect>>> {
+    debug!("{:<12} - api_v1_cancel_scheduled_price", "ROUTE_HANDLER");
+
+    let price = PriceModelController::cancel(mm, id, price_id).await?;
+    let body = Json(DataBody::new(price));
+    Ok(body)
+}
@@ -0,0 +1,423 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, PgConnection};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::{FoodModelController, ModelController},
+    error::{Error, Result},
+    lots_fns::{take_fefo, LotPicked},
+    metrics::metrics,
+    stock_fns::{put_stock, record_movement},
+    units::Unit,
+};
+
+/// Recipes are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct RecipeModelController;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RecipeItemToSet {
+    pub ingredient_id: i64,
+    /// Per base unit of the composite food.
+    pub quantity: i32,
+    /// Unit `quantity` is given in, the ingredient's base unit when absent.
+    pub quantity_unit: Option<Unit>,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct RecipeItemToSelect {
+    pub ingredient_id: i64,
+    pub food_name: String,
+    /// Per base unit of the composite food.
+    pub quantity: i32,
+    pub unit: Unit,
+}
+
+/// How many units of a composite food the ingredients in stock allow.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RecipeCapacity {
+    pub food_id: i64,
+    pub can_make: i32,
+    /// Ingredient running out first.
+    pub limited_by: i64,
+    pub ingredients: Vec<IngredientCapacity>,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct IngredientCapacity {
+    pub ingredient_id: i64,
+    pub food_name: String,
+    pub quantity: i32,
+    pub stocks: i32,
+    pub can_make: i32,
+}
+
+/// Ingredient stock consumed by producing or selling a composite food.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct IngredientUsed {
+    pub ingredient_id: i64,
+    pub quantity: i32,
+    /// Ingredient stock left.
+    pub stocks: i32,
+    pub lots: Vec<LotPicked>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct FoodProduced {
+    pub food_id: i64,
+    pub quantity: i32,
+    /// Composite stock once produced.
+    pub stocks: i32,
+    pub ingredients: Vec<IngredientUsed>,
+}
+
+impl RecipeModelController {
+    /// Replaces the recipe of a food, an empty list removes it.
+    pub async fn set(
+        mm: ModelController,
+        food_id: i64,
+        items: Vec<RecipeItemToSet>,
+    ) -> Result<Vec<RecipeItemToSelect>> {
+        debug!("{:<12} - set_recipe", "HANDLER");
+
+        let mut ingredient_ids: Vec<i64> = items.iter().map(|item| item.ingredient_id).collect();
+        ingredient_ids.sort_unstable();
+        ingredient_ids.dedup();
+        if ingredient_ids.len() != items.len() {
+            return Err(Error::RecipeFailed(String::from(
+                "an ingredient appears more than once",
+            )));
+        }
+        if ingredient_ids.contains(&food_id) {
+            return Err(Error::RecipeFailed(String::from(
+                "a food cannot be its own ingredient",
+            )));
+        }
+
+        let mut normalized = Vec::with_capacity(items.len());
+        for item in items {
+            if item.quantity <= 0 {
+                return Err(Error::RecipeFailed(String::from(
+                    "quantity must be positive",
+                )));
+            }
+            let (quantity, _) = FoodModelController::to_base_quantity(
+                &mm,
+                item.ingredient_id,
+                item.quantity,
+                item.quantity_unit,
+            )
+            .await?;
+            normalized.push((item.ingredient_id, quantity));
+        }
+
+        let timer = metrics().query_timer("set_recipe");
+        let res = set(&mm, food_id, normalized).await;
+        timer.observe(res)?;
+
+        Self::get(mm, food_id).await
+    }
+
+    pub async fn get(mm: ModelController, food_id: i64) -> Result<Vec<RecipeItemToSelect>> {
+        debug!("{:<12} - get_recipe", "HANDLER");
+
+        let timer = metrics().query_timer("get_recipe");
+        let query = "select r.ingredient_id, f.food_name, r.quantity, f.unit from recipe_items r join foods_table f on f.id = r.ingredient_id where r.food_id = $1 order by f.food_name";
+
+        let res = sqlx::query_as::<_, RecipeItemToSelect>(query)
+            .bind(food_id)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - get_recipe error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// "How many can I make" from the ingredient stock on hand.
+    pub async fn capacity(
+        mm: ModelController,
+        food_id: i64,
+        store_id: Option<i64>,
+    ) -> Result<RecipeCapacity> {
+        debug!("{:<12} - recipe_capacity", "HANDLER");
+
+        let timer = metrics().query_timer("recipe_capacity");
+        let query = "select r.ingredient_id, f.food_name, r.quantity, case when $2::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks, greatest(case when $2::bigint is null then f.stocks else coalesce(s.stocks, 0) end, 0) / r.quantity as can_make from recipe_items r join foods_table f on f.id = r.ingredient_id left join food_stocks s on s.food_id = r.ingredient_id and s.store_id = $2 where r.food_id = $1 order by can_make, f.food_name";
+
+        let res = sqlx::query_as::<_, IngredientCapacity>(query)
+            .bind(food_id)
+            .bind(store_id)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - recipe_capacity error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        let ingredients = timer.observe(res)?;
+
+        // Sorted by `can_make`, the first ingredient is the limiting one.
+        let Some(limiting) = ingredients.first() else {
+            return Err(Error::RecipeFailed(format!("food {food_id} has no recipe")));
+        };
+
+        Ok(RecipeCapacity {
+            food_id,
+            can_make: limiting.can_make,
+            limited_by: limiting.ingredient_id,
+            ingredients,
+        })
+    }
+
+    /// Makes `quantity` of a composite food: its ingredients are taken out
+    /// of the stock and the food's stock goes up, all or nothing.
+    pub async fn produce(
+        mm: ModelController,
+        food_id: i64,
+        quantity: i32,
+        store_id: Option<i64>,
+    ) -> Result<FoodProduced> {
+        debug!("{:<12} - produce", "HANDLER");
+
+        if quantity <= 0 {
+            return Err(Error::RecipeFailed(String::from(
+                "quantity must be positive",
+            )));
+        }
+
+        let timer = metrics().query_timer("produce");
+        timer.observe(produce(&mm, food_id, quantity, store_id).await)
+    }
+}
+
+async fn set(mm: &ModelController, food_id: i64, items: Vec<(i64, i32)>) -> Result<()> {
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let query = "delete from recipe_items where food_id = $1";
+    if let Err(err) = sqlx::query(query).bind(food_id).execute(&mut *tx).await {
+        debug!("{:<12} - set_recipe error", "ERROR_CONTROLLER");
+        return Err(Error::UpdateFailed(err.to_string()));
+    }
+
+    let query = "insert into recipe_items (food_id, ingredient_id, quantity) values ($1, $2, $3)";
+    for (ingredient_id, quantity) in items {
+        if let Err(err) = sqlx::query(query)
+            .bind(food_id)
+            .bind(ingredient_id)
+            .bind(quantity)
+            .execute(&mut *tx)
+            .await
+        {
+            debug!("{:<12} - set_recipe item error {err:?}", "ERROR_CONTROLLER");
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))
+}
+
+async fn produce(
+    mm: &ModelController,
+    food_id: i64,
+    quantity: i32,
+    store_id: Option<i64>,
+) -> Result<FoodProduced> {
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    if !has_recipe(&mut tx, food_id).await? {
+        return Err(Error::RecipeFailed(format!("food {food_id} has no recipe")));
+    }
+
+    let ingredients = consume_ingredients(&mut tx, food_id, store_id, quantity, "produce").await?;
+    let stocks = put_stock(&mut tx, food_id, store_id, quantity).await?;
+    record_movement(&mut tx, food_id, store_id, quantity, "produce", None).await?;
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(FoodProduced {
+        food_id,
+        quantity,
+        stocks,
+        ingredients,
+    })
+}
+
+pub(crate) async fn has_recipe(conn: &mut PgConnection, food_id: i64) -> Result<bool> {
+    let query = "select exists(select 1 from recipe_items where food_id = $1)";
+
+    sqlx::query_scalar::<_, bool>(query)
+        .bind(food_id)
+        .fetch_one(conn)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - has_recipe error", "ERROR_CONTROLLER");
+            Error::SelectFailed(err.to_string())
+        })
+}
+
+/// Takes the ingredients of `quantity` units of a composite food out of the
+/// stock. Ingredients are not expanded further, a composite ingredient is
+/// taken from its own stock.
+pub(crate) async fn consume_ingredients(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    quantity: i32,
+    reason: &str,
+) -> Result<Vec<IngredientUsed>> {
+    // Ordered by id so concurrent consumers lock ingredient rows in the
+    // same order.
+    let query =
+        "select ingredient_id, quantity from recipe_items where food_id = $1 order by ingredient_id";
+    let items = match sqlx::query_as::<_, (i64, i32)>(query)
+        .bind(food_id)
+        .fetch_all(&mut *conn)
+        .await
+    {
+        Ok(items) => items,
+        Err(err) => {
+            debug!("{:<12} - consume_ingredients error", "ERROR_CONTROLLER");
+            return Err(Error::SelectFailed(err.to_string()));
+        }
+    };
+
+    let mut used = Vec::with_capacity(items.len());
+    for (ingredient_id, per_unit) in items {
+        let needed = per_unit
+            .checked_mul(quantity)
+            .ok_or(Error::RecipeFailed(format!(
+                "{quantity} of food {food_id} needs too much of ingredient {ingredient_id}"
+            )))?;
+        let (stocks, lots) = take_fefo(conn, ingredient_id, store_id, needed, reason).await?;
+
+        used.push(IngredientUsed {
+            ingredient_id,
+            quantity: needed,
+            stocks,
+            lots,
+        });
+    }
+
+    Ok(used)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        crud_fns::FoodToCreate,
+        orders_fns::{OrderLine, OrderModelController, OrderToPlace},
+    };
+
+    async fn food(mm: &ModelController, food_name: &str, stocks: i32) -> i64 {
+        FoodModelController::create(mm.clone(), FoodToCreate::sample(food_name, stocks), None)
+            .await
+            .unwrap()
+    }
+
+    async fn stocks(db: &PgPool, food_id: i64) -> i32 {
+        sqlx::query_scalar("select stocks from foods_table where id = $1")
+            .bind(food_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    /// A sandwich of two breads and one cheese, with `sandwiches` already
+    /// made.
+    async fn sandwich(
+        mm: &ModelController,
+        sandwiches: i32,
+        breads: i32,
+        cheeses: i32,
+    ) -> [i64; 3] {
+        let sandwich_id = food(mm, "Sandwich", sandwiches).await;
+        let bread_id = food(mm, "Bread", breads).await;
+        let cheese_id = food(mm, "Cheese", cheeses).await;
+        let item = |ingredient_id, quantity| RecipeItemToSet {
+            ingredient_id,
+            quantity,
+            quantity_unit: None,
+        };
+        RecipeModelController::set(
+            mm.clone(),
+            sandwich_id,
+            vec![item(bread_id, 2), item(cheese_id, 1)],
+        )
+        .await
+        .unwrap();
+        [sandwich_id, bread_id, cheese_id]
+    }
+
+    fn order(food_id: i64, quantity: i32) -> OrderToPlace {
+        OrderToPlace {
+            store_id: None,
+            lines: vec![OrderLine {
+                food_id,
+                quantity,
+                quantity_unit: None,
+            }],
+        }
+    }
+
+    #[sqlx::test]
+    async fn order_sells_prepared_stock_before_making_the_rest(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let [sandwich_id, bread_id, cheese_id] = sandwich(&mm, 2, 10, 10).await;
+
+        let placed = OrderModelController::place(mm.clone(), order(sandwich_id, 5))
+            .await
+            .unwrap();
+        let line = &placed.lines[0];
+        assert_eq!(line.made, 3);
+        assert_eq!(line.stocks, 0);
+        let used: Vec<(i64, i32)> = line
+            .ingredients
+            .iter()
+            .map(|used| (used.ingredient_id, used.quantity))
+            .collect();
+        assert_eq!(used, vec![(bread_id, 6), (cheese_id, 3)]);
+
+        assert_eq!(stocks(&db, sandwich_id).await, 0);
+        assert_eq!(stocks(&db, bread_id).await, 4);
+        assert_eq!(stocks(&db, cheese_id).await, 7);
+    }
+
+    #[sqlx::test]
+    async fn missing_ingredient_rolls_everything_back(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        // Enough bread for three sandwiches, cheese for one.
+        let [sandwich_id, bread_id, cheese_id] = sandwich(&mm, 1, 6, 1).await;
+
+        let res = RecipeModelController::produce(mm.clone(), sandwich_id, 2, None).await;
+        assert!(matches!(res, Err(Error::InsufficientStock(_))));
+
+        let res = OrderModelController::place(mm.clone(), order(sandwich_id, 3)).await;
+        assert!(matches!(res, Err(Error::InsufficientStock(_))));
+
+        assert_eq!(stocks(&db, sandwich_id).await, 1);
+        assert_eq!(stocks(&db, bread_id).await, 6);
+        assert_eq!(stocks(&db, cheese_id).await, 1);
+        let orders: i64 = sqlx::query_scalar("select count(*) from orders")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(orders, 0);
+    }
+}
@@ -0,0 +1,151 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    crud_fns::{FoodModelController, ModelController},
+    crud_routes::DataBody,
+    error::Result,
+    recipes_fns::{
+        FoodProduced, RecipeCapacity, RecipeItemToSelect, RecipeItemToSet, RecipeModelController,
+    },
+    units::Unit,
+};
+
+pub fn routes_recipes(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/foods/:id/recipe",
+            get(api_v1_get_recipe).put(api_v1_put_recipe),
+        )
+        .route(
+            "/api/v1/foods/:id/recipe/capacity",
+            get(api_v1_recipe_capacity),
+        )
+        .route("/api/v1/foods/:id/produce", post(api_v1_produce_food))
+        .with_state(mm)
+}
+
+/// `?store_id=` counting the ingredients one store holds.
+#[derive(Debug, Deserialize, IntoParams)]
+struct RecipeScope {
+    /// Use this store's ingredient stock instead of the foods' own stock.
+    store_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct PutRecipePayload {
+    items: Vec<RecipeItemToSet>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct ProducePayload {
+    quantity: i32,
+    /// Unit `quantity` is given in, the food's base unit when absent.
+    quantity_unit: Option<Unit>,
+    /// Take the ingredients from and put the food into this store.
+    store_id: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/recipe",
+    tag = "recipes",
+    params(("id" = i64, Path, description = "Composite food id")),
+    responses(
+        (status = 200, description = "Ingredients per base unit of the food", body = DataBody<Vec<RecipeItemToSelect>>),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_v1_get_recipe(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+) -> Result<Json<DataBody<Vec<RecipeItemToSelect>>>> {
+    debug!("{:<12} - api_v1_get_recipe", "ROUTE_HANDLER");
+
+    let items = RecipeModelController::get(mm, food_id).await?;
+    let body = Json(DataBody::new(items));
+    Ok(body)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/foods/{id}/recipe",
+    tag = "recipes",
+    params(("id" = i64, Path, description = "Composite food id")),
+    request_body = PutRecipePayload,
+    responses(
+        (status = 200, description = "Recipe replaced, empty `items` removes it", body = DataBody<Vec<RecipeItemToSelect>>),
+        (status = 400, description = "Invalid recipe"),
+    )
+)]
+async fn api_v1_put_recipe(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    Json(body): Json<PutRecipePayload>,
+) -> Result<Json<DataBody<Vec<RecipeItemToSelect>>>> {
+    debug!("{:<12} - api_v1_put_recipe", "ROUTE_HANDLER");
+
+    let items = RecipeModelController::set(mm, food_id, body.items).await?;
+    let body = Json(DataBody::new(items));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/recipe/capacity",
+    tag = "recipes",
+    params(("id" = i64, Path, description = "Composite food id"), RecipeScope),
+    responses(
+        (status = 200, description = "How many can be made from the ingredients in stock", body = DataBody<RecipeCapacity>),
+        (status = 400, description = "Food has no recipe"),
+    )
+)]
+async fn api_v1_recipe_capacity(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    Query(scope): Query<RecipeScope>,
+) -> Result<Json<DataBody<RecipeCapacity>>> {
+    debug!("{:<12} - api_v1_recipe_capacity", "ROUTE_HANDLER");
+
+    let capacity = RecipeModelController::capacity(mm, food_id, scope.store_id).await?;
+    let body = Json(DataBody::new(capacity));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/foods/{id}/produce",
+    tag = "recipes",
+    params(("id" = i64, Path, description = "Composite food id")),
+    request_body = ProducePayload,
+    responses(
+        (status = 200, description = "Ingredients consumed and the food's stock raised", body = DataBody<FoodProduced>),
+        (status = 400, description = "Quantity not positive or food has no recipe"),
+        (status = 409, description = "Insufficient ingredient stock"),
+    )
+)]
+async fn api_v1_produce_food(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    Json(body): Json<ProducePayload>,
+) -> Result<Json<DataBody<FoodProduced>>> {
+    debug!("{:<12} - api_v1_produce_food", "ROUTE_HANDLER");
+
+    let ProducePayload {
+        quantity,
+        quantity_unit,
+        store_id,
+    } = body;
+    let (quantity, _) =
+        FoodModelController::to_base_quantity(&mm, food_id, quantity, quantity_unit).await?;
+
+    let produced = RecipeModelController::produce(mm, food_id, quantity, store_id).await?;
+    let body = Json(DataBody::new(produced));
+    Ok(body)
+}
@@ -0,0 +1,301 @@
+//! Reorder suggestions from past consumption.
+//!
+//! Usage is what orders, recipe production and stock lowered through a food
+//! update took out of the stock over the last `window_days`, read from the
+//! `stock_movements` ledger. Waste, stock-take corrections and receipts do
+//! not count as usage. A food is
+//! suggested once its stock no longer covers the lead time plus the safety
+//! days, and the suggestion tops it back up to that level.
+
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    config::core_config,
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+    units::Unit,
+};
+
+/// Reorder suggestions read the Postgres ledger, the in-memory backend has
+/// none.
+#[derive(Clone, Debug)]
+pub struct ReorderModelController;
+
+#[derive(Debug)]
+pub struct ReorderPolicy {
+    /// Days of history the usage is averaged over.
+    pub window_days: u32,
+    /// Days between placing an order and receiving it.
+    pub lead_time_days: u32,
+    /// Extra days of usage kept on hand.
+    pub safety_days: u32,
+}
+
+impl Default for ReorderPolicy {
+    fn default() -> Self {
+        ReorderPolicy {
+            window_days: core_config().REORDER_WINDOW_DAYS,
+            lead_time_days: core_config().REORDER_LEAD_TIME_DAYS,
+            safety_days: core_config().REORDER_SAFETY_DAYS,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct FoodUsage {
+    food_id: i64,
+    food_name: String,
+    category: String,
+    unit: Unit,
+    case_size: Option<i32>,
+    stocks: i32,
+    /// Taken out over the window, in the base unit.
+    used: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReorderSuggestion {
+    pub food_id: i64,
+    pub food_name: String,
+    pub category: String,
+    pub unit: Unit,
+    pub stocks: i32,
+    /// Average usage per day over the window, in `unit`.
+    pub avg_daily_usage: f64,
+    /// Days the current stock lasts at that usage.
+    pub days_of_cover: f64,
+    /// In `unit`, rounded up to whole cases when the food has a case size.
+    pub suggested_quantity: i32,
+    pub suggested_cases: Option<i32>,
+}
+
+impl ReorderModelController {
+    /// Foods whose stock runs out before a new order would arrive plus the
+    /// safety days, fewest days of cover first.
+    pub async fn suggestions(
+        mm: ModelController,
+        policy: ReorderPolicy,
+        store_id: Option<i64>,
+    ) -> Result<Vec<ReorderSuggestion>> {
+        debug!("{:<12} - reorder_suggestions", "HANDLER");
+
+        if policy.window_days == 0 {
+            return Err(Error::InvalidReorderParams(String::from(
+                "window_days must be positive",
+            )));
+        }
+
+        let timer = metrics().query_timer("reorder_suggestions");
+        let query = "with usage as (select food_id, -sum(delta) as used from stock_movements where delta < 0 and reason in ('order', 'produce', 'update') and ctime >= now() - make_interval(days => $1) and store_id is not distinct from $2 group by food_id) select f.id as food_id, f.food_name, f.category, f.unit, f.case_size, case when $2::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks, u.used from foods_table f join usage u on u.food_id = f.id left join food_stocks s on s.food_id = f.id and s.store_id = $2 where f.food_status != 'removed'";
+
+        let res = sqlx::query_as::<_, FoodUsage>(query)
+            .bind(policy.window_days as i32)
+            .bind(store_id)
+            .fetch_all(mm.read_db()?)
+            .await
+            .map(|usages| {
+                let mut suggestions: Vec<ReorderSuggestion> = usages
+                    .into_iter()
+                    .filter_map(|usage| suggest(usage, &policy))
+                    .collect();
+                suggestions.sort_by(|a, b| {
+                    a.days_of_cover
+                        .total_cmp(&b.days_of_cover)
+                        .then_with(|| a.food_name.cmp(&b.food_name))
+                });
+                suggestions
+            })
+            .map_err(|err| {
+                debug!("{:<12} - reorder_suggestions error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+}
+
+fn suggest(usage: FoodUsage, policy: &ReorderPolicy) -> Option<ReorderSuggestion> {
+    let avg_daily_usage = usage.used as f64 / f64::from(policy.window_days);
+    if avg_daily_usage <= 0.0 {
+        return None;
+    }
+
+    let stocks = usage.stocks.max(0);
+    let target = (avg_daily_usage * f64::from(policy.lead_time_days + policy.safety_days)).ceil();
+    let shortfall = target as i64 - i64::from(stocks);
+    if shortfall <= 0 {
+        return None;
+    }
+
+    let (suggested_quantity, suggested_cases) = match usage.case_size {
+        Some(case_size) => {
+            let cases = (shortfall + i64::from(case_size) - 1) / i64::from(case_size);
+            (cases * i64::from(case_size), Some(cases))
+        }
+        None => (shortfall, None),
+    };
+
+    Some(ReorderSuggestion {
+        food_id: usage.food_id,
+        food_name: usage.food_name,
+        category: usage.category,
+        unit: usage.unit,
+        stocks: usage.stocks,
+        avg_daily_usage: (avg_daily_usage * 100.0).round() / 100.0,
+        days_of_cover: (f64::from(stocks) / avg_daily_usage * 10.0).round() / 10.0,
+        suggested_quantity: i32::try_from(suggested_quantity).unwrap_or(i32::MAX),
+        suggested_cases: suggested_cases.map(|cases| i32::try_from(cases).unwrap_or(i32::MAX)),
+    })
+}
+
+/// The suggestions as CSV with a header row, one food per line.
+pub fn to_csv(suggestions: &[ReorderSuggestion]) -> Result<String> {
+    let mut csv = csv::WriterBuilder::new()
+        .has_headers(false)
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(Vec::new());
+
+    csv.write_record([
+        "food_id",
+        "food_name",
+        "category",
+        "unit",
+        "stocks",
+        "avg_daily_usage",
+        "days_of_cover",
+        "suggested_quantity",
+        "suggested_cases",
+    ])
+    .map_err(|err| Error::CsvFailed(err.to_string()))?;
+    for s in suggestions {
+        csv.serialize((
+            s.food_id,
+            defuse_formula(&s.food_name),
+            defuse_formula(&s.category),
+            s.unit,
+            s.stocks,
+            s.avg_daily_usage,
+            s.days_of_cover,
+            s.suggested_quantity,
+            s.suggested_cases,
+        ))
+        .map_err(|err| Error::CsvFailed(err.to_string()))?;
+    }
+
+    let bytes = csv
+        .into_inner()
+        .map_err(|err| Error::CsvFailed(err.to_string()))?;
+    String::from_utf8(bytes).map_err(|err| Error::CsvFailed(err.to_string()))
+}
+
+/// A leading `=`, `+`, `-` or `@` gets a `'` so spreadsheets do not run the
+/// field as a formula.
+fn defuse_formula(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::crud_fns::{FoodModelController, FoodToCreate, FoodToUpdate};
+
+    fn usage(stocks: i32, used: i64, case_size: Option<i32>) -> FoodUsage {
+        FoodUsage {
+            food_id: 1,
+            food_name: String::from("Flour, bread"),
+            category: String::from("baking"),
+            unit: Unit::Kg,
+            case_size,
+            stocks,
+            used,
+        }
+    }
+
+    #[test]
+    fn test_suggest() {
+        let policy = ReorderPolicy {
+            window_days: 28,
+            lead_time_days: 7,
+            safety_days: 3,
+        };
+
+        // -- 2 a day, 10 days to cover: 20 wanted, 5 on hand.
+        let s = suggest(usage(5, 56, None), &policy).unwrap();
+        assert_eq!(s.avg_daily_usage, 2.0);
+        assert_eq!(s.days_of_cover, 2.5);
+        assert_eq!(s.suggested_quantity, 15);
+        assert_eq!(s.suggested_cases, None);
+
+        let s = suggest(usage(5, 56, Some(6)), &policy).unwrap();
+        assert_eq!((s.suggested_quantity, s.suggested_cases), (18, Some(3)));
+
+        assert!(suggest(usage(20, 56, None), &policy).is_none());
+        assert!(suggest(usage(0, 0, None), &policy).is_none());
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let s = suggest(
+            usage(5, 56, None),
+            &ReorderPolicy {
+                window_days: 28,
+                lead_time_days: 7,
+                safety_days: 3,
+            },
+        )
+        .unwrap();
+
+        let csv = to_csv(&[s]).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().starts_with("food_id,food_name,"));
+        assert_eq!(
+            lines.next(),
+            Some("1,\"Flour, bread\",baking,kg,5,2.0,2.5,15,")
+        );
+        assert_eq!(defuse_formula("=SUM(A1)"), "'=SUM(A1)");
+    }
+
+    #[sqlx::test]
+    async fn stock_lowered_by_update_is_usage(db: PgPool) {
+        let mm = ModelController::with_db(db);
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", 30), None)
+                .await
+                .unwrap();
+        let data = FoodToUpdate {
+            id: food_id,
+            store_id: None,
+            food_name: None,
+            category: None,
+            stocks: Some(2),
+            price: None,
+            total_quantity: None,
+            unit: None,
+            case_size: None,
+        };
+        FoodModelController::update(mm.clone(), data, None)
+            .await
+            .unwrap();
+
+        let policy = ReorderPolicy {
+            window_days: 28,
+            lead_time_days: 7,
+            safety_days: 3,
+        };
+        let suggestions = ReorderModelController::suggestions(mm, policy, None)
+            .await
+            .unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].food_id, food_id);
+        assert_eq!(suggestions[0].avg_daily_usage, 1.0);
+    }
+}
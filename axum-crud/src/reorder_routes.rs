@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::IntoParams;
+
+use crate::{
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::{Error, Result},
+    reorder_fns::{to_csv, ReorderModelController, ReorderPolicy, ReorderSuggestion},
+};
+
+pub fn routes_reorder(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/reports/reorder-suggestions",
+            get(api_v1_reorder_suggestions),
+        )
+        // Short form used by purchasing.
+        .route("/api/reorder-suggestions", get(api_v1_reorder_suggestions))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct ReorderQuery {
+    /// Only this store's usage and stock.
+    store_id: Option<i64>,
+    /// Days of history to average usage over, `REORDER_WINDOW_DAYS` when absent.
+    window_days: Option<u32>,
+    /// Days an order takes to arrive, `REORDER_LEAD_TIME_DAYS` when absent.
+    lead_time_days: Option<u32>,
+    /// Extra days of usage to keep on hand, `REORDER_SAFETY_DAYS` when absent.
+    safety_days: Option<u32>,
+    /// `json` (default) or `csv`.
+    format: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/reorder-suggestions",
+    tag = "reorder",
+    params(ReorderQuery),
+    responses(
+        (status = 200, description = "Foods to reorder, fewest days of cover first", body = DataBody<Vec<ReorderSuggestion>>),
+        (status = 200, description = "The same with `format=csv`", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid parameters"),
+        (status = 500, description = "Report failed"),
+    )
+)]
+async fn api_v1_reorder_suggestions(
+    State(mm): State<ModelController>,
+    Query(query): Query<ReorderQuery>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_reorder_suggestions", "ROUTE_HANDLER");
+
+    let csv = match query.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(format) => {
+            return Err(Error::InvalidReorderParams(format!(
+                "unknown format {format}"
+            )))
+        }
+    };
+
+    let defaults = ReorderPolicy::default();
+    let policy = ReorderPolicy {
+        window_days: query.window_days.unwrap_or(defaults.window_days),
+        lead_time_days: query.lead_time_days.unwrap_or(defaults.lead_time_days),
+        safety_days: query.safety_days.unwrap_or(defaults.safety_days),
+    };
+
+    let suggestions = ReorderModelController::suggestions(mm, policy, query.store_id).await?;
+    if csv {
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"reorder-suggestions.csv\"",
+                ),
+            ],
+            to_csv(&suggestions)?,
+        )
+            .into_response());
+    }
+
+    let body = Json(DataBody::new(suggestions));
+    Ok(body.into_response())
+}
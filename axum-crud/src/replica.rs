@@ -0,0 +1,181 @@
+//! Routing of lag-tolerant reads to the optional read replica.
+//!
+//! Food listings and lookups, stores and the reports read through
+//! `ModelController::read_db`, which picks the replica when the current
+//! request allows it and the replica is healthy, the primary otherwise.
+//! Writes, and the reads they make, always use the primary.
+//!
+//! `mw_route_reads` lets GET and HEAD requests read from the replica,
+//! unless they send `X-Read-Your-Writes: true`, which also skips the food
+//! cache so the client sees its own writes. Work outside a request, like
+//! the jobs and the admin CLI, stays on the primary. So do the gRPC calls,
+//! except the read RPCs, which run under `with_reads` like the refresh of
+//! the business metrics.
+
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use tokio::time::{sleep, timeout};
+use tracing::{info, warn};
+
+use crate::{config::core_config, crud_fns::ModelController, metrics::metrics, store::Db};
+
+pub const READ_YOUR_WRITES_HEADER: &str = "x-read-your-writes";
+
+/// Where the reads of the current request may go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reads {
+    Replica,
+    Primary,
+    /// Primary, bypassing the food cache as well.
+    OwnWrites,
+}
+
+tokio::task_local! {
+    static READS: Reads;
+}
+
+impl Reads {
+    /// `Replica` for reads, unless the caller asked to see its own writes.
+    pub fn for_request(is_read: bool, headers: &HeaderMap) -> Reads {
+        let own_writes = headers
+            .get(READ_YOUR_WRITES_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+
+        match (own_writes, is_read) {
+            (true, _) => Reads::OwnWrites,
+            (false, true) => Reads::Replica,
+            (false, false) => Reads::Primary,
+        }
+    }
+
+    /// The current task's, `Primary` outside `with_reads`.
+    pub fn current() -> Reads {
+        READS.try_with(|reads| *reads).unwrap_or(Reads::Primary)
+    }
+}
+
+/// Runs `fut` with its reads routed as `reads` says. Tasks it spawns are
+/// back on the primary.
+pub async fn with_reads<F: Future>(reads: Reads, fut: F) -> F::Output {
+    READS.scope(reads, fut).await
+}
+
+pub async fn mw_route_reads(req: Request<Body>, next: Next) -> Response {
+    let is_read = matches!(*req.method(), Method::GET | Method::HEAD);
+    let reads = Reads::for_request(is_read, req.headers());
+
+    with_reads(reads, next.run(req)).await
+}
+
+pub struct Replica {
+    db: Db,
+    /// Set by `spawn_health_check`, so reads stay on the primary until the
+    /// first check passes.
+    healthy: AtomicBool,
+}
+
+impl Replica {
+    pub fn new(db: Db) -> Self {
+        Replica {
+            db,
+            healthy: AtomicBool::new(false),
+        }
+    }
+
+    pub fn db(&self) -> &Db {
+        &self.db
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Acquire)
+    }
+}
+
+/// The pool a lag-tolerant read of the current task goes to.
+pub fn route<'a>(primary: &'a Db, replica: Option<&'a Replica>) -> &'a Db {
+    match replica {
+        Some(replica) if Reads::current() == Reads::Replica && replica.is_healthy() => replica.db(),
+        _ => primary,
+    }
+}
+
+/// Checks the replica every `DB_READ_CHECK_INTERVAL_MS`. It is taken out of
+/// rotation while it does not answer within `HEALTH_CHECK_TIMEOUT_MS` or
+/// lags more than `DB_READ_MAX_LAG_MS` behind the primary.
+pub fn spawn_health_check(mm: ModelController) {
+    let Some(replica) = mm.replica().cloned() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let config = core_config();
+        let every = Duration::from_millis(config.DB_READ_CHECK_INTERVAL_MS);
+        let limit = Duration::from_millis(config.HEALTH_CHECK_TIMEOUT_MS);
+        let max_lag = Duration::from_millis(config.DB_READ_MAX_LAG_MS);
+
+        let mut first = true;
+        loop {
+            let problem = match timeout(limit, lag(replica.db())).await {
+                Ok(Ok(lag)) if lag <= max_lag => None,
+                Ok(Ok(lag)) => Some(format!("{}ms behind", lag.as_millis())),
+                Ok(Err(err)) => Some(err.to_string()),
+                Err(_) => Some(format!("timed out after {}ms", limit.as_millis())),
+            };
+
+            let healthy = problem.is_none();
+            let was_healthy = replica.healthy.swap(healthy, Ordering::AcqRel);
+            match problem {
+                Some(problem) if was_healthy || first => {
+                    warn!("{:<12} - reads on the primary, {problem}", "REPLICA");
+                }
+                None if !was_healthy => info!("{:<12} - serving reads", "REPLICA"),
+                _ => {}
+            }
+            metrics().set_replica_healthy(healthy);
+            first = false;
+
+            sleep(every).await;
+        }
+    });
+}
+
+/// Replay lag, zero once everything received is replayed. A primary, as
+/// in local setups pointing both pools at one server, reports none.
+async fn lag(db: &Db) -> sqlx::Result<Duration> {
+    let query = "select coalesce(case when pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() then 0 else extract(epoch from now() - pg_last_xact_replay_timestamp()) end, 0)::float8";
+    let secs = sqlx::query_scalar::<_, f64>(query).fetch_one(db).await?;
+
+    Ok(Duration::from_secs_f64(secs.max(0.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_follow_the_method_and_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Reads::for_request(true, &headers), Reads::Replica);
+        assert_eq!(Reads::for_request(false, &headers), Reads::Primary);
+
+        headers.insert(READ_YOUR_WRITES_HEADER, HeaderValue::from_static("TRUE"));
+        assert_eq!(Reads::for_request(true, &headers), Reads::OwnWrites);
+
+        assert_eq!(Reads::current(), Reads::Primary);
+        let inside = with_reads(Reads::Replica, async { Reads::current() }).await;
+        assert_eq!(inside, Reads::Replica);
+    }
+}
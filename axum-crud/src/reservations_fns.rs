@@ -0,0 +1,463 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    config::core_config,
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+    orders_fns::{insert_order, place_line, OrderPlaced},
+    stock_fns::{lock_stock, reserved_stock},
+    units::Unit,
+};
+
+/// Reservations are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct ReservationModelController;
+
+#[derive(Debug, Serialize)]
+pub struct ReservationToCreate {
+    pub food_id: i64,
+    pub store_id: Option<i64>,
+    /// In the food's base unit.
+    pub quantity: i32,
+    /// `RESERVATION_TTL_SEC` when absent.
+    pub ttl_sec: Option<u64>,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct ReservationToSelect {
+    pub id: i64,
+    pub food_id: i64,
+    pub store_id: Option<i64>,
+    pub quantity: i32,
+    pub reservation_status: String,
+    /// RFC 3339, UTC.
+    pub expires_at: String,
+    /// Order the reservation was confirmed into.
+    pub order_id: Option<i64>,
+}
+
+/// Stock that can still be ordered or reserved.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct FoodAvailability {
+    pub food_id: i64,
+    pub store_id: Option<i64>,
+    pub stocks: i32,
+    /// Held by reservations that have not expired.
+    pub reserved: i32,
+    pub available: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReservationConfirmed {
+    pub reservation_id: i64,
+    pub order: OrderPlaced,
+}
+
+const RESERVATION_COLUMNS: &str = "id, food_id, store_id, quantity, reservation_status::text as reservation_status, to_char(expires_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as expires_at, order_id";
+
+impl ReservationModelController {
+    /// Holds stock without taking it, if enough is available.
+    pub async fn create(
+        mm: ModelController,
+        data: ReservationToCreate,
+    ) -> Result<ReservationToSelect> {
+        debug!("{:<12} - create_reservation", "HANDLER");
+
+        if data.quantity <= 0 {
+            return Err(Error::InvalidReservation(String::from(
+                "quantity must be positive",
+            )));
+        }
+        if data.ttl_sec == Some(0) {
+            return Err(Error::InvalidReservation(String::from(
+                "ttl_sec must be positive",
+            )));
+        }
+
+        let timer = metrics().query_timer("create_reservation");
+        timer.observe(create(&mm, data).await)
+    }
+
+    pub async fn get_by_id(mm: ModelController, id: i64) -> Result<ReservationToSelect> {
+        debug!("{:<12} - get_reservation", "HANDLER");
+
+        let timer = metrics().query_timer("get_reservation");
+        let query = format!("select {RESERVATION_COLUMNS} from reservations where id = $1");
+
+        let res = sqlx::query_as::<_, ReservationToSelect>(&query)
+            .bind(id)
+            .fetch_one(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - get_reservation error", "ERROR_CONTROLLER");
+                Error::row_not_found(err, Error::ReservationNotFound(id.to_string()))
+            });
+        timer.observe(res)
+    }
+
+    /// Gives the held stock back.
+    pub async fn release(mm: ModelController, id: i64) -> Result<ReservationToSelect> {
+        debug!("{:<12} - release_reservation", "HANDLER");
+
+        let timer = metrics().query_timer("release_reservation");
+        let query = format!("update reservations set reservation_status = 'released', mtime = now() where id = $1 and reservation_status = 'held' returning {RESERVATION_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, ReservationToSelect>(&query)
+            .bind(id)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(reservation)) => Ok(reservation),
+            Ok(None) => Err(Error::ReservationFailed(format!(
+                "reservation {id} is not held"
+            ))),
+            Err(err) => {
+                debug!("{:<12} - release_reservation error", "ERROR_CONTROLLER");
+                Err(Error::UpdateFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+
+    /// Turns a held reservation into an order, taking the stock.
+    pub async fn confirm(mm: ModelController, id: i64) -> Result<ReservationConfirmed> {
+        debug!("{:<12} - confirm_reservation", "HANDLER");
+
+        let timer = metrics().query_timer("confirm_reservation");
+        timer.observe(confirm(&mm, id).await)
+    }
+
+    pub async fn availability(
+        mm: ModelController,
+        food_id: i64,
+        store_id: Option<i64>,
+    ) -> Result<FoodAvailability> {
+        debug!("{:<12} - food_availability", "HANDLER");
+
+        let timer = metrics().query_timer("food_availability");
+        let query = "select f.id as food_id, $2::bigint as store_id, x.stocks, x.reserved, x.stocks - x.reserved as available from foods_table f left join food_stocks s on s.food_id = f.id and s.store_id = $2 cross join lateral (select case when $2::bigint is null then f.stocks else coalesce(s.stocks, 0) end as stocks, (select coalesce(sum(r.quantity), 0)::int from reservations r where r.food_id = f.id and r.store_id is not distinct from $2 and r.reservation_status = 'held' and r.expires_at > now()) as reserved) x where f.id = $1";
+
+        let res = sqlx::query_as::<_, FoodAvailability>(query)
+            .bind(food_id)
+            .bind(store_id)
+            .fetch_one(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - food_availability error", "ERROR_CONTROLLER");
+                Error::row_not_found(
+                    err,
+                    Error::FoodIdNotFound(format!("no food with id {food_id}")),
+                )
+            });
+        timer.observe(res)
+    }
+
+    /// Marks held reservations past their expiry as expired. Their stock
+    /// is already available again, this only settles their status.
+    pub async fn sweep(mm: &ModelController) -> Result<u64> {
+        let timer = metrics().query_timer("sweep_reservations");
+        let query = "update reservations set reservation_status = 'expired', mtime = now() where reservation_status = 'held' and expires_at <= now()";
+
+        let res = sqlx::query(query)
+            .execute(mm.db()?)
+            .await
+            .map(|done| done.rows_affected())
+            .map_err(|err| {
+                debug!("{:<12} - sweep_reservations error", "ERROR_CONTROLLER");
+                Error::UpdateFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+}
+
+async fn create(mm: &ModelController, data: ReservationToCreate) -> Result<ReservationToSelect> {
+    let ReservationToCreate {
+        food_id,
+        store_id,
+        quantity,
+        ttl_sec,
+    } = data;
+    let ttl_sec = ttl_sec.unwrap_or(core_config().RESERVATION_TTL_SEC);
+
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    // The stock row lock serializes reservations of the same food.
+    let stocks = lock_stock(&mut tx, food_id, store_id).await?;
+    let reserved = reserved_stock(&mut tx, food_id, store_id).await?;
+    if stocks - reserved < quantity {
+        return Err(Error::InsufficientStock(format!(
+            "less than {quantity} of food {food_id} available"
+        )));
+    }
+
+    let query = format!("insert into reservations (food_id, store_id, quantity, expires_at) values ($1, $2, $3, now() + make_interval(secs => $4)) returning {RESERVATION_COLUMNS}");
+    let reservation = match sqlx::query_as::<_, ReservationToSelect>(&query)
+        .bind(food_id)
+        .bind(store_id)
+        .bind(quantity)
+        .bind(ttl_sec as f64)
+        .fetch_one(&mut *tx)
+        .await
+    {
+        Ok(reservation) => reservation,
+        Err(err) => {
+            debug!(
+                "{:<12} - create_reservation error {err:?}",
+                "ERROR_CONTROLLER"
+            );
+            return Err(Error::CreateFailed(err.to_string()));
+        }
+    };
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(reservation)
+}
+
+async fn confirm(mm: &ModelController, id: i64) -> Result<ReservationConfirmed> {
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let query = "select r.food_id, r.store_id, r.quantity, r.reservation_status::text, r.expires_at > now(), f.unit from reservations r join foods_table f on f.id = r.food_id where r.id = $1 for update of r";
+    let (food_id, store_id, quantity, status, live, unit) =
+        match sqlx::query_as::<_, (i64, Option<i64>, i32, String, bool, Unit)>(query)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(Some(row)) => row,
+            Ok(None) => return Err(Error::ReservationNotFound(id.to_string())),
+            Err(err) => {
+                debug!("{:<12} - confirm_reservation error", "ERROR_CONTROLLER");
+                return Err(Error::UpdateFailed(err.to_string()));
+            }
+        };
+
+    if status != "held" {
+        return Err(Error::ReservationFailed(format!(
+            "reservation {id} is {status}"
+        )));
+    }
+
+    // -- Settled before taking the stock, so its own hold does not block it.
+    let new_status = if live { "confirmed" } else { "expired" };
+    let query = "update reservations set reservation_status = $2::reservation_stat, mtime = now() where id = $1";
+    if let Err(err) = sqlx::query(query)
+        .bind(id)
+        .bind(new_status)
+        .execute(&mut *tx)
+        .await
+    {
+        debug!("{:<12} - confirm_reservation error", "ERROR_CONTROLLER");
+        return Err(Error::UpdateFailed(err.to_string()));
+    }
+
+    if !live {
+        tx.commit()
+            .await
+            .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+        return Err(Error::ReservationFailed(format!(
+            "reservation {id} has expired"
+        )));
+    }
+
+    let order_id = insert_order(&mut tx, store_id).await?;
+    let line = place_line(&mut tx, order_id, store_id, food_id, quantity, unit).await?;
+
+    let query = "update reservations set order_id = $2 where id = $1";
+    if let Err(err) = sqlx::query(query)
+        .bind(id)
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await
+    {
+        debug!("{:<12} - confirm_reservation error", "ERROR_CONTROLLER");
+        return Err(Error::UpdateFailed(err.to_string()));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(ReservationConfirmed {
+        reservation_id: id,
+        order: OrderPlaced {
+            order_id,
+            store_id,
+            lines: vec![line],
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        crud_fns::{FoodModelController, FoodToCreate},
+        stores_fns::{StockTransfer, StoreModelController, StoreToCreate},
+    };
+
+    async fn food(mm: &ModelController, stocks: i32) -> i64 {
+        FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", stocks), None)
+            .await
+            .unwrap()
+    }
+
+    async fn hold(
+        mm: &ModelController,
+        food_id: i64,
+        quantity: i32,
+    ) -> Result<ReservationToSelect> {
+        let data = ReservationToCreate {
+            food_id,
+            store_id: None,
+            quantity,
+            ttl_sec: None,
+        };
+        ReservationModelController::create(mm.clone(), data).await
+    }
+
+    async fn expire(db: &PgPool, id: i64) {
+        sqlx::query(
+            "update reservations set expires_at = now() - interval '1 second' where id = $1",
+        )
+        .bind(id)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn hold_reduces_available_stock(db: PgPool) {
+        let mm = ModelController::with_db(db);
+        let food_id = food(&mm, 10).await;
+
+        hold(&mm, food_id, 4).await.unwrap();
+        let availability = ReservationModelController::availability(mm.clone(), food_id, None)
+            .await
+            .unwrap();
+        assert_eq!(availability.stocks, 10);
+        assert_eq!(availability.reserved, 4);
+        assert_eq!(availability.available, 6);
+
+        let res = hold(&mm, food_id, 7).await;
+        assert!(matches!(res, Err(Error::InsufficientStock(_))));
+    }
+
+    #[sqlx::test]
+    async fn transfers_leave_held_stock_in_place(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id = food(&mm, 0).await;
+        let store = |store_name: &str| {
+            let data = StoreToCreate {
+                store_name: store_name.to_string(),
+                address: None,
+            };
+            StoreModelController::create(mm.clone(), data)
+        };
+        let from_store_id = store("North").await.unwrap();
+        let to_store_id = store("South").await.unwrap();
+        sqlx::query("insert into food_stocks (food_id, store_id, stocks) values ($1, $2, 10)")
+            .bind(food_id)
+            .bind(from_store_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let data = ReservationToCreate {
+            food_id,
+            store_id: Some(from_store_id),
+            quantity: 4,
+            ttl_sec: None,
+        };
+        let reservation = ReservationModelController::create(mm.clone(), data)
+            .await
+            .unwrap();
+
+        let transfer = |quantity| StockTransfer {
+            food_id,
+            from_store_id,
+            to_store_id,
+            quantity,
+        };
+        let res = StoreModelController::transfer(mm.clone(), transfer(7)).await;
+        assert!(matches!(res, Err(Error::InsufficientStock(_))));
+        let moved = StoreModelController::transfer(mm.clone(), transfer(6))
+            .await
+            .unwrap();
+        assert_eq!(moved.from_stocks, 4);
+
+        let confirmed = ReservationModelController::confirm(mm.clone(), reservation.id)
+            .await
+            .unwrap();
+        assert_eq!(confirmed.order.lines[0].stocks, 0);
+    }
+
+    #[sqlx::test]
+    async fn confirm_after_expiry_is_rejected(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id = food(&mm, 10).await;
+        let reservation = hold(&mm, food_id, 4).await.unwrap();
+        expire(&db, reservation.id).await;
+
+        let res = ReservationModelController::confirm(mm.clone(), reservation.id).await;
+        assert!(matches!(res, Err(Error::ReservationFailed(_))));
+
+        let reservation = ReservationModelController::get_by_id(mm.clone(), reservation.id)
+            .await
+            .unwrap();
+        assert_eq!(reservation.reservation_status, "expired");
+        assert_eq!(reservation.order_id, None);
+        let availability = ReservationModelController::availability(mm.clone(), food_id, None)
+            .await
+            .unwrap();
+        assert_eq!(availability.stocks, 10);
+    }
+
+    #[sqlx::test]
+    async fn sweep_only_settles_the_status(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id = food(&mm, 10).await;
+        let expired = hold(&mm, food_id, 4).await.unwrap();
+        let live = hold(&mm, food_id, 3).await.unwrap();
+        expire(&db, expired.id).await;
+
+        let before = ReservationModelController::availability(mm.clone(), food_id, None)
+            .await
+            .unwrap();
+        assert_eq!(before.available, 7);
+
+        assert_eq!(ReservationModelController::sweep(&mm).await.unwrap(), 1);
+
+        let after = ReservationModelController::availability(mm.clone(), food_id, None)
+            .await
+            .unwrap();
+        assert_eq!(after.stocks, 10);
+        assert_eq!(after.available, before.available);
+        let status = |id| {
+            let mm = mm.clone();
+            async move {
+                ReservationModelController::get_by_id(mm, id)
+                    .await
+                    .unwrap()
+                    .reservation_status
+            }
+        };
+        assert_eq!(status(expired.id).await, "expired");
+        assert_eq!(status(live.id).await, "held");
+    }
+}
@@ -0,0 +1,186 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    crud_fns::{FoodModelController, ModelController},
+    crud_routes::DataBody,
+    error::Result,
+    reservations_fns::{
+        FoodAvailability, ReservationConfirmed, ReservationModelController, ReservationToCreate,
+        ReservationToSelect,
+    },
+    units::Unit,
+};
+
+pub fn routes_reservations(mm: ModelController) -> Router {
+    Router::new()
+        .route("/api/v1/reservations", post(api_v1_create_reservation))
+        .route(
+            "/api/v1/reservations/:id",
+            get(api_v1_get_reservation).delete(api_v1_release_reservation),
+        )
+        .route(
+            "/api/v1/reservations/:id/confirm",
+            post(api_v1_confirm_reservation),
+        )
+        .route(
+            "/api/v1/foods/:id/availability",
+            get(api_v1_food_availability),
+        )
+        .with_state(mm)
+}
+
+/// `?store_id=` scoping availability to one store.
+#[derive(Debug, Deserialize, IntoParams)]
+struct AvailabilityScope {
+    /// Report this store's stock instead of the food's own stock.
+    store_id: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct CreateReservationPayload {
+    food_id: i64,
+    quantity: i32,
+    /// Unit `quantity` is given in, the food's base unit when absent.
+    quantity_unit: Option<Unit>,
+    /// Hold this store's stock instead of the food's own stock.
+    store_id: Option<i64>,
+    /// Seconds until the hold lapses, the server default when absent.
+    ttl_sec: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/reservations",
+    tag = "reservations",
+    request_body = CreateReservationPayload,
+    responses(
+        (status = 201, description = "Stock held until `expires_at`", body = DataBody<ReservationToSelect>,
+            headers(("location" = String, description = "URL of the reservation"))),
+        (status = 400, description = "Quantity or ttl_sec not positive"),
+        (status = 409, description = "Insufficient available stock"),
+    )
+)]
+async fn api_v1_create_reservation(
+    State(mm): State<ModelController>,
+    Json(body): Json<CreateReservationPayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_create_reservation", "ROUTE_HANDLER");
+
+    let CreateReservationPayload {
+        food_id,
+        quantity,
+        quantity_unit,
+        store_id,
+        ttl_sec,
+    } = body;
+    let (quantity, _) =
+        FoodModelController::to_base_quantity(&mm, food_id, quantity, quantity_unit).await?;
+    let data = ReservationToCreate {
+        food_id,
+        store_id,
+        quantity,
+        ttl_sec,
+    };
+
+    let reservation = ReservationModelController::create(mm, data).await?;
+    let location = format!("/api/v1/reservations/{}", reservation.id);
+    let body = Json(DataBody::new(reservation));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/reservations/{id}",
+    tag = "reservations",
+    params(("id" = i64, Path, description = "Reservation id")),
+    responses(
+        (status = 200, description = "Reservation found", body = DataBody<ReservationToSelect>),
+        (status = 404, description = "Reservation not found"),
+    )
+)]
+async fn api_v1_get_reservation(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<ReservationToSelect>>> {
+    debug!("{:<12} - api_v1_get_reservation", "ROUTE_HANDLER");
+
+    let reservation = ReservationModelController::get_by_id(mm, id).await?;
+    let body = Json(DataBody::new(reservation));
+    Ok(body)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/reservations/{id}",
+    tag = "reservations",
+    params(("id" = i64, Path, description = "Reservation id")),
+    responses(
+        (status = 200, description = "Held stock released", body = DataBody<ReservationToSelect>),
+        (status = 409, description = "Reservation is not held"),
+    )
+)]
+async fn api_v1_release_reservation(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<ReservationToSelect>>> {
+    debug!("{:<12} - api_v1_release_reservation", "ROUTE_HANDLER");
+
+    let reservation = ReservationModelController::release(mm, id).await?;
+    let body = Json(DataBody::new(reservation));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/reservations/{id}/confirm",
+    tag = "reservations",
+    params(("id" = i64, Path, description = "Reservation id")),
+    responses(
+        (status = 200, description = "Reservation turned into an order", body = DataBody<ReservationConfirmed>),
+        (status = 404, description = "Reservation not found"),
+        (status = 409, description = "Reservation is not held or has expired"),
+    )
+)]
+async fn api_v1_confirm_reservation(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<ReservationConfirmed>>> {
+    debug!("{:<12} - api_v1_confirm_reservation", "ROUTE_HANDLER");
+
+    let confirmed = ReservationModelController::confirm(mm, id).await?;
+    let body = Json(DataBody::new(confirmed));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/availability",
+    tag = "reservations",
+    params(("id" = i64, Path, description = "Food id"), AvailabilityScope),
+    responses(
+        (status = 200, description = "Stock, reserved and available quantities", body = DataBody<FoodAvailability>),
+        (status = 404, description = "Food not found"),
+    )
+)]
+async fn api_v1_food_availability(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+    Query(scope): Query<AvailabilityScope>,
+) -> Result<Json<DataBody<FoodAvailability>>> {
+    debug!("{:<12} - api_v1_food_availability", "ROUTE_HANDLER");
+
+    let availability =
+        ReservationModelController::availability(mm, food_id, scope.store_id).await?;
+    let body = Json(DataBody::new(availability));
+    Ok(body)
+}
@@ -0,0 +1,59 @@
+use std::io::Write;
+
+use tokio::{
+    signal,
+    sync::watch,
+    time::{sleep_until, Instant},
+};
+use tracing::{info, warn};
+
+/// Resolves once the process receives Ctrl+C (SIGINT) or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("{:<12} - received SIGINT", "SHUTDOWN"),
+        _ = terminate => info!("{:<12} - received SIGTERM", "SHUTDOWN"),
+    }
+}
+
+/// Resolves at the deadline set when the shutdown signal was received, i.e.
+/// when in-flight requests had their chance to drain.
+///
+/// Never resolves if no deadline is ever set (sender dropped).
+pub async fn drain_deadline(mut deadline: watch::Receiver<Option<Instant>>) {
+    let deadline = match deadline.wait_for(Option::is_some).await {
+        Ok(deadline) => deadline.unwrap_or_else(Instant::now),
+        Err(_) => return std::future::pending().await,
+    };
+
+    info!(
+        "{:<12} - draining in-flight requests (deadline {:.0}s)",
+        "SHUTDOWN",
+        deadline
+            .saturating_duration_since(Instant::now())
+            .as_secs_f64()
+    );
+    sleep_until(deadline).await;
+    warn!("{:<12} - drain deadline elapsed", "SHUTDOWN");
+}
+
+/// Flushes the request log sink (stdout) before exit.
+pub fn flush_logs() {
+    let _ = std::io::stdout().flush();
+}
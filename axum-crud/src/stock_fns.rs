@@ -0,0 +1,206 @@
+//! Stock level changes made inside a caller's transaction.
+//!
+//! A food's stock lives on `foods_table.stocks`, or in `food_stocks` when a
+//! store is given. Every change is also written to the `stock_movements`
+//! ledger. Held reservations are part of the stock but cannot be taken.
+
+use sqlx::PgConnection;
+use tracing::debug;
+
+use crate::error::{Error, Result};
+
+/// Current stock, locked until the transaction ends.
+pub(crate) async fn lock_stock(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+) -> Result<i32> {
+    let res = match store_id {
+        Some(store_id) => {
+            let query =
+                "select stocks from food_stocks where food_id = $1 and store_id = $2 for update";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(store_id)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+        None => {
+            let query = "select stocks from foods_table where id = $1 and food_status != 'removed' for update";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+    };
+
+    match res {
+        Ok(stocks) => Ok(stocks.unwrap_or(0)),
+        Err(err) => {
+            debug!("{:<12} - lock_stock error", "ERROR_CONTROLLER");
+            Err(Error::SelectFailed(err.to_string()))
+        }
+    }
+}
+
+/// Stock held by reservations that have not expired.
+pub(crate) async fn reserved_stock(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+) -> Result<i32> {
+    let query = "select coalesce(sum(quantity), 0)::int from reservations where food_id = $1 and store_id is not distinct from $2 and reservation_status = 'held' and expires_at > now()";
+
+    sqlx::query_scalar::<_, i32>(query)
+        .bind(food_id)
+        .bind(store_id)
+        .fetch_one(conn)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - reserved_stock error", "ERROR_CONTROLLER");
+            Error::SelectFailed(err.to_string())
+        })
+}
+
+/// Takes `quantity` out of the stock, refusing to go below what held
+/// reservations need. Returns the stock left.
+pub(crate) async fn take_stock(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    quantity: i32,
+) -> Result<i32> {
+    let res = match store_id {
+        Some(store_id) => {
+            let query = "update food_stocks set stocks = stocks - $3 where food_id = $1 and store_id = $2 and stocks - (select coalesce(sum(quantity), 0) from reservations where food_id = $1 and store_id = $2 and reservation_status = 'held' and expires_at > now()) >= $3 returning stocks";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(store_id)
+                .bind(quantity)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+        None => {
+            let query = "update foods_table set stocks = stocks - $2 where id = $1 and food_status != 'removed' and stocks - (select coalesce(sum(quantity), 0) from reservations where food_id = $1 and store_id is null and reservation_status = 'held' and expires_at > now()) >= $2 returning stocks";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(quantity)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+    };
+
+    match res {
+        Ok(Some(stocks)) => Ok(stocks),
+        Ok(None) => Err(Error::InsufficientStock(format!(
+            "less than {quantity} of food {food_id} available"
+        ))),
+        Err(err) => {
+            debug!("{:<12} - take_stock error", "ERROR_CONTROLLER");
+            Err(Error::UpdateFailed(err.to_string()))
+        }
+    }
+}
+
+/// Takes at most `quantity` out of the stock, stopping at zero.
+/// Returns how much was actually taken.
+pub(crate) async fn take_stock_upto(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    quantity: i32,
+) -> Result<i32> {
+    // `old` is read under the row lock taken by the update.
+    let res = match store_id {
+        Some(store_id) => {
+            let query = "update food_stocks s set stocks = greatest(s.stocks - $3, 0) from (select stocks from food_stocks where food_id = $1 and store_id = $2 for update) old where s.food_id = $1 and s.store_id = $2 returning old.stocks - s.stocks";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(store_id)
+                .bind(quantity)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+        None => {
+            let query = "update foods_table f set stocks = greatest(f.stocks - $2, 0) from (select stocks from foods_table where id = $1 for update) old where f.id = $1 returning old.stocks - f.stocks";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(quantity)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+    };
+
+    match res {
+        Ok(taken) => Ok(taken.unwrap_or(0)),
+        Err(err) => {
+            debug!("{:<12} - take_stock_upto error", "ERROR_CONTROLLER");
+            Err(Error::UpdateFailed(err.to_string()))
+        }
+    }
+}
+
+/// Adds `quantity` to the stock. Returns the new stock.
+pub(crate) async fn put_stock(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    quantity: i32,
+) -> Result<i32> {
+    let res = match store_id {
+        Some(store_id) => {
+            let query = "insert into food_stocks (food_id, store_id, stocks) values ($1, $2, $3) on conflict (food_id, store_id) do update set stocks = food_stocks.stocks + excluded.stocks returning stocks";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(store_id)
+                .bind(quantity)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+        None => {
+            let query = "update foods_table set stocks = stocks + $2 where id = $1 and food_status != 'removed' returning stocks";
+            sqlx::query_scalar::<_, i32>(query)
+                .bind(food_id)
+                .bind(quantity)
+                .fetch_optional(&mut *conn)
+                .await
+        }
+    };
+
+    match res {
+        Ok(Some(stocks)) => Ok(stocks),
+        Ok(None) => Err(Error::FoodIdNotFound(format!("no food with id {food_id}"))),
+        Err(err) => {
+            debug!("{:<12} - put_stock error", "ERROR_CONTROLLER");
+            Err(Error::UpdateFailed(err.to_string()))
+        }
+    }
+}
+
+/// Appends a signed change to the `stock_movements` ledger.
+pub(crate) async fn record_movement(
+    conn: &mut PgConnection,
+    food_id: i64,
+    store_id: Option<i64>,
+    delta: i32,
+    reason: &str,
+    lot_id: Option<i64>,
+) -> Result<()> {
+    let query = "insert into stock_movements (food_id, store_id, delta, reason, lot_id) values ($1, $2, $3, $4, $5)";
+
+    match sqlx::query(query)
+        .bind(food_id)
+        .bind(store_id)
+        .bind(delta)
+        .bind(reason)
+        .bind(lot_id)
+        .execute(conn)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            debug!("{:<12} - record_movement error", "ERROR_CONTROLLER");
+            Err(Error::UpdateFailed(err.to_string()))
+        }
+    }
+}
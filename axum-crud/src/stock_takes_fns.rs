@@ -0,0 +1,505 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{prelude::FromRow, PgConnection};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+    stock_fns::{lock_stock, put_stock, record_movement, take_stock_upto},
+};
+
+/// Stock takes are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct StockTakeModelController;
+
+#[derive(Debug, Serialize)]
+pub struct StockTakeToOpen {
+    /// Count this store's stock instead of the foods' own stock.
+    pub store_id: Option<i64>,
+    /// Only foods of this category can be counted.
+    pub category: Option<String>,
+    pub note: Option<String>,
+    pub opened_by: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CountToSubmit {
+    pub food_id: i64,
+    /// In the food's base unit.
+    pub counted: i32,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct StockTakeToSelect {
+    pub id: i64,
+    pub store_id: Option<i64>,
+    pub category: Option<String>,
+    pub note: Option<String>,
+    pub stock_take_status: String,
+    /// Name of the API key that opened the session.
+    pub opened_by: Option<String>,
+    /// Name of the API key that approved or cancelled it.
+    pub closed_by: Option<String>,
+    /// RFC 3339, UTC.
+    pub opened_at: String,
+    pub closed_at: Option<String>,
+    #[sqlx(skip)]
+    pub lines: Vec<StockTakeLine>,
+}
+
+/// A counted food and how far the count is from the stock.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct StockTakeLine {
+    pub food_id: i64,
+    pub food_name: String,
+    pub counted: i32,
+    /// The stock when the food was counted.
+    pub expected: i32,
+    /// `counted - expected`, negative when stock is missing.
+    pub variance: i32,
+}
+
+const STOCK_TAKE_COLUMNS: &str = "id, store_id, category, note, stock_take_status::text as stock_take_status, opened_by, closed_by, to_char(ctime at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as opened_at, to_char(closed_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as closed_at";
+
+impl StockTakeModelController {
+    pub async fn open(mm: ModelController, data: StockTakeToOpen) -> Result<StockTakeToSelect> {
+        debug!("{:<12} - open_stock_take", "HANDLER");
+
+        let timer = metrics().query_timer("open_stock_take");
+        let query = format!("insert into stock_takes (store_id, category, note, opened_by) values ($1, $2, $3, $4) returning {STOCK_TAKE_COLUMNS}");
+
+        let res = sqlx::query_as::<_, StockTakeToSelect>(&query)
+            .bind(data.store_id)
+            .bind(data.category)
+            .bind(data.note)
+            .bind(data.opened_by)
+            .fetch_one(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - open_stock_take error", "ERROR_CONTROLLER");
+                Error::CreateFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// The session with its counted lines, for review before approval.
+    pub async fn get_by_id(mm: ModelController, id: i64) -> Result<StockTakeToSelect> {
+        debug!("{:<12} - get_stock_take", "HANDLER");
+
+        let timer = metrics().query_timer("get_stock_take");
+        let res = async {
+            let conn = &mut *mm
+                .db()?
+                .acquire()
+                .await
+                .map_err(|err| Error::SelectFailed(err.to_string()))?;
+            let mut stock_take = select_stock_take(conn, id, false).await?;
+            stock_take.lines = select_lines(conn, id).await?;
+            Ok(stock_take)
+        }
+        .await;
+        timer.observe(res)
+    }
+
+    /// Records counted quantities along with the stock at that moment,
+    /// replacing earlier counts of the same foods. Only open sessions take
+    /// counts.
+    pub async fn submit_counts(
+        mm: ModelController,
+        id: i64,
+        counts: Vec<CountToSubmit>,
+    ) -> Result<StockTakeToSelect> {
+        debug!("{:<12} - submit_stock_take_counts", "HANDLER");
+
+        if counts.is_empty() {
+            return Err(Error::InvalidStockTake(String::from("no counts given")));
+        }
+        if let Some(count) = counts.iter().find(|count| count.counted < 0) {
+            return Err(Error::InvalidStockTake(format!(
+                "count of food {} is negative",
+                count.food_id
+            )));
+        }
+
+        let timer = metrics().query_timer("submit_stock_take_counts");
+        timer.observe(submit_counts(&mm, id, counts).await)
+    }
+
+    /// Applies the variance of every count, against the stock when it was
+    /// counted, to the current stock, booking it as a `stock_take`
+    /// movement. Sales and receipts made since the count are kept.
+    pub async fn approve(
+        mm: ModelController,
+        id: i64,
+        closed_by: Option<String>,
+    ) -> Result<StockTakeToSelect> {
+        debug!("{:<12} - approve_stock_take", "HANDLER");
+
+        let timer = metrics().query_timer("approve_stock_take");
+        timer.observe(approve(&mm, id, closed_by).await)
+    }
+
+    /// Closes the session without touching the stock.
+    pub async fn cancel(
+        mm: ModelController,
+        id: i64,
+        closed_by: Option<String>,
+    ) -> Result<StockTakeToSelect> {
+        debug!("{:<12} - cancel_stock_take", "HANDLER");
+
+        let timer = metrics().query_timer("cancel_stock_take");
+        let query = format!("update stock_takes set stock_take_status = 'cancelled', closed_by = $2, closed_at = now(), mtime = now() where id = $1 and stock_take_status = 'open' returning {STOCK_TAKE_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, StockTakeToSelect>(&query)
+            .bind(id)
+            .bind(closed_by)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(stock_take)) => Ok(stock_take),
+            Ok(None) => Err(Error::StockTakeFailed(format!(
+                "stock take {id} is not open"
+            ))),
+            Err(err) => {
+                debug!("{:<12} - cancel_stock_take error", "ERROR_CONTROLLER");
+                Err(Error::UpdateFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+}
+
+/// The session, locked until the transaction ends when `lock` is set.
+async fn select_stock_take(
+    conn: &mut PgConnection,
+    id: i64,
+    lock: bool,
+) -> Result<StockTakeToSelect> {
+    let lock = if lock { " for update" } else { "" };
+    let query = format!("select {STOCK_TAKE_COLUMNS} from stock_takes where id = $1{lock}");
+
+    match sqlx::query_as::<_, StockTakeToSelect>(&query)
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+    {
+        Ok(Some(stock_take)) => Ok(stock_take),
+        Ok(None) => Err(Error::StockTakeNotFound(format!(
+            "no stock take with id {id}"
+        ))),
+        Err(err) => {
+            debug!("{:<12} - select_stock_take error", "ERROR_CONTROLLER");
+            Err(Error::SelectFailed(err.to_string()))
+        }
+    }
+}
+
+async fn select_lines(conn: &mut PgConnection, id: i64) -> Result<Vec<StockTakeLine>> {
+    // -- Counts taken before the stock was saved with them compare against
+    //    the live stock.
+    let query = "select food_id, food_name, counted, expected, counted - expected as variance from (select c.food_id, f.food_name, c.counted, coalesce(c.expected, case when t.store_id is null then f.stocks else coalesce(s.stocks, 0) end) as expected from stock_take_counts c join stock_takes t on t.id = c.stock_take_id join foods_table f on f.id = c.food_id left join food_stocks s on s.food_id = c.food_id and s.store_id = t.store_id where c.stock_take_id = $1) lines order by food_name, food_id";
+
+    sqlx::query_as::<_, StockTakeLine>(query)
+        .bind(id)
+        .fetch_all(conn)
+        .await
+        .map_err(|err| {
+            debug!("{:<12} - select_stock_take_lines error", "ERROR_CONTROLLER");
+            Error::SelectFailed(err.to_string())
+        })
+}
+
+fn require_open(stock_take: &StockTakeToSelect) -> Result<()> {
+    if stock_take.stock_take_status != "open" {
+        return Err(Error::StockTakeFailed(format!(
+            "stock take {} is {}",
+            stock_take.id, stock_take.stock_take_status
+        )));
+    }
+
+    Ok(())
+}
+
+async fn submit_counts(
+    mm: &ModelController,
+    id: i64,
+    counts: Vec<CountToSubmit>,
+) -> Result<StockTakeToSelect> {
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let mut stock_take = select_stock_take(&mut tx, id, true).await?;
+    require_open(&stock_take)?;
+
+    // -- Every food must exist and fall in the session's category.
+    let food_ids: Vec<i64> = counts.iter().map(|count| count.food_id).collect();
+    let query = "select id from foods_table where id = any($1) and food_status != 'removed' and ($2::text is null or category = $2)";
+    let in_scope = sqlx::query_scalar::<_, i64>(query)
+        .bind(&food_ids)
+        .bind(&stock_take.category)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|err| Error::SelectFailed(err.to_string()))?;
+    if let Some(food_id) = food_ids.iter().find(|food_id| !in_scope.contains(food_id)) {
+        return Err(Error::InvalidStockTake(format!(
+            "food {food_id} is not part of stock take {id}"
+        )));
+    }
+
+    // -- The stock the count is compared with, as it was when counted.
+    let query = "insert into stock_take_counts (stock_take_id, food_id, counted, expected) select $1, f.id, $3, case when $4::bigint is null then f.stocks else coalesce((select s.stocks from food_stocks s where s.food_id = f.id and s.store_id = $4), 0) end from foods_table f where f.id = $2 on conflict (stock_take_id, food_id) do update set counted = excluded.counted, expected = excluded.expected, mtime = now()";
+    for count in &counts {
+        if let Err(err) = sqlx::query(query)
+            .bind(id)
+            .bind(count.food_id)
+            .bind(count.counted)
+            .bind(stock_take.store_id)
+            .execute(&mut *tx)
+            .await
+        {
+            debug!(
+                "{:<12} - submit_stock_take_counts error",
+                "ERROR_CONTROLLER"
+            );
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+    }
+
+    stock_take.lines = select_lines(&mut tx, id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(stock_take)
+}
+
+async fn approve(
+    mm: &ModelController,
+    id: i64,
+    closed_by: Option<String>,
+) -> Result<StockTakeToSelect> {
+    let mut tx = mm
+        .db()?
+        .begin()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    let stock_take = select_stock_take(&mut tx, id, true).await?;
+    require_open(&stock_take)?;
+
+    // -- Locked in food order, so overlapping sessions cannot deadlock.
+    let query = "select food_id, counted, expected from stock_take_counts where stock_take_id = $1 order by food_id";
+    let counts = sqlx::query_as::<_, (i64, i32, Option<i32>)>(query)
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|err| Error::SelectFailed(err.to_string()))?;
+    if counts.is_empty() {
+        return Err(Error::StockTakeFailed(format!(
+            "stock take {id} has no counts"
+        )));
+    }
+
+    let store_id = stock_take.store_id;
+    for (food_id, counted, expected) in counts {
+        let stock = lock_stock(&mut tx, food_id, store_id).await?;
+        let expected = expected.unwrap_or(stock);
+        let variance = counted - expected;
+
+        // -- Held reservations do not keep the stock above what the count
+        //    says is on the shelf. The stock never goes below zero, so the
+        //    ledger gets what was actually taken.
+        let applied = match variance {
+            0 => 0,
+            1.. => {
+                put_stock(&mut tx, food_id, store_id, variance).await?;
+                variance
+            }
+            _ => -take_stock_upto(&mut tx, food_id, store_id, -variance).await?,
+        };
+        if applied != 0 {
+            record_movement(&mut tx, food_id, store_id, applied, "stock_take", None).await?;
+        }
+
+        let query = "update stock_take_counts set expected = $3, variance = $4, mtime = now() where stock_take_id = $1 and food_id = $2";
+        if let Err(err) = sqlx::query(query)
+            .bind(id)
+            .bind(food_id)
+            .bind(expected)
+            .bind(variance)
+            .execute(&mut *tx)
+            .await
+        {
+            debug!("{:<12} - approve_stock_take error", "ERROR_CONTROLLER");
+            return Err(Error::UpdateFailed(err.to_string()));
+        }
+    }
+
+    let query = format!("update stock_takes set stock_take_status = 'approved', closed_by = $2, closed_at = now(), mtime = now() where id = $1 returning {STOCK_TAKE_COLUMNS}");
+    let mut stock_take = sqlx::query_as::<_, StockTakeToSelect>(&query)
+        .bind(id)
+        .bind(closed_by)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+    stock_take.lines = select_lines(&mut tx, id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    Ok(stock_take)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::crud_fns::{FoodModelController, FoodToCreate};
+
+    fn count(food_id: i64, counted: i32) -> Vec<CountToSubmit> {
+        vec![CountToSubmit { food_id, counted }]
+    }
+
+    fn open_session() -> StockTakeToOpen {
+        StockTakeToOpen {
+            store_id: None,
+            category: None,
+            note: None,
+            opened_by: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn approval_keeps_changes_made_after_the_count(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", 10), None)
+                .await
+                .unwrap();
+        let stock_take = StockTakeModelController::open(mm.clone(), open_session())
+            .await
+            .unwrap();
+
+        // -- 8 on the shelf where 10 were expected, then 3 are sold.
+        let counted =
+            StockTakeModelController::submit_counts(mm.clone(), stock_take.id, count(food_id, 8))
+                .await
+                .unwrap();
+        assert_eq!(counted.lines[0].expected, 10);
+        assert_eq!(counted.lines[0].variance, -2);
+        sqlx::query("update foods_table set stocks = stocks - 3 where id = $1")
+            .bind(food_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let approved = StockTakeModelController::approve(mm.clone(), stock_take.id, None)
+            .await
+            .unwrap();
+        assert_eq!(approved.stock_take_status, "approved");
+        assert_eq!(approved.lines[0].expected, 10);
+        assert_eq!(approved.lines[0].variance, -2);
+
+        let stocks: i32 = sqlx::query_scalar("select stocks from foods_table where id = $1")
+            .bind(food_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(stocks, 5);
+        let booked: Vec<i32> = sqlx::query_scalar(
+            "select delta from stock_movements where food_id = $1 and reason = 'stock_take'",
+        )
+        .bind(food_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(booked, [-2]);
+    }
+
+    #[sqlx::test]
+    async fn ledger_books_only_the_stock_actually_taken(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", 10), None)
+                .await
+                .unwrap();
+        let stock_take = StockTakeModelController::open(mm.clone(), open_session())
+            .await
+            .unwrap();
+
+        // -- 2 on the shelf where 10 were expected, then 7 are sold.
+        StockTakeModelController::submit_counts(mm.clone(), stock_take.id, count(food_id, 2))
+            .await
+            .unwrap();
+        sqlx::query("update foods_table set stocks = stocks - 7 where id = $1")
+            .bind(food_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let approved = StockTakeModelController::approve(mm.clone(), stock_take.id, None)
+            .await
+            .unwrap();
+        assert_eq!(approved.lines[0].variance, -8);
+
+        let stocks: i32 = sqlx::query_scalar("select stocks from foods_table where id = $1")
+            .bind(food_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(stocks, 0);
+        let booked: Vec<i32> = sqlx::query_scalar(
+            "select delta from stock_movements where food_id = $1 and reason = 'stock_take'",
+        )
+        .bind(food_id)
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(booked, [-3]);
+    }
+
+    #[sqlx::test]
+    async fn recounts_compare_against_the_stock_at_recount(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", 10), None)
+                .await
+                .unwrap();
+        let stock_take = StockTakeModelController::open(mm.clone(), open_session())
+            .await
+            .unwrap();
+
+        StockTakeModelController::submit_counts(mm.clone(), stock_take.id, count(food_id, 8))
+            .await
+            .unwrap();
+        sqlx::query("update foods_table set stocks = 12 where id = $1")
+            .bind(food_id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let recounted =
+            StockTakeModelController::submit_counts(mm.clone(), stock_take.id, count(food_id, 13))
+                .await
+                .unwrap();
+        assert_eq!(recounted.lines[0].expected, 12);
+        assert_eq!(recounted.lines[0].variance, 1);
+
+        StockTakeModelController::approve(mm.clone(), stock_take.id, None)
+            .await
+            .unwrap();
+        let stocks: i32 = sqlx::query_scalar("select stocks from foods_table where id = $1")
+            .bind(food_id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(stocks, 13);
+    }
+}
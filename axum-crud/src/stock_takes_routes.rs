@@ -0,0 +1,203 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    api_keys_fns::ApiKeyPrincipal,
+    crud_fns::{FoodModelController, ModelController},
+    crud_routes::DataBody,
+    error::Result,
+    stock_takes_fns::{
+        CountToSubmit, StockTakeModelController, StockTakeToOpen, StockTakeToSelect,
+    },
+    units::Unit,
+};
+
+pub fn routes_stock_takes(mm: ModelController) -> Router {
+    Router::new()
+        .route("/api/v1/stock-takes", post(api_v1_open_stock_take))
+        .route(
+            "/api/v1/stock-takes/:id",
+            get(api_v1_get_stock_take).delete(api_v1_cancel_stock_take),
+        )
+        .route(
+            "/api/v1/stock-takes/:id/counts",
+            put(api_v1_submit_stock_take_counts),
+        )
+        .route(
+            "/api/v1/stock-takes/:id/approve",
+            post(api_v1_approve_stock_take),
+        )
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct OpenStockTakePayload {
+    /// Count this store's stock instead of the foods' own stock.
+    store_id: Option<i64>,
+    /// Only foods of this category can be counted, any food when absent.
+    category: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct StockTakeCountPayload {
+    food_id: i64,
+    /// Quantity found on the shelf.
+    counted: i32,
+    /// Unit `counted` is given in, the food's base unit when absent.
+    quantity_unit: Option<Unit>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct SubmitCountsPayload {
+    counts: Vec<StockTakeCountPayload>,
+}
+
+/// Name of the key the request was made with, kept on the session.
+fn key_name(principal: Option<Extension<ApiKeyPrincipal>>) -> Option<String> {
+    principal.map(|Extension(principal)| principal.key_name)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/stock-takes",
+    tag = "stock-takes",
+    request_body = OpenStockTakePayload,
+    responses(
+        (status = 201, description = "Session opened for counts", body = DataBody<StockTakeToSelect>,
+            headers(("location" = String, description = "URL of the session"))),
+        (status = 404, description = "Store not found"),
+    )
+)]
+async fn api_v1_open_stock_take(
+    State(mm): State<ModelController>,
+    principal: Option<Extension<ApiKeyPrincipal>>,
+    Json(body): Json<OpenStockTakePayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_open_stock_take", "ROUTE_HANDLER");
+
+    let data = StockTakeToOpen {
+        store_id: body.store_id,
+        category: body.category,
+        note: body.note,
+        opened_by: key_name(principal),
+    };
+
+    let stock_take = StockTakeModelController::open(mm, data).await?;
+    let location = format!("/api/v1/stock-takes/{}", stock_take.id);
+    let body = Json(DataBody::new(stock_take));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stock-takes/{id}",
+    tag = "stock-takes",
+    params(("id" = i64, Path, description = "Stock take id")),
+    responses(
+        (status = 200, description = "Session with the variance of every count", body = DataBody<StockTakeToSelect>),
+        (status = 404, description = "Stock take not found"),
+    )
+)]
+async fn api_v1_get_stock_take(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<StockTakeToSelect>>> {
+    debug!("{:<12} - api_v1_get_stock_take", "ROUTE_HANDLER");
+
+    let stock_take = StockTakeModelController::get_by_id(mm, id).await?;
+    let body = Json(DataBody::new(stock_take));
+    Ok(body)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/stock-takes/{id}/counts",
+    tag = "stock-takes",
+    params(("id" = i64, Path, description = "Stock take id")),
+    request_body = SubmitCountsPayload,
+    responses(
+        (status = 200, description = "Counts recorded, earlier counts of the same foods replaced", body = DataBody<StockTakeToSelect>),
+        (status = 400, description = "No counts, negative count or food outside its category"),
+        (status = 409, description = "Session not open"),
+    )
+)]
+async fn api_v1_submit_stock_take_counts(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    Json(body): Json<SubmitCountsPayload>,
+) -> Result<Json<DataBody<StockTakeToSelect>>> {
+    debug!("{:<12} - api_v1_submit_stock_take_counts", "ROUTE_HANDLER");
+
+    let mut counts = Vec::with_capacity(body.counts.len());
+    for count in body.counts {
+        let (counted, _) = FoodModelController::to_base_quantity(
+            &mm,
+            count.food_id,
+            count.counted,
+            count.quantity_unit,
+        )
+        .await?;
+        counts.push(CountToSubmit {
+            food_id: count.food_id,
+            counted,
+        });
+    }
+
+    let stock_take = StockTakeModelController::submit_counts(mm, id, counts).await?;
+    let body = Json(DataBody::new(stock_take));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/stock-takes/{id}/approve",
+    tag = "stock-takes",
+    params(("id" = i64, Path, description = "Stock take id")),
+    responses(
+        (status = 200, description = "Variances against the stock at count time applied and booked as movements", body = DataBody<StockTakeToSelect>),
+        (status = 409, description = "Session not open or without counts"),
+    )
+)]
+async fn api_v1_approve_stock_take(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    principal: Option<Extension<ApiKeyPrincipal>>,
+) -> Result<Json<DataBody<StockTakeToSelect>>> {
+    debug!("{:<12} - api_v1_approve_stock_take", "ROUTE_HANDLER");
+
+    let stock_take = StockTakeModelController::approve(mm, id, key_name(principal)).await?;
+    let body = Json(DataBody::new(stock_take));
+    Ok(body)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/stock-takes/{id}",
+    tag = "stock-takes",
+    params(("id" = i64, Path, description = "Stock take id")),
+    responses(
+        (status = 200, description = "Session cancelled, stock untouched", body = DataBody<StockTakeToSelect>),
+        (status = 409, description = "Session not open"),
+    )
+)]
+async fn api_v1_cancel_stock_take(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    principal: Option<Extension<ApiKeyPrincipal>>,
+) -> Result<Json<DataBody<StockTakeToSelect>>> {
+    debug!("{:<12} - api_v1_cancel_stock_take", "ROUTE_HANDLER");
+
+    let stock_take = StockTakeModelController::cancel(mm, id, key_name(principal)).await?;
+    let body = Json(DataBody::new(stock_take));
+    Ok(body)
+}
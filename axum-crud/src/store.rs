@@ -1,4 +1,4 @@
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, Pool, Postgres};
 
 use crate::{
     config::core_config,
@@ -7,14 +7,11 @@ use crate::{
 
 pub type Db = Pool<Postgres>;
 
+/// The migrations under `migrations/`, embedded at build time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
 pub async fn new_db_pool() -> Result<Db> {
-    let db_url = format!(
-        "postgres://{}:{}@{}/{}",
-        &core_config().DB_USER,
-        &core_config().DB_PASS,
-        &core_config().DB_HOST,
-        &core_config().DB_NAME
-    );
+    let db_url = db_url(&core_config().DB_HOST, core_config().DB_PORT);
 
     PgPoolOptions::new()
         .max_connections(5)
@@ -22,3 +19,30 @@ pub async fn new_db_pool() -> Result<Db> {
         .await
         .map_err(|ex| Error::FailToConnectPool(ex.to_string()))
 }
+
+/// Pool on the read replica, `None` when `DB_READ_HOST` is empty. It
+/// connects on first use, so a replica that is down does not stop startup.
+pub fn new_read_pool() -> Result<Option<Db>> {
+    let host = &core_config().DB_READ_HOST;
+    if host.is_empty() {
+        return Ok(None);
+    }
+    let db_url = db_url(host, core_config().DB_READ_PORT);
+
+    PgPoolOptions::new()
+        .max_connections(5)
+        .connect_lazy(&db_url)
+        .map(Some)
+        .map_err(|ex| Error::FailToConnectPool(ex.to_string()))
+}
+
+fn db_url(host: &str, port: u32) -> String {
+    format!(
+        "postgres://{}:{}@{}:{}/{}",
+        &core_config().DB_USER,
+        &core_config().DB_PASS,
+        host,
+        port,
+        &core_config().DB_NAME
+    )
+}
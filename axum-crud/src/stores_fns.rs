@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+};
+
+#[derive(Clone, Debug)]
+pub struct StoreModelController;
+
+#[derive(Debug, Serialize)]
+pub struct StoreToCreate {
+    pub store_name: String,
+    pub address: Option<String>,
+}
+
+#[derive(Clone, Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct StoreToSelect {
+    pub id: i64,
+    pub store_name: String,
+    pub address: Option<String>,
+    pub created_date: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StoreToUpdate {
+    pub id: i64,
+    pub store_name: Option<String>,
+    pub address: Option<String>,
+}
+
+/// Stock a single store holds of a food.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct FoodStoreStock {
+    pub store_id: i64,
+    pub store_name: String,
+    pub stocks: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StockTransfer {
+    pub food_id: i64,
+    pub from_store_id: i64,
+    pub to_store_id: i64,
+    pub quantity: i32,
+}
+
+/// Stock levels of both stores once the transfer is committed.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct StockTransferred {
+    pub food_id: i64,
+    pub from_store_id: i64,
+    pub from_stocks: i32,
+    pub to_store_id: i64,
+    pub to_stocks: i32,
+}
+
+impl StoreModelController {
+    pub async fn create(mm: ModelController, data: StoreToCreate) -> Result<i64> {
+        debug!("{:<12} - create_store", "HANDLER");
+
+        let timer = metrics().query_timer("create_store");
+        timer.observe(mm.foods().create_store(data).await)
+    }
+
+    pub async fn select(mm: ModelController) -> Result<Vec<StoreToSelect>> {
+        debug!("{:<12} - select_stores", "HANDLER");
+
+        let timer = metrics().query_timer("select_stores");
+        timer.observe(mm.foods().select_stores().await)
+    }
+
+    pub async fn get_by_id(mm: ModelController, id: i64) -> Result<StoreToSelect> {
+        debug!("{:<12} - get_store", "HANDLER");
+
+        let timer = metrics().query_timer("get_store");
+        timer.observe(mm.foods().get_store(id).await)
+    }
+
+    pub async fn update(mm: ModelController, data: StoreToUpdate) -> Result<StoreToSelect> {
+        debug!("{:<12} - update_store", "HANDLER");
+
+        let timer = metrics().query_timer("update_store");
+        timer.observe(mm.foods().update_store(data).await)
+    }
+
+    /// Per-store stock levels of a food.
+    pub async fn food_stocks(mm: ModelController, food_id: i64) -> Result<Vec<FoodStoreStock>> {
+        debug!("{:<12} - food_stocks", "HANDLER");
+
+        let timer = metrics().query_timer("food_stocks");
+        timer.observe(mm.foods().food_stocks(food_id).await)
+    }
+
+    /// Moves stock of a food between two stores, all or nothing.
+    pub async fn transfer(mm: ModelController, data: StockTransfer) -> Result<StockTransferred> {
+        debug!("{:<12} - transfer", "HANDLER");
+
+        if data.quantity <= 0 {
+            return Err(Error::TransferFailed(String::from(
+                "quantity must be positive",
+            )));
+        }
+        if data.from_store_id == data.to_store_id {
+            return Err(Error::TransferFailed(String::from(
+                "source and destination store are the same",
+            )));
+        }
+
+        let timer = metrics().query_timer("transfer");
+        timer.observe(mm.foods().transfer_stock(data).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::crud_fns::{FoodModelController, FoodToCreate};
+
+    async fn store(mm: &ModelController, store_name: &str) -> i64 {
+        let data = StoreToCreate {
+            store_name: store_name.to_string(),
+            address: None,
+        };
+        StoreModelController::create(mm.clone(), data)
+            .await
+            .unwrap()
+    }
+
+    async fn store_stocks(db: &PgPool, food_id: i64, store_id: i64) -> Option<i32> {
+        sqlx::query_scalar("select stocks from food_stocks where food_id = $1 and store_id = $2")
+            .bind(food_id)
+            .bind(store_id)
+            .fetch_optional(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn transfer_never_takes_more_than_the_source_holds(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", 0), None)
+                .await
+                .unwrap();
+        let from_store_id = store(&mm, "North").await;
+        let to_store_id = store(&mm, "South").await;
+        sqlx::query("insert into food_stocks (food_id, store_id, stocks) values ($1, $2, 5)")
+            .bind(food_id)
+            .bind(from_store_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let transfer = |quantity| StockTransfer {
+            food_id,
+            from_store_id,
+            to_store_id,
+            quantity,
+        };
+        let res = StoreModelController::transfer(mm.clone(), transfer(6)).await;
+        assert!(matches!(res, Err(Error::InsufficientStock(_))));
+        assert_eq!(store_stocks(&db, food_id, from_store_id).await, Some(5));
+        assert_eq!(store_stocks(&db, food_id, to_store_id).await, None);
+
+        let moved = StoreModelController::transfer(mm.clone(), transfer(5))
+            .await
+            .unwrap();
+        assert_eq!(moved.from_stocks, 0);
+        assert_eq!(moved.to_stocks, 5);
+    }
+
+    #[sqlx::test]
+    async fn transfer_to_an_unknown_store_is_rejected(db: PgPool) {
+        let mm = ModelController::with_db(db.clone());
+        let food_id =
+            FoodModelController::create(mm.clone(), FoodToCreate::sample("Rice", 0), None)
+                .await
+                .unwrap();
+        let from_store_id = store(&mm, "North").await;
+        sqlx::query("insert into food_stocks (food_id, store_id, stocks) values ($1, $2, 5)")
+            .bind(food_id)
+            .bind(from_store_id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let data = StockTransfer {
+            food_id,
+            from_store_id,
+            to_store_id: from_store_id + 1000,
+            quantity: 2,
+        };
+        let res = StoreModelController::transfer(mm.clone(), data).await;
+        assert!(matches!(res, Err(Error::StoreNotFound(_))));
+        assert_eq!(store_stocks(&db, food_id, from_store_id).await, Some(5));
+    }
+}
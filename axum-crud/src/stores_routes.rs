@@ -0,0 +1,243 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::{
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::Result,
+    stores_fns::{
+        FoodStoreStock, StockTransfer, StockTransferred, StoreModelController, StoreToCreate,
+        StoreToSelect, StoreToUpdate,
+    },
+};
+
+pub fn routes_stores(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/stores",
+            get(api_v1_list_stores).post(api_v1_create_store),
+        )
+        .route(
+            "/api/v1/stores/:id",
+            get(api_v1_get_store).patch(api_v1_patch_store),
+        )
+        .route("/api/v1/foods/:id/stocks", get(api_v1_food_stocks))
+        .route("/api/v1/stock-transfers", post(api_v1_transfer_stock))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct CreateStorePayload {
+    store_name: String,
+    address: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct PatchStorePayload {
+    store_name: Option<String>,
+    address: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct StockTransferPayload {
+    food_id: i64,
+    from_store_id: i64,
+    to_store_id: i64,
+    quantity: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StoreCreatedBody {
+    result: StoreCreatedResult,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StoreCreatedResult {
+    message: String,
+    status: bool,
+    store_id: i64,
+}
+
+impl StoreCreatedBody {
+    fn new(store_id: i64) -> Self {
+        StoreCreatedBody {
+            result: StoreCreatedResult {
+                message: String::from("success"),
+                status: true,
+                store_id,
+            },
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stores",
+    tag = "stores",
+    responses(
+        (status = 200, description = "Stores by name", body = DataBody<Vec<StoreToSelect>>),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_v1_list_stores(
+    State(mm): State<ModelController>,
+) -> Result<Json<DataBody<Vec<StoreToSelect>>>> {
+    debug!("{:<12} - api_v1_list_stores", "ROUTE_HANDLER");
+
+    let stores = StoreModelController::select(mm).await?;
+    let body = Json(DataBody::new(stores));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/stores",
+    tag = "stores",
+    request_body = CreateStorePayload,
+    responses(
+        (status = 201, description = "Store created, `Location` points to it", body = StoreCreatedBody,
+            headers(("location" = String, description = "URL of the created store"))),
+        (status = 500, description = "Create failed"),
+    )
+)]
+async fn api_v1_create_store(
+    State(mm): State<ModelController>,
+    Json(body): Json<CreateStorePayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_create_store", "ROUTE_HANDLER");
+
+    let CreateStorePayload {
+        store_name,
+        address,
+    } = body;
+    let data = StoreToCreate {
+        store_name,
+        address,
+    };
+
+    let store_id = StoreModelController::create(mm, data).await?;
+    let location = format!("/api/v1/stores/{store_id}");
+    let body = Json(StoreCreatedBody::new(store_id));
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)], body).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stores/{id}",
+    tag = "stores",
+    params(("id" = i64, Path, description = "Store id")),
+    responses(
+        (status = 200, description = "Store found", body = DataBody<StoreToSelect>),
+        (status = 404, description = "Store not found"),
+    )
+)]
+async fn api_v1_get_store(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<StoreToSelect>>> {
+    debug!("{:<12} - api_v1_get_store", "ROUTE_HANDLER");
+
+    let store = StoreModelController::get_by_id(mm, id).await?;
+    let body = Json(DataBody::new(store));
+    Ok(body)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/stores/{id}",
+    tag = "stores",
+    params(("id" = i64, Path, description = "Store id")),
+    request_body = PatchStorePayload,
+    responses(
+        (status = 200, description = "Given fields updated", body = DataBody<StoreToSelect>),
+        (status = 500, description = "Update failed"),
+    )
+)]
+async fn api_v1_patch_store(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    Json(body): Json<PatchStorePayload>,
+) -> Result<Json<DataBody<StoreToSelect>>> {
+    debug!("{:<12} - api_v1_patch_store", "ROUTE_HANDLER");
+
+    let PatchStorePayload {
+        store_name,
+        address,
+    } = body;
+    let data = StoreToUpdate {
+        id,
+        store_name,
+        address,
+    };
+
+    let store = StoreModelController::update(mm, data).await?;
+    let body = Json(DataBody::new(store));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/foods/{id}/stocks",
+    tag = "stores",
+    params(("id" = i64, Path, description = "Food id")),
+    responses(
+        (status = 200, description = "Stock held by each store", body = DataBody<Vec<FoodStoreStock>>),
+        (status = 500, description = "Select failed"),
+    )
+)]
+async fn api_v1_food_stocks(
+    State(mm): State<ModelController>,
+    Path(food_id): Path<i64>,
+) -> Result<Json<DataBody<Vec<FoodStoreStock>>>> {
+    debug!("{:<12} - api_v1_food_stocks", "ROUTE_HANDLER");
+
+    let stocks = StoreModelController::food_stocks(mm, food_id).await?;
+    let body = Json(DataBody::new(stocks));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/stock-transfers",
+    tag = "stores",
+    request_body = StockTransferPayload,
+    responses(
+        (status = 200, description = "Stock moved, new levels of both stores", body = DataBody<StockTransferred>),
+        (status = 400, description = "Quantity not positive or same store on both ends"),
+        (status = 404, description = "Store not found"),
+        (status = 409, description = "Insufficient stock in the source store"),
+        (status = 500, description = "Transfer failed"),
+    )
+)]
+async fn api_v1_transfer_stock(
+    State(mm): State<ModelController>,
+    Json(body): Json<StockTransferPayload>,
+) -> Result<Json<DataBody<StockTransferred>>> {
+    debug!("{:<12} - api_v1_transfer_stock", "ROUTE_HANDLER");
+
+    let StockTransferPayload {
+        food_id,
+        from_store_id,
+        to_store_id,
+        quantity,
+    } = body;
+    let data = StockTransfer {
+        food_id,
+        from_store_id,
+        to_store_id,
+        quantity,
+    };
+
+    let transferred = StoreModelController::transfer(mm, data).await?;
+    let body = Json(DataBody::new(transferred));
+    Ok(body)
+}
@@ -0,0 +1,58 @@
+//! How food timestamps are shown.
+//!
+//! `created_at` and `updated_at` are RFC 3339, at the offset of the IANA
+//! zone a client asks for with `?tz=`, UTC otherwise. The older
+//! `created_date` (`Month DD, YYYY`) is only filled in while
+//! `LEGACY_CREATED_DATE` is on.
+
+use time::OffsetDateTime;
+use time_tz::{timezones, OffsetDateTimeExt, Tz};
+
+use crate::error::{Error, Result};
+
+/// Looks up an IANA zone name such as `Asia/Manila`.
+pub fn parse_tz(name: &str) -> Result<&'static Tz> {
+    timezones::get_by_name(name).ok_or_else(|| Error::InvalidTimezone(name.to_string()))
+}
+
+/// The same instant at `tz`'s offset, UTC when no zone is given.
+pub fn in_tz(at: OffsetDateTime, tz: Option<&Tz>) -> OffsetDateTime {
+    match tz {
+        Some(tz) => at.to_timezone(tz),
+        None => at.to_offset(time::UtcOffset::UTC),
+    }
+}
+
+/// Same rendering as `to_char(ctime, 'Month DD, YYYY')`.
+pub fn created_date(ctime: OffsetDateTime) -> String {
+    format!(
+        "{:<9} {:02}, {}",
+        ctime.month().to_string(),
+        ctime.day(),
+        ctime.year()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Date, Month};
+
+    use super::*;
+
+    #[test]
+    fn test_in_tz() {
+        let at = Date::from_calendar_date(2026, Month::October, 19)
+            .unwrap()
+            .with_hms(20, 30, 0)
+            .unwrap()
+            .assume_utc();
+
+        let manila = in_tz(at, Some(parse_tz("Asia/Manila").unwrap()));
+        assert_eq!(manila, at);
+        assert_eq!(manila.offset().whole_hours(), 8);
+        assert_eq!(created_date(manila), "October   20, 2026");
+
+        assert_eq!(in_tz(at, None).offset(), time::UtcOffset::UTC);
+        assert!(parse_tz("Mars/Olympus").is_err());
+    }
+}
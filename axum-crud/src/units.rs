@@ -0,0 +1,160 @@
+//! Units of measure of food quantities.
+//!
+//! `stocks` and `total_quantity` are whole numbers in the food's base unit.
+//! A quantity given in another unit of the same kind (mass, volume, count)
+//! or in cases is converted to the base unit, and must come out whole.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::{Error, Result};
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "food_unit", rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Piece,
+    G,
+    Kg,
+    Ml,
+    Litre,
+    /// A pack of `case_size` base units, never a base unit itself.
+    Case,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    Count,
+    Mass,
+    Volume,
+}
+
+impl Unit {
+    /// Kind of the unit and its size in the smallest unit of that kind.
+    fn scale(self) -> Option<(Kind, i64)> {
+        match self {
+            Unit::Piece => Some((Kind::Count, 1)),
+            Unit::G => Some((Kind::Mass, 1)),
+            Unit::Kg => Some((Kind::Mass, 1000)),
+            Unit::Ml => Some((Kind::Volume, 1)),
+            Unit::Litre => Some((Kind::Volume, 1000)),
+            Unit::Case => None,
+        }
+    }
+
+    /// Rejects a unit a food cannot be counted in.
+    pub fn check_base(self) -> Result<()> {
+        match self {
+            Unit::Case => Err(Error::UnitConversion(String::from(
+                "case is a pack size, not a base unit",
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_case_size(case_size: Option<i32>) -> Result<()> {
+        match case_size {
+            Some(size) if size <= 0 => Err(Error::UnitConversion(String::from(
+                "case_size must be positive",
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Converts `quantity` given in `unit` into `base` units, a case counting
+/// `case_size` base units.
+pub fn to_base(quantity: i32, unit: Unit, base: Unit, case_size: Option<i32>) -> Result<i32> {
+    let converted = match (unit.scale(), base.scale()) {
+        _ if unit == base => i64::from(quantity),
+        (None, Some(_)) => {
+            let case_size =
+                case_size.ok_or(Error::UnitConversion(String::from("food has no case_size")))?;
+            i64::from(quantity) * i64::from(case_size)
+        }
+        (Some((kind, scale)), Some((base_kind, base_scale))) if kind == base_kind => {
+            let smallest = i64::from(quantity) * scale;
+            if smallest % base_scale != 0 {
+                return Err(Error::UnitConversion(format!(
+                    "{quantity} {unit:?} is not a whole number of {base:?}"
+                )));
+            }
+            smallest / base_scale
+        }
+        _ => {
+            return Err(Error::UnitConversion(format!(
+                "cannot convert {unit:?} to {base:?}"
+            )))
+        }
+    };
+
+    i32::try_from(converted)
+        .map_err(|_| Error::UnitConversion(format!("{quantity} {unit:?} is out of range")))
+}
+
+/// Checks a change of a food's base unit. Quantities already counted in
+/// the old unit, in stock, lots, movements or recipes (`in_use`), would be
+/// read in the new one, so the unit is kept then. A non-zero
+/// `total_quantity` must be restated along with the new unit.
+pub fn check_unit_change(
+    current: Unit,
+    unit: Option<Unit>,
+    in_use: bool,
+    total_quantity: i32,
+    new_total_quantity: Option<i32>,
+) -> Result<()> {
+    let Some(unit) = unit.filter(|unit| *unit != current) else {
+        return Ok(());
+    };
+    if in_use {
+        return Err(Error::UnitInUse(format!(
+            "food has stock, lots, movements or recipe uses counted in {current:?}, cannot switch to {unit:?}"
+        )));
+    }
+    if total_quantity != 0 && new_total_quantity.is_none() {
+        return Err(Error::UnitConversion(format!(
+            "total_quantity is counted in {current:?}, give it in {unit:?} along with the new unit"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_base() {
+        assert_eq!(to_base(3, Unit::Kg, Unit::G, None).unwrap(), 3000);
+        assert_eq!(to_base(2000, Unit::G, Unit::Kg, None).unwrap(), 2);
+        assert_eq!(to_base(2, Unit::Litre, Unit::Ml, None).unwrap(), 2000);
+        assert_eq!(to_base(2, Unit::Case, Unit::Piece, Some(24)).unwrap(), 48);
+        assert_eq!(to_base(5, Unit::Piece, Unit::Piece, None).unwrap(), 5);
+
+        assert!(to_base(1500, Unit::G, Unit::Kg, None).is_err());
+        assert!(to_base(1, Unit::Kg, Unit::Litre, None).is_err());
+        assert!(to_base(1, Unit::Case, Unit::Piece, None).is_err());
+        assert!(to_base(i32::MAX, Unit::Kg, Unit::G, None).is_err());
+    }
+
+    #[test]
+    fn test_check_unit_change() {
+        assert!(check_unit_change(Unit::Piece, None, true, 5, None).is_ok());
+        assert!(check_unit_change(Unit::Piece, Some(Unit::Piece), true, 5, None).is_ok());
+        assert!(check_unit_change(Unit::Piece, Some(Unit::G), false, 0, None).is_ok());
+        assert!(check_unit_change(Unit::Piece, Some(Unit::G), false, 5, Some(500)).is_ok());
+
+        assert!(matches!(
+            check_unit_change(Unit::Piece, Some(Unit::G), true, 5, Some(500)),
+            Err(Error::UnitInUse(_))
+        ));
+        assert!(matches!(
+            check_unit_change(Unit::Piece, Some(Unit::G), false, 5, None),
+            Err(Error::UnitConversion(_))
+        ));
+    }
+}
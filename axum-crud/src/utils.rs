@@ -9,6 +9,7 @@ pub fn b32_hex() -> Result<String> {
     Ok(data_encoding::BASE32HEX_NOPAD.encode(uuid.as_bytes()))
 }
 
+#[allow(dead_code)]
 pub fn b64() -> Result<String> {
     let uuid = Uuid::now_v7();
     Ok(data_encoding::BASE64.encode(uuid.as_bytes()))
@@ -20,6 +21,7 @@ pub fn b64u() -> Result<String> {
     Ok(data_encoding::BASE64_NOPAD.encode(uuid.as_bytes()))
 }
 
+#[allow(dead_code)]
 pub fn b58() -> Result<String> {
     let uuid = Uuid::now_v7();
     Ok(uuid.as_bytes().to_base58())
@@ -0,0 +1,707 @@
+//! Outbound webhooks for food events.
+//!
+//! `food.created`, `food.updated`, `food.removed` and `food.out_of_stock`
+//! are queued by triggers on `foods_table` and `food_stocks`, in the
+//! transaction making the change, as one delivery per subscription that
+//! wants the event. `spawn_dispatcher` POSTs the due deliveries, the JSON
+//! event as body, with these headers:
+//!
+//! - `X-Webhook-Id`: the event id, the same for every subscription
+//! - `X-Webhook-Event`: the event type
+//! - `X-Webhook-Timestamp`: Unix seconds at which the attempt was signed
+//! - `X-Webhook-Signature`: `sha256=` followed by the hex HMAC-SHA256 of
+//!   `{timestamp}.{body}`, keyed with the subscription's secret
+//!
+//! A 2xx answer delivers the event. Anything else is retried after
+//! `WEBHOOK_BACKOFF_BASE_SEC`, doubling up to `WEBHOOK_BACKOFF_MAX_SEC`.
+//! After `WEBHOOK_MAX_ATTEMPTS` attempts the delivery is dead until it is
+//! requeued.
+//!
+//! Targets must be public: a URL whose host is, or resolves to, a loopback,
+//! private, link-local (cloud metadata at `169.254.169.254` included) or
+//! otherwise internal address is refused when subscribing, and checked
+//! again when connecting, so a name re-pointed at an internal address after
+//! the fact is not followed. `WEBHOOK_ALLOW_PRIVATE_TARGETS` lifts this for
+//! local testing.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use base58::ToBase58;
+use hmac::{Hmac, Mac};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Client, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::prelude::FromRow;
+use time::OffsetDateTime;
+use tokio::{
+    net::lookup_host,
+    sync::watch,
+    task::{JoinHandle, JoinSet},
+    time::sleep,
+};
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    config::core_config,
+    crud_fns::ModelController,
+    error::{Error, Result},
+    metrics::metrics,
+};
+
+/// Webhooks are kept in Postgres only, the in-memory backend has none.
+#[derive(Clone, Debug)]
+pub struct WebhookModelController;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub enum WebhookEvent {
+    #[serde(rename = "food.created")]
+    FoodCreated,
+    #[serde(rename = "food.updated")]
+    FoodUpdated,
+    #[serde(rename = "food.removed")]
+    FoodRemoved,
+    /// The food's own stock, or a store's, reached zero.
+    #[serde(rename = "food.out_of_stock")]
+    FoodOutOfStock,
+}
+
+impl WebhookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::FoodCreated => "food.created",
+            WebhookEvent::FoodUpdated => "food.updated",
+            WebhookEvent::FoodRemoved => "food.removed",
+            WebhookEvent::FoodOutOfStock => "food.out_of_stock",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Dead,
+}
+
+impl DeliveryStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Dead => "dead",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookToCreate {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    /// Generated when absent.
+    pub secret: Option<String>,
+}
+
+/// A subscription as listed, without its secret.
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct WebhookToSelect {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+    /// RFC 3339, UTC.
+    pub created_at: String,
+}
+
+/// A new subscription. `secret` is shown only this once.
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct WebhookCreated {
+    pub secret: String,
+    #[serde(flatten)]
+    pub webhook: WebhookToSelect,
+}
+
+#[derive(Debug, FromRow, Deserialize, Serialize, ToSchema)]
+pub struct DeliveryToSelect {
+    pub id: i64,
+    pub subscription_id: i64,
+    pub event_id: String,
+    pub event_type: String,
+    /// The body sent.
+    #[schema(value_type = Object)]
+    pub payload: Value,
+    /// `pending`, `delivered` or `dead`.
+    pub delivery_status: String,
+    pub attempts: i32,
+    /// RFC 3339, UTC. Only while pending.
+    pub next_attempt_at: Option<String>,
+    pub last_attempt_at: Option<String>,
+    /// HTTP status of the last answer, absent when none came.
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<String>,
+    pub created_at: String,
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, events, to_char(ctime at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as created_at";
+
+const DELIVERY_COLUMNS: &str = "id, subscription_id, event_id::text as event_id, event_type, payload, delivery_status::text as delivery_status, attempts, case when delivery_status = 'pending' then to_char(next_attempt_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') end as next_attempt_at, to_char(last_attempt_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as last_attempt_at, last_status_code, last_error, to_char(delivered_at at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as delivered_at, to_char(ctime at time zone 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') as created_at";
+
+/// Shortest secret accepted from a caller.
+const MIN_SECRET_LEN: usize = 16;
+
+/// Deliveries claimed by one dispatcher pass.
+const BATCH: i64 = 32;
+
+/// Added to `WEBHOOK_TIMEOUT_MS` for the time an attempt keeps its delivery
+/// away from other replicas.
+const LEASE_MARGIN: Duration = Duration::from_secs(30);
+
+/// Characters of an error kept on the delivery.
+const ERROR_LEN: usize = 500;
+
+/// `whsec_` followed by two v4 UUIDs, 244 random bits as each UUID fixes 6
+/// version and variant bits.
+fn new_secret() -> String {
+    let mut secret = [0u8; 32];
+    secret[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    secret[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+
+    format!("whsec_{}", secret.to_base58())
+}
+
+impl WebhookModelController {
+    pub async fn create(mm: ModelController, data: WebhookToCreate) -> Result<WebhookCreated> {
+        debug!("{:<12} - create_webhook", "HANDLER");
+
+        match Url::parse(&data.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                check_target(&url).await.map_err(Error::InvalidWebhook)?
+            }
+            _ => {
+                return Err(Error::InvalidWebhook(format!(
+                    "{} is not an http(s) URL",
+                    data.url
+                )))
+            }
+        }
+        let mut events: Vec<&str> = data.events.iter().map(|event| event.as_str()).collect();
+        events.sort_unstable();
+        events.dedup();
+        if events.is_empty() {
+            return Err(Error::InvalidWebhook(String::from(
+                "at least one event is required",
+            )));
+        }
+        let secret = match data.secret {
+            Some(secret) if secret.len() < MIN_SECRET_LEN => {
+                return Err(Error::InvalidWebhook(format!(
+                    "secret must be at least {MIN_SECRET_LEN} characters"
+                )))
+            }
+            Some(secret) => secret,
+            None => new_secret(),
+        };
+
+        let timer = metrics().query_timer("create_webhook");
+        let query = format!("insert into webhook_subscriptions (url, secret, events) values ($1, $2, $3) returning {WEBHOOK_COLUMNS}");
+
+        let res = sqlx::query_as::<_, WebhookToSelect>(&query)
+            .bind(data.url)
+            .bind(&secret)
+            .bind(events)
+            .fetch_one(mm.db()?)
+            .await
+            .map(|webhook| WebhookCreated { secret, webhook })
+            .map_err(|err| {
+                debug!("{:<12} - create_webhook error", "ERROR_CONTROLLER");
+                Error::CreateFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    pub async fn select(mm: ModelController) -> Result<Vec<WebhookToSelect>> {
+        debug!("{:<12} - select_webhooks", "HANDLER");
+
+        let timer = metrics().query_timer("select_webhooks");
+        let query = format!("select {WEBHOOK_COLUMNS} from webhook_subscriptions order by id");
+
+        let res = sqlx::query_as::<_, WebhookToSelect>(&query)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!("{:<12} - select_webhooks error", "ERROR_CONTROLLER");
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// Drops the subscription along with its deliveries, sent or not.
+    pub async fn delete(mm: ModelController, id: i64) -> Result<WebhookToSelect> {
+        debug!("{:<12} - delete_webhook", "HANDLER");
+
+        let timer = metrics().query_timer("delete_webhook");
+        let query =
+            format!("delete from webhook_subscriptions where id = $1 returning {WEBHOOK_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, WebhookToSelect>(&query)
+            .bind(id)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(webhook)) => Ok(webhook),
+            Ok(None) => Err(Error::WebhookNotFound(format!("no webhook with id {id}"))),
+            Err(err) => {
+                debug!("{:<12} - delete_webhook error", "ERROR_CONTROLLER");
+                Err(Error::DeleteFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+
+    /// A subscription's deliveries, newest first.
+    pub async fn deliveries(
+        mm: ModelController,
+        subscription_id: i64,
+        status: Option<DeliveryStatus>,
+        limit: i64,
+    ) -> Result<Vec<DeliveryToSelect>> {
+        debug!("{:<12} - select_webhook_deliveries", "HANDLER");
+
+        let timer = metrics().query_timer("select_webhook_deliveries");
+        let query = format!("select {DELIVERY_COLUMNS} from webhook_deliveries where subscription_id = $1 and ($2::text is null or delivery_status::text = $2) order by id desc limit $3");
+
+        let res = sqlx::query_as::<_, DeliveryToSelect>(&query)
+            .bind(subscription_id)
+            .bind(status.map(DeliveryStatus::as_str))
+            .bind(limit)
+            .fetch_all(mm.db()?)
+            .await
+            .map_err(|err| {
+                debug!(
+                    "{:<12} - select_webhook_deliveries error",
+                    "ERROR_CONTROLLER"
+                );
+                Error::SelectFailed(err.to_string())
+            });
+        timer.observe(res)
+    }
+
+    /// Puts a dead delivery back in the queue with a fresh set of attempts.
+    pub async fn requeue(
+        mm: ModelController,
+        subscription_id: i64,
+        id: i64,
+    ) -> Result<DeliveryToSelect> {
+        debug!("{:<12} - requeue_webhook_delivery", "HANDLER");
+
+        let timer = metrics().query_timer("requeue_webhook_delivery");
+        let query = format!("update webhook_deliveries set delivery_status = 'pending', attempts = 0, next_attempt_at = now() where id = $1 and subscription_id = $2 and delivery_status = 'dead' returning {DELIVERY_COLUMNS}");
+
+        let res = match sqlx::query_as::<_, DeliveryToSelect>(&query)
+            .bind(id)
+            .bind(subscription_id)
+            .fetch_optional(mm.db()?)
+            .await
+        {
+            Ok(Some(delivery)) => Ok(delivery),
+            Ok(None) => Err(Error::WebhookDeliveryNotFound(format!(
+                "no dead delivery {id} of webhook {subscription_id}"
+            ))),
+            Err(err) => {
+                debug!(
+                    "{:<12} - requeue_webhook_delivery error",
+                    "ERROR_CONTROLLER"
+                );
+                Err(Error::UpdateFailed(err.to_string()))
+            }
+        };
+        timer.observe(res)
+    }
+}
+
+/// Stops the dispatcher between passes.
+pub struct Dispatcher {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Dispatcher {
+    /// Waits for the attempts in flight to finish and be recorded.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+    }
+}
+
+/// Sends the due deliveries every `WEBHOOK_POLL_INTERVAL_MS`, right away
+/// again while a pass finds a full batch. Replicas share the queue.
+pub fn spawn_dispatcher(mm: ModelController) -> Dispatcher {
+    let (stop, mut stopped) = watch::channel(false);
+
+    let task = tokio::spawn(async move {
+        if mm.db().is_err() {
+            return;
+        }
+        let config = core_config();
+        let every = Duration::from_millis(config.WEBHOOK_POLL_INTERVAL_MS);
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.WEBHOOK_TIMEOUT_MS))
+            .redirect(redirect::Policy::none())
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver))
+            .user_agent(concat!("axum-crud-webhooks/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .unwrap_or_else(|ex| {
+                panic!("FATAL - while building the webhook client - error: {ex:?}")
+            });
+
+        loop {
+            let claimed = match dispatch_due(&mm, &client).await {
+                Ok(claimed) => claimed,
+                Err(err) => {
+                    warn!("{:<12} - dispatch failed {err:?}", "WEBHOOKS");
+                    0
+                }
+            };
+            if *stopped.borrow() {
+                return;
+            }
+            if claimed == BATCH as usize {
+                continue;
+            }
+
+            tokio::select! {
+                _ = sleep(every) => {}
+                _ = stopped.changed() => return,
+            }
+        }
+    });
+
+    Dispatcher { stop, task }
+}
+
+#[derive(Debug, FromRow)]
+struct Claimed {
+    id: i64,
+    event_id: String,
+    event_type: String,
+    body: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+/// What came of an attempt: the HTTP status, if any, and the error unless
+/// it was delivered.
+type Attempt = (Option<u16>, Option<String>);
+
+/// Claims a batch of due deliveries, sends them concurrently and records
+/// each outcome. Returns the number claimed.
+async fn dispatch_due(mm: &ModelController, client: &Client) -> Result<usize> {
+    let db = mm.db()?;
+    let lease = Duration::from_millis(core_config().WEBHOOK_TIMEOUT_MS) + LEASE_MARGIN;
+
+    let query = "update webhook_deliveries d set attempts = d.attempts + 1, last_attempt_at = now(), next_attempt_at = now() + make_interval(secs => $2) from webhook_subscriptions s where s.id = d.subscription_id and d.id in (select id from webhook_deliveries where delivery_status = 'pending' and next_attempt_at <= now() order by next_attempt_at, id limit $1 for update skip locked) returning d.id, d.event_id::text as event_id, d.event_type, d.payload::text as body, d.attempts, s.url, s.secret";
+    let claimed = sqlx::query_as::<_, Claimed>(query)
+        .bind(BATCH)
+        .bind(lease.as_secs_f64())
+        .fetch_all(db)
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+    let count = claimed.len();
+
+    let mut attempts = JoinSet::new();
+    for delivery in claimed {
+        let client = client.clone();
+        attempts.spawn(async move {
+            let attempt = send(&client, &delivery).await;
+            (delivery, attempt)
+        });
+    }
+    while let Some(done) = attempts.join_next().await {
+        let Ok((delivery, attempt)) = done else {
+            continue;
+        };
+        // -- One failed write leaves that delivery to its lease, the others
+        //    are still recorded.
+        if let Err(err) = record(mm, &delivery, attempt).await {
+            warn!(
+                "{:<12} - delivery {} not recorded {err:?}",
+                "WEBHOOKS", delivery.id
+            );
+        }
+    }
+
+    Ok(count)
+}
+
+async fn send(client: &Client, delivery: &Claimed) -> Attempt {
+    // -- The resolver only sees host names, an address in the URL is
+    //    checked here.
+    let target = match Url::parse(&delivery.url) {
+        Ok(url) => check_target(&url).await,
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = target {
+        return (None, Some(err));
+    }
+
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+    let signature = sign(&delivery.secret, timestamp, &delivery.body);
+
+    let res = client
+        .post(&delivery.url)
+        .header("content-type", "application/json")
+        .header("x-webhook-id", &delivery.event_id)
+        .header("x-webhook-event", &delivery.event_type)
+        .header("x-webhook-timestamp", timestamp.to_string())
+        .header("x-webhook-signature", signature)
+        .body(delivery.body.clone())
+        .send()
+        .await;
+
+    match res {
+        Ok(res) if res.status().is_success() => (Some(res.status().as_u16()), None),
+        Ok(res) => (
+            Some(res.status().as_u16()),
+            Some(format!("answered {}", res.status())),
+        ),
+        Err(err) => (None, Some(err.to_string())),
+    }
+}
+
+async fn record(mm: &ModelController, delivery: &Claimed, attempt: Attempt) -> Result<()> {
+    let config = core_config();
+    let (status_code, error) = attempt;
+    let status_code = status_code.map(i32::from);
+
+    let outcome = match &error {
+        None => "delivered",
+        Some(_) if delivery.attempts >= config.WEBHOOK_MAX_ATTEMPTS => "dead",
+        Some(_) => "retry",
+    };
+    let error = error.map(|error| error.chars().take(ERROR_LEN).collect::<String>());
+    let retry_in = backoff(
+        delivery.attempts,
+        Duration::from_secs(config.WEBHOOK_BACKOFF_BASE_SEC),
+        Duration::from_secs(config.WEBHOOK_BACKOFF_MAX_SEC),
+    );
+
+    let query = "update webhook_deliveries set delivery_status = case $2 when 'retry' then 'pending' else $2 end::webhook_delivery_stat, last_status_code = $3, last_error = $4, delivered_at = case $2 when 'delivered' then now() end, next_attempt_at = case $2 when 'retry' then now() + make_interval(secs => $5) else next_attempt_at end where id = $1";
+    sqlx::query(query)
+        .bind(delivery.id)
+        .bind(outcome)
+        .bind(status_code)
+        .bind(&error)
+        .bind(retry_in.as_secs_f64())
+        .execute(mm.db()?)
+        .await
+        .map_err(|err| Error::UpdateFailed(err.to_string()))?;
+
+    metrics().webhook_attempt(outcome);
+    match error {
+        Some(error) => warn!(
+            "{:<12} - delivery {} attempt {} {outcome}: {error}",
+            "WEBHOOKS", delivery.id, delivery.attempts
+        ),
+        None => debug!("{:<12} - delivery {} delivered", "WEBHOOKS", delivery.id),
+    }
+
+    Ok(())
+}
+
+/// Refuses a URL whose host is, or resolves to, an address webhooks may not
+/// reach.
+async fn check_target(url: &Url) -> std::result::Result<(), String> {
+    let Some(host) = url.host_str() else {
+        return Err(format!("{url} has no host"));
+    };
+    // An IPv6 host comes in brackets.
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) if allowed(ip) => Ok(()),
+        Ok(ip) => Err(format!("{ip} is not a public address")),
+        Err(_) => resolve(host).await.map(|_| ()),
+    }
+}
+
+/// The addresses of `host`, all of them allowed, or an error.
+async fn resolve(host: &str) -> std::result::Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = lookup_host((host, 0))
+        .await
+        .map_err(|err| format!("{host} does not resolve: {err}"))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{host} does not resolve"));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !allowed(addr.ip())) {
+        return Err(format!(
+            "{host} resolves to {}, which is not a public address",
+            addr.ip()
+        ));
+    }
+
+    Ok(addrs)
+}
+
+fn allowed(ip: IpAddr) -> bool {
+    is_public(ip) || core_config().WEBHOOK_ALLOW_PRIVATE_TARGETS
+}
+
+/// False for loopback, private, link-local, shared, unspecified, broadcast,
+/// multicast and documentation addresses, IPv4 or IPv6.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // 0.0.0.0/8 and the carrier-grade NAT 100.64.0.0/10.
+                || a == 0
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    // NAT64 embeds an IPv4 address in its last 32 bits.
+                    || (ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                        && !is_public(Ipv4Addr::from_bits(ip.to_bits() as u32).into())))
+            }
+        },
+    }
+}
+
+/// Resolves delivery hosts at connect time, refusing the ones `resolve`
+/// refuses, so the address checked is the address connected to.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolve(&host).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    format!(
+        "sha256={}",
+        data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes())
+    )
+}
+
+/// Wait after the `attempts`-th failed attempt: `base`, doubled for each
+/// attempt before it, at most `max`.
+fn backoff(attempts: i32, base: Duration, max: Duration) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 31) as u32;
+
+    base.saturating_mul(2u32.saturating_pow(doublings)).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        let internal = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ];
+        for ip in internal {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn internal_targets_are_refused(db: PgPool) {
+        let mm = ModelController::with_db(db);
+
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/hook",
+            "http://localhost/hook",
+        ] {
+            let data = WebhookToCreate {
+                url: url.to_string(),
+                events: vec![WebhookEvent::FoodCreated],
+                secret: None,
+            };
+            let res = WebhookModelController::create(mm.clone(), data).await;
+            assert!(matches!(res, Err(Error::InvalidWebhook(_))), "{url}");
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let base = Duration::from_secs(10);
+        let max = Duration::from_secs(3600);
+
+        let waits: Vec<u64> = (1..=5)
+            .map(|attempts| backoff(attempts, base, max).as_secs())
+            .collect();
+        assert_eq!(waits, [10, 20, 40, 80, 160]);
+        assert_eq!(backoff(12, base, max), max);
+        assert_eq!(backoff(1_000, base, max), max);
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let body = r#"{"type":"food.created"}"#;
+        let signature = sign("whsec_test", 1_700_000_000, body);
+
+        let hex = signature.strip_prefix("sha256=").unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"whsec_test").unwrap();
+        mac.update(format!("1700000000.{body}").as_bytes());
+        mac.verify_slice(&data_encoding::HEXLOWER.decode(hex.as_bytes()).unwrap())
+            .unwrap();
+
+        assert_ne!(signature, sign("whsec_test", 1_700_000_001, body));
+        assert_ne!(signature, sign("whsec_other", 1_700_000_000, body));
+    }
+}
@@ -0,0 +1,194 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use tracing::debug;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::mw_require_admin,
+    crud_fns::ModelController,
+    crud_routes::DataBody,
+    error::Result,
+    webhooks_fns::{
+        DeliveryStatus, DeliveryToSelect, WebhookCreated, WebhookEvent, WebhookModelController,
+        WebhookToCreate, WebhookToSelect,
+    },
+};
+
+/// Deliveries listed when no `limit` is given.
+const DEFAULT_DELIVERIES: i64 = 50;
+
+pub fn routes_webhooks(mm: ModelController) -> Router {
+    Router::new()
+        .route(
+            "/api/v1/admin/webhooks",
+            get(api_v1_list_webhooks).post(api_v1_create_webhook),
+        )
+        .route("/api/v1/admin/webhooks/:id", delete(api_v1_delete_webhook))
+        .route(
+            "/api/v1/admin/webhooks/:id/deliveries",
+            get(api_v1_list_webhook_deliveries),
+        )
+        .route(
+            "/api/v1/admin/webhooks/:id/deliveries/:delivery_id/retry",
+            post(api_v1_retry_webhook_delivery),
+        )
+        .route_layer(middleware::from_fn_with_state(mm.clone(), mw_require_admin))
+        .with_state(mm)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct CreateWebhookPayload {
+    /// http(s) endpoint the events are POSTed to.
+    url: String,
+    events: Vec<WebhookEvent>,
+    /// At least 16 characters, generated when absent.
+    secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct DeliveriesQuery {
+    status: Option<DeliveryStatus>,
+    /// Deliveries to return, newest first, 50 by default.
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Subscriptions, without their secrets", body = DataBody<Vec<WebhookToSelect>>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+    )
+)]
+async fn api_v1_list_webhooks(
+    State(mm): State<ModelController>,
+) -> Result<Json<DataBody<Vec<WebhookToSelect>>>> {
+    debug!("{:<12} - api_v1_list_webhooks", "ROUTE_HANDLER");
+
+    let webhooks = WebhookModelController::select(mm).await?;
+    let body = Json(DataBody::new(webhooks));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhookPayload,
+    responses(
+        (status = 201, description = "Subscribed, `secret` is shown only this once", body = DataBody<WebhookCreated>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+        (status = 400, description = "Invalid URL, events or secret"),
+    )
+)]
+async fn api_v1_create_webhook(
+    State(mm): State<ModelController>,
+    Json(body): Json<CreateWebhookPayload>,
+) -> Result<Response> {
+    debug!("{:<12} - api_v1_create_webhook", "ROUTE_HANDLER");
+
+    let CreateWebhookPayload {
+        url,
+        events,
+        secret,
+    } = body;
+    let data = WebhookToCreate {
+        url,
+        events,
+        secret,
+    };
+
+    let created = WebhookModelController::create(mm, data).await?;
+    let body = Json(DataBody::new(created));
+
+    // The secret must not end up in shared caches.
+    Ok((
+        StatusCode::CREATED,
+        [(header::CACHE_CONTROL, "no-store")],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i64, Path, description = "Webhook id")),
+    responses(
+        (status = 200, description = "Unsubscribed, pending deliveries are dropped", body = DataBody<WebhookToSelect>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+        (status = 404, description = "Webhook not found"),
+    )
+)]
+async fn api_v1_delete_webhook(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+) -> Result<Json<DataBody<WebhookToSelect>>> {
+    debug!("{:<12} - api_v1_delete_webhook", "ROUTE_HANDLER");
+
+    let webhook = WebhookModelController::delete(mm, id).await?;
+    let body = Json(DataBody::new(webhook));
+    Ok(body)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(("id" = i64, Path, description = "Webhook id"), DeliveriesQuery),
+    responses(
+        (status = 200, description = "Delivery log, newest first", body = DataBody<Vec<DeliveryToSelect>>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+    )
+)]
+async fn api_v1_list_webhook_deliveries(
+    State(mm): State<ModelController>,
+    Path(id): Path<i64>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<DataBody<Vec<DeliveryToSelect>>>> {
+    debug!("{:<12} - api_v1_list_webhook_deliveries", "ROUTE_HANDLER");
+
+    let limit = query.limit.map_or(DEFAULT_DELIVERIES, i64::from);
+    let deliveries = WebhookModelController::deliveries(mm, id, query.status, limit).await?;
+    let body = Json(DataBody::new(deliveries));
+    Ok(body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/webhooks/{id}/deliveries/{delivery_id}/retry",
+    tag = "webhooks",
+    params(
+        ("id" = i64, Path, description = "Webhook id"),
+        ("delivery_id" = i64, Path, description = "Delivery id"),
+    ),
+    responses(
+        (status = 200, description = "Dead delivery queued again", body = DataBody<DeliveryToSelect>),
+        (status = 401, description = "Missing or invalid API key"),
+        (status = 403, description = "API key lacks the admin scope"),
+        (status = 404, description = "No dead delivery with that id"),
+    )
+)]
+async fn api_v1_retry_webhook_delivery(
+    State(mm): State<ModelController>,
+    Path((id, delivery_id)): Path<(i64, i64)>,
+) -> Result<Json<DataBody<DeliveryToSelect>>> {
+    debug!("{:<12} - api_v1_retry_webhook_delivery", "ROUTE_HANDLER");
+
+    let delivery = WebhookModelController::requeue(mm, id, delivery_id).await?;
+    let body = Json(DataBody::new(delivery));
+    Ok(body)
+}
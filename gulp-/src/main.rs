@@ -9,7 +9,7 @@ mod model;
 mod web;
 
 use crate::ctx::Ctx;
-use std::net::SocketAddr;
+use std::{future::IntoFuture, io::Write, net::SocketAddr, time::Duration};
 
 use crate::{log::log_request, model::ModelController};
 use axum::{
@@ -22,6 +22,7 @@ use axum::{
 };
 use serde::Deserialize;
 use serde_json::json;
+use tokio::{signal, sync::oneshot};
 use tower_cookies::CookieManagerLayer;
 use tower_http::services::ServeDir;
 use uuid::Uuid;
@@ -51,7 +52,21 @@ async fn main() -> Result<()> {
     let addr = tokio::net::TcpListener::bind(host_addr).await.unwrap();
     println!("Listening on http://{}", host_addr);
 
-    axum::serve(addr, app).await.unwrap();
+    let (drain_tx, drain_rx) = oneshot::channel();
+    let server = axum::serve(addr, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        let _ = drain_tx.send(());
+    });
+
+    tokio::select! {
+        res = server.into_future() => res.unwrap(),
+        _ = drain_deadline(drain_rx) => {
+            println!("->> {:<12} - dropping remaining connections", "SHUTDOWN");
+        }
+    }
+
+    // Flush pending request logs before exit.
+    let _ = std::io::stdout().flush();
     // end region ---- Start server
 
     Ok(())
@@ -96,6 +111,52 @@ async fn main_response_mapper(
     error_response.unwrap_or(res)
 }
 
+// region: ---- Graceful shutdown
+// A trimmed copy of axum-crud's `shutdown.rs`, the two crates share no library.
+
+// Resolves on Ctrl+C (SIGINT) or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    println!("->> {:<12} - signal received, stop accepting", "SHUTDOWN");
+}
+
+// Resolves `SHUTDOWN_TIMEOUT_SEC` (default 30) after the shutdown signal,
+// never if the server stops first.
+async fn drain_deadline(started: oneshot::Receiver<()>) {
+    if started.await.is_err() {
+        return std::future::pending().await;
+    }
+
+    let secs = std::env::var("SHUTDOWN_TIMEOUT_SEC")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30);
+    println!(
+        "->> {:<12} - draining in-flight requests ({secs}s)",
+        "SHUTDOWN"
+    );
+    tokio::time::sleep(Duration::from_secs(secs)).await;
+}
+// endregion: ---- Graceful shutdown
+
 // region: ---- Static Routes
 fn fn_static() -> Router {
     Router::new().nest_service("/", get_service(ServeDir::new("./")))